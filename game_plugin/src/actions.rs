@@ -0,0 +1,339 @@
+use bevy::prelude::*;
+use bevy::input::InputSystem;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::{game_controller, game_settings, locale, replay};
+
+pub static BINDINGS_FILE: &str = "bindings.ron";
+
+// how far the stick has to be pushed before it counts as pressing a direction in the menus
+static STICK_PRESS_THRESHOLD: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Action,
+    Switch,
+    Gadget,
+    CycleGadget,
+    Sneak,
+    Sprint,
+    Command,
+    Formation,
+    Hold,
+    Pause,
+    Confirm,
+    Back,
+    FastForward,
+    SkipCutscene,
+    Backlog,
+}
+
+impl GameAction {
+    // a string id, see locale.rs
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameAction::MoveUp => "action.move_up",
+            GameAction::MoveDown => "action.move_down",
+            GameAction::MoveLeft => "action.move_left",
+            GameAction::MoveRight => "action.move_right",
+            GameAction::Action => "action.action",
+            GameAction::Switch => "action.switch",
+            GameAction::Gadget => "action.gadget",
+            GameAction::CycleGadget => "action.cycle_gadget",
+            GameAction::Sneak => "action.sneak",
+            GameAction::Sprint => "action.sprint",
+            GameAction::Command => "action.command",
+            GameAction::Formation => "action.formation",
+            GameAction::Hold => "action.hold",
+            GameAction::Pause => "action.pause",
+            GameAction::Confirm => "action.confirm",
+            GameAction::Back => "action.back",
+            GameAction::FastForward => "action.fast_forward",
+            GameAction::SkipCutscene => "action.skip_cutscene",
+            GameAction::Backlog => "action.backlog",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    // keys go by what's printed on them, everything else comes from the string table
+    pub fn name(&self, strings: &locale::Strings) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => {
+                let button = match button {
+                                 MouseButton::Left => strings.get("mouse.left"),
+                                 MouseButton::Right => strings.get("mouse.right"),
+                                 MouseButton::Middle => strings.get("mouse.middle"),
+                                 MouseButton::Other(n) => n.to_string(),
+                             };
+                strings.get_with("binding.mouse", &[&button])
+            },
+            Binding::Gamepad(button) => strings.get_with("binding.pad", &[&strings.get(pad_button_name(*button))]),
+        }
+    }
+}
+
+fn pad_button_name(button: GamepadButtonType) -> &'static str {
+    match button {
+        GamepadButtonType::South => "pad.south",
+        GamepadButtonType::East => "pad.east",
+        GamepadButtonType::North => "pad.north",
+        GamepadButtonType::West => "pad.west",
+        GamepadButtonType::C => "pad.c",
+        GamepadButtonType::Z => "pad.z",
+        GamepadButtonType::LeftTrigger => "pad.left_bumper",
+        GamepadButtonType::LeftTrigger2 => "pad.left_trigger",
+        GamepadButtonType::RightTrigger => "pad.right_bumper",
+        GamepadButtonType::RightTrigger2 => "pad.right_trigger",
+        GamepadButtonType::Select => "pad.select",
+        GamepadButtonType::Start => "pad.start",
+        GamepadButtonType::Mode => "pad.mode",
+        GamepadButtonType::LeftThumb => "pad.left_stick",
+        GamepadButtonType::RightThumb => "pad.right_stick",
+        GamepadButtonType::DPadUp => "pad.dpad_up",
+        GamepadButtonType::DPadDown => "pad.dpad_down",
+        GamepadButtonType::DPadLeft => "pad.dpad_left",
+        GamepadButtonType::DPadRight => "pad.dpad_right",
+    }
+}
+
+// this is what gets saved to the bindings file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActionMap {
+    pub bindings: Vec::<(GameAction, Vec::<Binding>)>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        ActionMap {
+            bindings: vec!(
+                (GameAction::MoveUp, vec!(Binding::Key(KeyCode::W), Binding::Key(KeyCode::Up),
+                                          Binding::Gamepad(GamepadButtonType::DPadUp))),
+                (GameAction::MoveDown, vec!(Binding::Key(KeyCode::S), Binding::Key(KeyCode::Down),
+                                            Binding::Gamepad(GamepadButtonType::DPadDown))),
+                (GameAction::MoveLeft, vec!(Binding::Key(KeyCode::A), Binding::Key(KeyCode::Left),
+                                            Binding::Gamepad(GamepadButtonType::DPadLeft))),
+                (GameAction::MoveRight, vec!(Binding::Key(KeyCode::D), Binding::Key(KeyCode::Right),
+                                             Binding::Gamepad(GamepadButtonType::DPadRight))),
+                (GameAction::Action, vec!(Binding::Key(KeyCode::J), Binding::Key(KeyCode::Space),
+                                          Binding::Key(KeyCode::Return), Binding::Gamepad(GamepadButtonType::South))),
+                (GameAction::Switch, vec!(Binding::Key(KeyCode::K), Binding::Gamepad(GamepadButtonType::East))),
+                (GameAction::Gadget, vec!(Binding::Key(KeyCode::L), Binding::Gamepad(GamepadButtonType::North))),
+                (GameAction::CycleGadget, vec!(Binding::Key(KeyCode::U), Binding::Gamepad(GamepadButtonType::West))),
+                (GameAction::Sneak, vec!(Binding::Key(KeyCode::LControl), Binding::Key(KeyCode::C),
+                                         Binding::Gamepad(GamepadButtonType::LeftTrigger2))),
+                (GameAction::Sprint, vec!(Binding::Key(KeyCode::LShift), Binding::Gamepad(GamepadButtonType::RightTrigger2))),
+                (GameAction::Command, vec!(Binding::Key(KeyCode::Tab), Binding::Gamepad(GamepadButtonType::Select))),
+                (GameAction::Formation, vec!(Binding::Key(KeyCode::X), Binding::Gamepad(GamepadButtonType::LeftThumb))),
+                (GameAction::Hold, vec!(Binding::Key(KeyCode::Z), Binding::Gamepad(GamepadButtonType::RightThumb))),
+                (GameAction::Pause, vec!(Binding::Key(KeyCode::Escape), Binding::Gamepad(GamepadButtonType::Start))),
+                (GameAction::Confirm, vec!(Binding::Key(KeyCode::Return), Binding::Key(KeyCode::Space),
+                                           Binding::Gamepad(GamepadButtonType::South))),
+                (GameAction::Back, vec!(Binding::Key(KeyCode::Escape), Binding::Key(KeyCode::Back),
+                                        Binding::Mouse(MouseButton::Right), Binding::Gamepad(GamepadButtonType::East))),
+                // nothing here can share a binding with gameplay, a held sprint would fast forward whatever it walked into
+                (GameAction::FastForward, vec!(Binding::Key(KeyCode::E), Binding::Gamepad(GamepadButtonType::RightTrigger))),
+                (GameAction::SkipCutscene, vec!(Binding::Key(KeyCode::End), Binding::Gamepad(GamepadButtonType::LeftTrigger))),
+                // every pad button is taken, it can be given one on the controls screen
+                (GameAction::Backlog, vec!(Binding::Key(KeyCode::H))),
+            ),
+        }
+    }
+}
+
+impl ActionMap {
+    pub fn load() -> Self {
+        match crate::load_ron_file::<ActionMap>(BINDINGS_FILE) {
+            Some(mut action_map) => {
+                // anything missing from an older file gets its default
+                for (action, bindings) in ActionMap::default().bindings {
+                    if action_map.get(action).is_none() {
+                        action_map.bindings.push((action, bindings));
+                    }
+                }
+                action_map
+            },
+            None => ActionMap::default()
+        }
+    }
+
+    pub fn save(&self) {
+        crate::save_ron_file(BINDINGS_FILE, self);
+    }
+
+    pub fn get(&self, action: GameAction) -> Option::<&Vec::<Binding>> {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, bindings)| bindings)
+    }
+
+    // swaps out the one binding in that slot, a slot past the end adds a new one
+    pub fn rebind(&mut self, action: GameAction, slot: usize, binding: Binding) {
+        if let Some((_, bindings)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            // it's already on there, no point in having it twice
+            if bindings.contains(&binding) { return; }
+
+            match bindings.get_mut(slot) {
+                Some(existing) => *existing = binding,
+                None => bindings.push(binding),
+            }
+        }
+    }
+}
+
+// what the player is doing this frame, everything reads from this instead of the raw input
+#[derive(Default)]
+pub struct ActionState {
+    pressed: HashSet::<GameAction>,
+    just_pressed: HashSet::<GameAction>,
+
+    // x is right and y is up, the length is how hard they're pushing
+    pub movement: Vec2,
+
+    // whatever was pressed this frame, used for rebinding
+    pub just_pressed_binding: Option::<Binding>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: GameAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: GameAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn pressed_actions(&self) -> Vec::<GameAction> {
+        self.pressed.iter().cloned().collect()
+    }
+
+    // a replay frame goes through the same path as real input so just_pressed still works
+    pub fn set_from_replay(&mut self, pressed: &[GameAction], movement: Vec2) {
+        self.update(pressed.iter().cloned().collect(), movement);
+        self.just_pressed_binding = None;
+    }
+
+    fn update(&mut self, pressed: HashSet::<GameAction>, stick: Vec2) {
+        self.just_pressed = pressed.difference(&self.pressed).cloned().collect();
+
+        // keys are always full speed, diagonals shouldn't be faster though
+        let mut digital_movement = Vec2::ZERO;
+        if pressed.contains(&GameAction::MoveUp) { digital_movement.y += 1.0; }
+        if pressed.contains(&GameAction::MoveDown) { digital_movement.y -= 1.0; }
+        if pressed.contains(&GameAction::MoveLeft) { digital_movement.x -= 1.0; }
+        if pressed.contains(&GameAction::MoveRight) { digital_movement.x += 1.0; }
+
+        self.movement =
+            if digital_movement != Vec2::ZERO {
+                digital_movement.normalize()
+            } else {
+                stick
+            };
+
+        self.pressed = pressed;
+    }
+}
+
+// the co-op pads, player one (keyboard and the first pad) is always the ActionState resource
+#[derive(Default)]
+pub struct PadActions {
+    pub pads: HashMap::<Gamepad, ActionState>,
+}
+
+pub struct ActionsPlugin;
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ActionMap::load())
+           .init_resource::<ActionState>()
+           .init_resource::<PadActions>()
+           .init_resource::<game_controller::GameControllers>()
+           .add_system_to_stage(CoreStage::PreUpdate, game_controller::gamepad_connections.system().after(InputSystem))
+           .add_system_to_stage(CoreStage::PreUpdate, update_action_state.system().label("update_action_state").after(InputSystem));
+    }
+}
+
+pub fn update_action_state(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    buttons: Res<Input<GamepadButton>>,
+    controllers: Res<game_controller::GameControllers>,
+    action_map: Res<ActionMap>,
+    settings: Res<game_settings::GameSettings>,
+    mut action_state: ResMut<ActionState>,
+    mut pad_actions: ResMut<PadActions>,
+    replay: Res<replay::Replay>,
+) {
+    // a replay owns the input, leave the last played frame in place so the next one diffs against it
+    if replay.mode == replay::ReplayMode::Playing {
+        action_state.just_pressed.clear();
+        action_state.just_pressed_binding = None;
+        return;
+    }
+
+    let gamepad = controllers.main();
+    let is_pressed = |binding: &Binding, gamepad: Option::<Gamepad>, include_keyboard: bool| {
+        match binding {
+            Binding::Key(key) => include_keyboard && keyboard_input.pressed(*key),
+            Binding::Mouse(button) => include_keyboard && mouse_input.pressed(*button),
+            Binding::Gamepad(button_type) => {
+                gamepad.map(|gp| buttons.pressed(GamepadButton(gp, *button_type))).unwrap_or(false)
+            }
+        }
+    };
+
+    let read_pressed = |gamepad: Option::<Gamepad>, include_keyboard: bool, stick: Vec2| {
+        let mut pressed = HashSet::new();
+        for (action, bindings) in action_map.bindings.iter() {
+            let stick_pressed =
+                match action {
+                    GameAction::MoveUp => stick.y > STICK_PRESS_THRESHOLD,
+                    GameAction::MoveDown => stick.y < -STICK_PRESS_THRESHOLD,
+                    GameAction::MoveLeft => stick.x < -STICK_PRESS_THRESHOLD,
+                    GameAction::MoveRight => stick.x > STICK_PRESS_THRESHOLD,
+                    _ => false
+                };
+
+            if stick_pressed || bindings.iter().any(|b| is_pressed(b, gamepad, include_keyboard)) {
+                pressed.insert(*action);
+            }
+        }
+        pressed
+    };
+
+    let stick = game_controller::get_movement(&axes, gamepad, settings.stick_deadzone);
+    action_state.update(read_pressed(gamepad, true, stick), stick);
+
+    pad_actions.pads.retain(|pad, _| controllers.extra().contains(pad));
+    for pad in controllers.extra() {
+        let stick = game_controller::get_movement(&axes, Some(*pad), settings.stick_deadzone);
+        pad_actions.pads
+                   .entry(*pad)
+                   .or_insert_with(ActionState::default)
+                   .update(read_pressed(Some(*pad), false, stick), stick);
+    }
+
+    action_state.just_pressed_binding =
+        keyboard_input.get_just_pressed().next().map(|key| Binding::Key(*key))
+            .or_else(|| mouse_input.get_just_pressed().next().map(|button| Binding::Mouse(*button)))
+            .or_else(|| {
+                gamepad.and_then(|gp| {
+                    buttons.get_just_pressed()
+                           .find(|GamepadButton(id, _)| *id == gp)
+                           .map(|GamepadButton(_, button_type)| Binding::Gamepad(*button_type))
+                })
+            });
+}
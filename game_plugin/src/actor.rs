@@ -0,0 +1,338 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::{camera, cutscene::Character, enemy, get_colors, party, player, replay, rng, theater_outside, AppState, GameState, Kid};
+
+// how long turning to face something takes
+static TURN_TIME: f32 = 0.3;
+static HOP_TIME: f32 = 0.4;
+static HOP_HEIGHT: f32 = 0.3;
+// close enough to count as having walked there
+static ARRIVE_DISTANCE: f32 = 0.05;
+
+// someone standing in the level that a cutscene can boss around
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub enum Actor {
+    // A-D and recruits are the kids in the level, Mom is the Mom guard
+    Character(Character),
+    // whichever guard started closest to x,z
+    Guard(f32, f32),
+    // spawned by a SpawnNpc segment
+    Npc(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Facing {
+    Camera,
+    Point(f32, f32), // x, z
+    Actor(Actor),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum NpcLook {
+    Kid(Kid),
+    Guard,
+    Mom,
+}
+
+#[derive(Debug, Clone)]
+pub enum ActorAction {
+    Walk(Actor, Vec2, f32), // to x,z at this speed
+    Face(Actor, Facing),
+    Hop(Actor),
+    Spawn(String, NpcLook, Vec2),
+    Despawn(String),
+}
+
+// marks anything a cutscene spawned so it goes away with the level
+pub struct CutsceneActor(pub String);
+
+pub struct ActorOrder {
+    id: usize,
+    action: ActorAction,
+    // skipping puts everyone where they'd end up without the walking
+    instant: bool,
+    elapsed: f32,
+    // where they were when the order started
+    start: Option::<Transform>,
+}
+
+// cutscenes hand out orders, direct_actors carries them out over however many frames they take
+#[derive(Default)]
+pub struct ActorDirector {
+    orders: Vec::<ActorOrder>,
+    next_id: usize,
+}
+
+impl ActorDirector {
+    pub fn order(&mut self, action: ActorAction, instant: bool) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.orders.push(ActorOrder { id, action, instant, elapsed: 0.0, start: None });
+        id
+    }
+
+    pub fn is_done(&self, id: usize) -> bool {
+        !self.orders.iter().any(|order| order.id == id)
+    }
+
+    // finishes the order the next time the actors move
+    pub fn hurry(&mut self, id: usize) {
+        for order in self.orders.iter_mut().filter(|order| order.id == id) {
+            order.instant = true;
+        }
+    }
+}
+
+pub struct ActorPlugin;
+impl Plugin for ActorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ActorDirector>()
+           .add_system_set(
+               SystemSet::on_update(AppState::Cutscene)
+                   .with_system(direct_actors.system().after("update_cutscene"))
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::Cutscene)
+                   .with_system(forget_orders.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::InGame)
+                   .with_system(despawn_npcs.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::Lobby)
+                   .with_system(despawn_npcs.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::Movie)
+                   .with_system(despawn_npcs.system())
+           );
+    }
+}
+
+// the same way the kids and guards turn when they walk
+fn facing_rotation(from: Vec3, to: Vec3) -> Option::<Quat> {
+    let direction = to - from;
+    if direction.x.abs() < 0.0001 && direction.z.abs() < 0.0001 {
+        return None;
+    }
+
+    Some(Quat::from_axis_angle(Vec3::Y, (-direction.z).atan2(direction.x)))
+}
+
+// who's who in the level, the transforms get their own query so this one can stay read only
+type Actors<'w, 'a> = Query<'w, (Entity, Option<&'a player::Player>, Option<&'a enemy::Enemy>, Option<&'a party::Recruit>, Option<&'a CutsceneActor>)>;
+
+fn find_actor(
+    actor: &Actor,
+    actors: &Actors,
+) -> Option::<Entity> {
+    match actor {
+        Actor::Character(Character::Mom) => {
+            actors.iter()
+                  .find(|(_, _, enemy, _, _)| matches!(enemy, Some(e) if matches!(e.enemy_spawn.enemy_type, enemy::EnemyType::Mom(_))))
+                  .map(|(entity, _, _, _, _)| entity)
+        },
+        Actor::Character(character) => {
+            let kid = character.kid()?;
+            actors.iter()
+                  .find(|(_, player, _, recruit, _)| {
+                      matches!(player, Some(p) if p.kid == kid) || matches!(recruit, Some(r) if r.kid == kid)
+                  })
+                  .map(|(entity, _, _, _, _)| entity)
+        },
+        Actor::Guard(x, z) => {
+            let spot = Vec2::new(*x, *z);
+            actors.iter()
+                  .filter_map(|(entity, _, enemy, _, _)| enemy.map(|e| (entity, e.enemy_spawn.location.distance(spot))))
+                  .fold(None, |closest: Option::<(Entity, f32)>, (entity, distance)| {
+                      match closest {
+                          Some((_, closest_distance)) if closest_distance <= distance => closest,
+                          _ => Some((entity, distance))
+                      }
+                  })
+                  .map(|(entity, _)| entity)
+        },
+        Actor::Npc(name) => {
+            actors.iter()
+                  .find(|(_, _, _, _, npc)| matches!(npc, Some(n) if n.0 == *name))
+                  .map(|(entity, _, _, _, _)| entity)
+        },
+    }
+}
+
+fn spawn_npc(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    theater_meshes: &theater_outside::TheaterMeshes,
+    game_state: &GameState,
+    rng: &mut rng::GameRng,
+    name: &str,
+    look: &NpcLook,
+    location: Vec2,
+) {
+    let position = Vec3::new(location.x, 0.0, location.y);
+    let entity =
+        match look {
+            NpcLook::Kid(kid) if game_state.kid_colors.contains_key(kid) => {
+                player::spawn_kid(commands, materials, theater_meshes, game_state, *kid, position)
+            },
+            NpcLook::Kid(kid) => {
+                println!("Kid {:?} doesn't have any colors, can't spawn {}", kid, name);
+                return;
+            },
+            NpcLook::Guard | NpcLook::Mom => {
+                let (skin_color, hair_color) =
+                    match look {
+                        NpcLook::Mom => (Color::hex(game_state.colors(Kid::D).skin).unwrap(),
+                                         Some(Color::hex(game_state.colors(Kid::D).hair).unwrap())),
+                        _ => (Color::hex(get_colors(rng.rng()).skin).unwrap(), None),
+                    };
+
+                let mut transform = Transform::from_translation(position);
+                transform.apply_non_uniform_scale(Vec3::new(enemy::SCALE, enemy::SCALE, enemy::SCALE));
+                transform.rotate(Quat::from_axis_angle(Vec3::Y, std::f32::consts::PI));
+
+                commands.spawn_bundle(PbrBundle {
+                            transform,
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            enemy::spawn_grown_up_parts(parent, materials, theater_meshes, skin_color, hair_color);
+                        })
+                        .id()
+            },
+        };
+
+    commands.entity(entity).insert(CutsceneActor(name.to_string()));
+}
+
+pub fn direct_actors(
+    mut commands: Commands,
+    mut director: ResMut<ActorDirector>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
+    game_state: Res<GameState>,
+    mut rng: ResMut<rng::GameRng>,
+    time: Res<replay::GameTime>,
+    actors: Actors,
+    mut transforms: Query<&mut Transform, Without<camera::MainCamera>>,
+    cameras: Query<&Transform, With<camera::MainCamera>>,
+) {
+    let mut finished = vec!();
+    let spawning = director.orders
+                           .iter()
+                           .filter_map(|order| match &order.action {
+                               ActorAction::Spawn(name, _, _) => Some(name.clone()),
+                               _ => None
+                           })
+                           .collect::<Vec::<_>>();
+
+    for order in director.orders.iter_mut() {
+        order.elapsed += time.delta_seconds();
+
+        let actor = match &order.action {
+                        ActorAction::Walk(actor, _, _) | ActorAction::Face(actor, _) | ActorAction::Hop(actor) => actor.clone(),
+                        ActorAction::Spawn(name, look, location) => {
+                            // it's done once it shows up in the query, the frame after it gets spawned
+                            if order.start.is_some() {
+                                if find_actor(&Actor::Npc(name.clone()), &actors).is_some() {
+                                    finished.push(order.id);
+                                }
+                            } else {
+                                spawn_npc(&mut commands, &mut materials, &theater_meshes, &game_state, &mut rng, name, look, *location);
+                                order.start = Some(Transform::default());
+                            }
+                            continue;
+                        },
+                        ActorAction::Despawn(name) => {
+                            if let Some(entity) = find_actor(&Actor::Npc(name.clone()), &actors) {
+                                commands.entity(entity).despawn_recursive();
+                            }
+                            finished.push(order.id);
+                            continue;
+                        },
+                    };
+
+        let entity = match find_actor(&actor, &actors) {
+                         Some(entity) => entity,
+                         None => {
+                             // they might be getting spawned by an earlier order
+                             if !matches!(&actor, Actor::Npc(name) if spawning.contains(name)) {
+                                 println!("Couldn't find {:?} in the level", actor);
+                                 finished.push(order.id);
+                             }
+                             continue;
+                         }
+                     };
+
+        // where a Face order is looking, worked out before borrowing whoever is turning
+        let look_target =
+            match &order.action {
+                ActorAction::Face(_, Facing::Camera) => cameras.iter().next().map(|t| t.translation),
+                ActorAction::Face(_, Facing::Point(x, z)) => Some(Vec3::new(*x, 0.0, *z)),
+                ActorAction::Face(_, Facing::Actor(other)) => {
+                    find_actor(other, &actors).and_then(|e| transforms.get_mut(e).ok().map(|t| t.translation))
+                },
+                _ => None
+            };
+
+        let mut transform = match transforms.get_mut(entity) {
+                                Ok(transform) => transform,
+                                Err(_) => continue
+                            };
+        let start = *order.start.get_or_insert(*transform);
+
+        match &order.action {
+            ActorAction::Walk(_, target, speed) => {
+                let target = Vec3::new(target.x, transform.translation.y, target.y);
+                if let Some(rotation) = facing_rotation(transform.translation, target) {
+                    transform.rotation = rotation;
+                }
+
+                let step = speed * time.delta_seconds();
+                if order.instant || transform.translation.distance(target) <= step.max(ARRIVE_DISTANCE) {
+                    transform.translation = target;
+                    finished.push(order.id);
+                } else {
+                    let direction = (target - transform.translation) / transform.translation.distance(target);
+                    transform.translation += direction * step;
+                }
+            },
+            ActorAction::Face(_, _) => {
+                let rotation = look_target.and_then(|target| facing_rotation(transform.translation, target))
+                                          .unwrap_or(start.rotation);
+                let t = if order.instant { 1.0 } else { (order.elapsed / TURN_TIME).min(1.0) };
+                transform.rotation = start.rotation.slerp(rotation, t);
+                if t >= 1.0 {
+                    finished.push(order.id);
+                }
+            },
+            ActorAction::Hop(_) => {
+                let t = if order.instant { 1.0 } else { (order.elapsed / HOP_TIME).min(1.0) };
+                transform.translation.y = start.translation.y + HOP_HEIGHT * 4.0 * t * (1.0 - t);
+                if t >= 1.0 {
+                    finished.push(order.id);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    director.orders.retain(|order| !finished.contains(&order.id));
+}
+
+pub fn forget_orders(
+    mut director: ResMut<ActorDirector>,
+) {
+    director.orders.clear();
+}
+
+pub fn despawn_npcs(
+    mut commands: Commands,
+    npcs: Query<Entity, With<CutsceneActor>>,
+) {
+    for entity in npcs.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -2,11 +2,17 @@ use bevy::{prelude::*,};
 use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::reflect::{TypeUuid};
 use bevy::utils::{BoxedFuture};
+use bevy::app::Events;
+use bevy::app::AppExit;
 use serde::Deserialize;
+use std::sync::Mutex;
 
-use crate::{level_collision, cutscene, enemy};
+use crate::{level_collision, cutscene, enemy, inventory, party};
 
 
+// the loader runs off on its own, whatever went wrong gets left here for the loading screen
+pub static LEVEL_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
 // this is for hot reloading
 #[derive(Default)]
 pub struct LevelsAssetLoader;
@@ -18,7 +24,21 @@ impl AssetLoader for LevelsAssetLoader {
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
             println!("Level asset reloaded");
-            let lvl_asset = ron::de::from_bytes::<LevelInfo>(bytes)?;
+            // a typo'd or unknown segment fails here
+            let lvl_asset = match ron::de::from_bytes::<LevelInfo>(bytes) {
+                                Ok(lvl_asset) => lvl_asset,
+                                Err(e) => {
+                                    println!("Couldn't read level: {}", e);
+                                    *LEVEL_ERROR.lock().unwrap() = Some(format!("Couldn't read level: {}", e));
+                                    return Err(e.into());
+                                }
+                            };
+            if let Err(e) = lvl_asset.check() {
+                println!("Level has a problem: {}", e);
+                *LEVEL_ERROR.lock().unwrap() = Some(format!("Level has a problem: {}", e));
+                return Err(anyhow::anyhow!(e));
+            }
+            *LEVEL_ERROR.lock().unwrap() = None;
             load_context.set_default_asset(LoadedAsset::new(lvl_asset));
             Ok(())
         })
@@ -37,12 +57,31 @@ pub fn check_assets_ready(
     mut state: ResMut<State<crate::AppState>>,
     server: Res<AssetServer>,
     loading: Res<AssetsLoading>,
+    level_info_state: Res<LevelInfoState>,
+    mut exit: ResMut<Events<AppExit>>,
 ) {
     println!("Loading...");
     use bevy::asset::LoadState;
 
     let mut ready = true;
 
+    // there's no playing without the level, so a bad one stops the game with whatever was wrong with it
+    match server.get_load_state(&level_info_state.handle) {
+        LoadState::Failed => {
+            match LEVEL_ERROR.lock().unwrap().as_ref() {
+                Some(error) => println!("Can't start the game. {}", error),
+                None => println!("Can't start the game, the level didn't load"),
+            }
+            exit.send(AppExit);
+            return;
+        }
+        LoadState::Loaded => {
+        }
+        _ => {
+            ready = false;
+        }
+    }
+
     for handle in loading.asset_handles.iter() {
         match server.get_load_state(handle) {
             LoadState::Failed => {
@@ -71,5 +110,23 @@ pub struct LevelInfo {
     pub collision_info: level_collision::LevelCollisionInfo,
     pub cutscenes: cutscene::Cutscenes,
     pub enemies: Vec::<enemy::EnemySpawnPoint>,
+    #[serde(default)]
+    pub pickups: Vec::<inventory::PickupSpawnPoint>,
+    #[serde(default)]
+    pub kids: Vec::<party::KidInfo>,
+    #[serde(default)]
+    pub recruits: Vec::<party::RecruitSpawnPoint>,
+}
+
+impl LevelInfo {
+    // a mistake in the level data should show up when it loads instead of getting stuck in a cutscene
+    pub fn check(&self) -> Result<(), String> {
+        self.cutscenes.check()?;
+        for recruit in self.recruits.iter() {
+            cutscene::check_segments(&recruit.cutscene).map_err(|e| format!("recruit {:?}: {}", recruit.kid, e))?;
+        }
+
+        Ok(())
+    }
 }
 
@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+use crate::{actions, cutscene, locale, AppState};
+
+// how many lines fit on the screen at once
+static BACKLOG_PAGE: usize = 12;
+// older lines fall off the top after this many
+static MAX_BACKLOG_LINES: usize = 500;
+
+pub struct BacklogLine {
+    speaker: Option::<String>,
+    // the string id, so switching languages redoes the whole backlog
+    text: String,
+}
+
+// every line shown in a cutscene since the game started, nothing gets saved
+#[derive(Default)]
+pub struct Backlog {
+    lines: Vec::<BacklogLine>,
+    pub is_open: bool,
+    // how many lines up from the newest one
+    scroll: usize,
+}
+
+impl Backlog {
+    pub fn add(&mut self, speaker: Option::<String>, text: &str) {
+        self.lines.push(BacklogLine { speaker, text: text.to_string() });
+        let extra = self.lines.len().saturating_sub(MAX_BACKLOG_LINES);
+        self.lines.drain(..extra);
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(BACKLOG_PAGE)
+    }
+}
+
+pub struct BacklogPanel;
+pub struct BacklogText;
+
+pub struct BacklogPlugin;
+impl Plugin for BacklogPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Backlog>()
+           .add_system_set(
+               SystemSet::on_update(AppState::Cutscene)
+                   .with_system(toggle_backlog.system().label("toggle_backlog"))
+                   .with_system(scroll_backlog.system().after("toggle_backlog"))
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::Cutscene)
+                   .with_system(close_backlog.system())
+           );
+    }
+}
+
+pub fn toggle_backlog(
+    mut commands: Commands,
+    mut backlog: ResMut<Backlog>,
+    actions: Res<actions::ActionState>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    panels: Query<Entity, With<BacklogPanel>>,
+) {
+    let close = backlog.is_open && actions.just_pressed(actions::GameAction::Back);
+    if !actions.just_pressed(actions::GameAction::Backlog) && !close {
+        return;
+    }
+
+    backlog.is_open = !backlog.is_open;
+    backlog.scroll = 0;
+
+    if !backlog.is_open {
+        for entity in panels.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                align_items: AlignItems::FlexEnd,
+                padding: Rect::all(Val::Px(20.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.85).into()),
+            ..Default::default()
+        })
+        .insert(BacklogPanel)
+        .insert(cutscene::CutsceneTrashMarker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    max_size: Size {
+                        width: Val::Px(1240.0),
+                        height: Val::Undefined,
+                    },
+                    ..Default::default()
+                },
+                // scroll_backlog fills this in
+                text: Text {
+                    sections: vec!(),
+                    alignment: Default::default(),
+                },
+                ..Default::default()
+            })
+            .insert(BacklogText);
+        });
+}
+
+pub fn scroll_backlog(
+    mut backlog: ResMut<Backlog>,
+    actions: Res<actions::ActionState>,
+    asset_server: Res<AssetServer>,
+    strings: Res<locale::Strings>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut texts: Query<&mut Text, With<BacklogText>>,
+) {
+    if !backlog.is_open {
+        // otherwise wheel turns from before it was opened would scroll it
+        for _ in mouse_wheel_events.iter() {}
+        return;
+    }
+
+    let mut scroll = backlog.scroll as i32;
+    if actions.just_pressed(actions::GameAction::MoveUp) {
+        scroll += 1;
+    }
+    if actions.just_pressed(actions::GameAction::MoveDown) {
+        scroll -= 1;
+    }
+    for event in mouse_wheel_events.iter() {
+        scroll += event.y.signum() as i32;
+    }
+    backlog.scroll = (scroll.max(0) as usize).min(backlog.max_scroll());
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let end = backlog.lines.len() - backlog.scroll;
+    let start = end.saturating_sub(BACKLOG_PAGE);
+
+    let mut sections = vec!();
+    if start > 0 {
+        sections.push(TextSection {
+            value: "...\n".to_string(),
+            style: TextStyle { font: font.clone(), font_size: 30.0, color: Color::GRAY },
+        });
+    }
+    for line in backlog.lines[start..end].iter() {
+        if let Some(speaker) = &line.speaker {
+            sections.push(TextSection {
+                value: format!("{}: ", strings.get(speaker)),
+                style: TextStyle { font: font.clone(), font_size: 30.0, color: Color::hex("FCF300").unwrap() },
+            });
+        }
+        sections.push(TextSection {
+            value: format!("{}\n", strings.get(&line.text)),
+            style: TextStyle { font: font.clone(), font_size: 30.0, color: Color::WHITE },
+        });
+    }
+    if backlog.lines.is_empty() {
+        sections.push(TextSection {
+            value: strings.get("backlog.empty"),
+            style: TextStyle { font: font.clone(), font_size: 30.0, color: Color::GRAY },
+        });
+    }
+
+    for mut text in texts.iter_mut() {
+        text.sections = sections.clone();
+    }
+}
+
+// the panel itself goes with the rest of the cutscene trash
+pub fn close_backlog(
+    mut backlog: ResMut<Backlog>,
+) {
+    backlog.is_open = false;
+    backlog.scroll = 0;
+}
@@ -2,12 +2,15 @@ use bevy::prelude::*;
 use serde::Deserialize;
 use bevy::reflect::{TypeUuid};
 use bevy::render::camera::PerspectiveProjection;
-use crate::{theater_outside::LevelReady, GameState, asset_loader, player::Player, level_collision::CollisionShape};
+use crate::{theater_outside::LevelReady, GameState, asset_loader, player::Player, level_collision::CollisionShape, replay, Kid};
 
 pub mod fly_camera;
 
+// how much further back the camera goes for each unit the co-op kids are spread out
+static CO_OP_PULL_BACK: f32 = 1.2;
 
-#[derive(Debug, Clone, Deserialize, TypeUuid)]
+
+#[derive(Debug, Clone, Deserialize, TypeUuid, PartialEq)]
 #[uuid = "522adc56-aa9c-4543-8640-a018b74b5052"] // this needs to be actually generated
 pub struct CameraPosition {
     pub x: f32,
@@ -18,6 +21,181 @@ pub struct CameraPosition {
     pub rotation_z: f32,
     pub rotation_angle: f32,
     pub speed: f32,
+    // takes exactly this many seconds to get there instead of easing in by speed
+    #[serde(default)]
+    pub duration: Option::<f32>,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+impl CameraPosition {
+    fn timed_move(&self) -> Option::<CameraMove> {
+        self.duration.map(|duration| {
+            CameraMove {
+                position: (self.x, self.y, self.z),
+                rotation: Some((self.rotation_x, self.rotation_y, self.rotation_z, self.rotation_angle)),
+                duration,
+                easing: self.easing,
+                through: vec!(),
+                look_at: None,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::EaseInOut
+    }
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// what the camera keeps pointed at while it moves
+#[derive(Debug, Clone, Deserialize)]
+pub enum LookAt {
+    Point(f32, f32, f32),
+    Kid(Kid),
+    // whoever the player is controlling right now
+    Controlled,
+}
+
+// a camera move that takes a set amount of time, used by cutscenes and by zones with a duration
+#[derive(Debug, Clone, Deserialize)]
+pub struct CameraMove {
+    pub position: (f32, f32, f32),
+    // axis and angle, same as CameraPosition. left out it keeps whatever rotation it started with
+    #[serde(default)]
+    pub rotation: Option::<(f32, f32, f32, f32)>,
+    pub duration: f32,
+    #[serde(default)]
+    pub easing: Easing,
+    // curves through these on the way to position
+    #[serde(default)]
+    pub through: Vec::<(f32, f32, f32)>,
+    // overrides rotation, follows the target if it moves
+    #[serde(default)]
+    pub look_at: Option::<LookAt>,
+}
+
+pub struct CameraTween {
+    start: Vec3,
+    start_rotation: Quat,
+    camera_move: CameraMove,
+    elapsed: f32,
+}
+
+impl CameraTween {
+    pub fn new(transform: &Transform, camera_move: CameraMove) -> Self {
+        CameraTween {
+            start: transform.translation,
+            start_rotation: transform.rotation,
+            camera_move,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn look_at(&self) -> Option::<&LookAt> {
+        self.camera_move.look_at.as_ref()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.camera_move.duration
+    }
+
+    pub fn finish(&mut self) {
+        self.elapsed = self.camera_move.duration;
+    }
+
+    // keeps the time it's been going but heads somewhere else
+    fn retarget(&mut self, camera_move: CameraMove) {
+        self.camera_move = camera_move;
+    }
+
+    // moves the camera along, true once it's there
+    pub fn update(&mut self, transform: &mut Transform, delta: f32, look_at: Option::<Vec3>) -> bool {
+        let duration = self.camera_move.duration;
+        self.elapsed = (self.elapsed + delta).min(duration);
+        let t = if duration <= 0.0 { 1.0 } else { self.elapsed / duration };
+        let t = self.camera_move.easing.apply(t);
+
+        let (x, y, z) = self.camera_move.position;
+        let end = Vec3::new(x, y, z);
+        transform.translation =
+            if self.camera_move.through.is_empty() {
+                self.start + (end - self.start) * t
+            } else {
+                let mut points = vec!(self.start);
+                points.extend(self.camera_move.through.iter().map(|(x, y, z)| Vec3::new(*x, *y, *z)));
+                points.push(end);
+                catmull_rom(&points, t)
+            };
+
+        let end_rotation =
+            match (look_at, self.camera_move.rotation) {
+                (Some(target), _) => Transform::from_translation(transform.translation).looking_at(target, Vec3::Y).rotation,
+                (None, Some((rx, ry, rz, angle))) => Quat::from_axis_angle(Vec3::new(rx, ry, rz), angle),
+                (None, None) => self.start_rotation,
+            };
+        transform.rotation = self.start_rotation.slerp(end_rotation, t);
+
+        self.is_done()
+    }
+}
+
+// a curve that passes through every point, t goes from the first one to the last
+fn catmull_rom(points: &[Vec3], t: f32) -> Vec3 {
+    let segments = points.len() - 1;
+    let scaled = t * segments as f32;
+    let i = (scaled as usize).min(segments - 1);
+    let local = scaled - i as f32;
+
+    let p0 = points[i.saturating_sub(1)];
+    let p1 = points[i];
+    let p2 = points[i + 1];
+    let p3 = points[(i + 2).min(segments)];
+
+    let t2 = local * local;
+    let t3 = t2 * local;
+    (p1 * 2.0
+     + (p2 - p0) * local
+     + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+     + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+// where a look at target is right now, kids get aimed at about head height
+pub fn look_at_position(
+    look_at: &LookAt,
+    players: &Query<(&Transform, &Player), Without<MainCamera>>,
+    game_state: &GameState,
+) -> Option::<Vec3> {
+    let kid = match look_at {
+                  LookAt::Point(x, y, z) => return Some(Vec3::new(*x, *y, *z)),
+                  LookAt::Kid(kid) => *kid,
+                  LookAt::Controlled => game_state.controlling,
+              };
+
+    players.iter()
+           .find(|(_, player)| player.kid == kid)
+           .map(|(transform, _)| transform.translation + Vec3::new(0.0, 0.5, 0.0))
 }
 
 
@@ -55,7 +233,7 @@ fn update_camera(
     fly_camera: Query<&fly_camera::FlyCamera>,
     player: Query<(&Transform, &Player), Without<MainCamera>>,
     keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     game_state: Res<GameState>,
     level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
     level_info_state: Res<asset_loader::LevelInfoState>, 
@@ -77,6 +255,16 @@ fn update_camera(
             println!("speed: 1.8,"); 
             println!("))))),");
 
+            println!("");
+            println!("CameraMove((position: ({}, {}, {}), rotation: Some(({}, {}, {}, {})), duration: 2.0))",
+                translation.x,
+                translation.y,
+                translation.z,
+                rotation.x,
+                rotation.y,
+                rotation.z,
+                axis,);
+
             println!("");
             println!("CameraPosition({}, {}, {}, {}, {}, {}, {}, 2.0)",
                 translation.x,
@@ -96,50 +284,100 @@ fn update_camera(
     }
 
     match state.current() {
-        crate::AppState::Cutscene => { return; },
+        crate::AppState::Cutscene => {
+            // the cutscene moved the camera, start over from wherever it left it
+            for (_e, mut camera, _transform) in cameras.iter_mut() {
+                camera.zone_tween = None;
+            }
+            return;
+        },
         _ => ()
     }
 
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
+        // with more than one person playing, aim for the middle of everyone and back up to fit them all
+        let controlled = player.iter()
+                               .filter(|(_, p)| game_state.is_controlled(p.kid))
+                               .map(|(t, _)| t.translation)
+                               .collect::<Vec::<_>>();
+        let (focus, spread) =
+            if game_state.co_op.is_empty() || controlled.is_empty() {
+                (None, 0.0)
+            } else {
+                let center = controlled.iter().fold(Vec3::ZERO, |acc, t| acc + *t) / controlled.len() as f32;
+                let spread = controlled.iter().map(|t| t.distance(center)).fold(0.0, f32::max);
+                (Some(center), spread)
+            };
+
         for (transform, player) in player.iter() {
             if player.kid == game_state.controlling {
-                for (level, shape) in level_asset.collision_info.shapes.iter() {
-                    if *level == game_state.current_level {
-                        match shape {
-                            CollisionShape::Rect((r, c)) 
-                          //| CollisionShape::LevelSwitch((r, c)) 
-                          | CollisionShape::TicketCheck((r, c)) 
-                          | CollisionShape::GetTicket((r, c)) => {
-
-                                if let Some(c) = c {
-                                    if transform.translation.x >= r.bottom_x 
-                                    && transform.translation.x <= r.top_x 
-                                    && transform.translation.z <= r.right_z
-                                    && transform.translation.z >= r.left_z {
-
-                                        for (_e, _camera, mut transform) in cameras.iter_mut() {
-                                            transform.translation.x += 
-                                                (c.x - transform.translation.x) 
-                                               * c.speed
-                                               * time.delta_seconds();
-                                            transform.translation.y += 
-                                                (c.y - transform.translation.y) 
-                                               * c.speed
-                                               * time.delta_seconds();
-                                            transform.translation.z += 
-                                                (c.z - transform.translation.z) 
-                                               * c.speed
-                                               * time.delta_seconds();
-
-                                            let end_rotation = Quat::from_axis_angle(Vec3::new(c.rotation_x, c.rotation_y, c.rotation_z), 
-                                                                                     c.rotation_angle);
-                                            transform.rotation = transform.rotation.slerp(end_rotation, time.delta_seconds());
-                                        }
-                                    }
-                                }
+                let position = focus.unwrap_or(transform.translation);
+
+                // peek out from wherever they're hiding if the spot has its own camera
+                if player.is_hidden && focus.is_none() {
+                    let peek_camera = 
+                        level_asset.collision_info.shapes
+                                   .iter()
+                                   .enumerate()
+                                   .filter(|(_, (level, _))| *level == game_state.current_level)
+                                   .find_map(|(i, (_, shape))| {
+                                       match shape {
+                                           CollisionShape::HidingSpot((r, Some(c))) 
+                                               if position.x >= r.bottom_x 
+                                               && position.x <= r.top_x 
+                                               && position.z <= r.right_z
+                                               && position.z >= r.left_z => Some((i, c)),
+                                           _ => None
+                                       }
+                                   });
+
+                    if let Some((zone, c)) = peek_camera {
+                        for (_e, mut camera, mut transform) in cameras.iter_mut() {
+                            move_toward_camera_position(&mut camera, &mut transform, zone, c, &time);
+                        }
+                        continue;
+                    }
+                }
+
+                // a zone is the index of its shape in the level file
+                let zones = 
+                    level_asset.collision_info.shapes
+                               .iter()
+                               .enumerate()
+                               .filter(|(_, (level, _))| *level == game_state.current_level)
+                               .filter_map(|(i, (_, shape))| {
+                                   match shape {
+                                       CollisionShape::Rect((r, Some(c))) 
+                                     //| CollisionShape::LevelSwitch((r, Some(c))) 
+                                     | CollisionShape::TicketCheck((r, Some(c))) 
+                                     | CollisionShape::GetTicket((r, Some(c))) 
+                                           if position.x >= r.bottom_x 
+                                           && position.x <= r.top_x 
+                                           && position.z <= r.right_z
+                                           && position.z >= r.left_z => Some((i, c)),
+                                       _ => None
+                                   }
+                               })
+                               .collect::<Vec::<_>>();
+
+                for (_e, mut camera, mut transform) in cameras.iter_mut() {
+                    // standing where timed zones overlap, only the one the camera is already headed to
+                    // gets to move it, otherwise they'd keep restarting each other's tweens
+                    let current_zone = camera.zone_tween.as_ref().map(|(zone, _)| *zone);
+                    let timed_zone = zones.iter()
+                                          .filter(|(_, c)| c.duration.is_some())
+                                          .find(|(zone, _)| Some(*zone) == current_zone)
+                                          .or_else(|| zones.iter().find(|(_, c)| c.duration.is_some()));
+
+                    match timed_zone {
+                        Some((zone, c)) => {
+                            move_toward_camera_position(&mut camera, &mut transform, *zone, &pull_back(c, spread), &time);
+                        },
+                        None => {
+                            for (zone, c) in zones.iter() {
+                                move_toward_camera_position(&mut camera, &mut transform, *zone, &pull_back(c, spread), &time);
                             }
-                            _ => ()
                         }
                     }
                 }
@@ -148,6 +386,60 @@ fn update_camera(
     }
 }
 
+// backs the camera away along the way it's looking so kids that far apart still fit
+fn pull_back(
+    c: &CameraPosition,
+    spread: f32,
+) -> CameraPosition {
+    let rotation = Quat::from_axis_angle(Vec3::new(c.rotation_x, c.rotation_y, c.rotation_z), c.rotation_angle);
+    let back = rotation * Vec3::Z * spread * CO_OP_PULL_BACK;
+
+    CameraPosition {
+        x: c.x + back.x,
+        y: c.y + back.y,
+        z: c.z + back.z,
+        ..c.clone()
+    }
+}
+
+// zone is which shape in the level the camera came from, c is where it actually goes after backing up for co-op
+fn move_toward_camera_position(
+    camera: &mut MainCamera,
+    transform: &mut Transform,
+    zone: usize,
+    c: &CameraPosition,
+    time: &Res<replay::GameTime>,
+) {
+    if let Some(camera_move) = c.timed_move() {
+        match &mut camera.zone_tween {
+            Some((current_zone, tween)) if *current_zone == zone => tween.retarget(camera_move),
+            _ => camera.zone_tween = Some((zone, CameraTween::new(transform, camera_move))),
+        }
+
+        if let Some((_, tween)) = &mut camera.zone_tween {
+            tween.update(transform, time.delta_seconds(), None);
+        }
+        return;
+    }
+    camera.zone_tween = None;
+
+    transform.translation.x += 
+        (c.x - transform.translation.x) 
+       * c.speed
+       * time.delta_seconds();
+    transform.translation.y += 
+        (c.y - transform.translation.y) 
+       * c.speed
+       * time.delta_seconds();
+    transform.translation.z += 
+        (c.z - transform.translation.z) 
+       * c.speed
+       * time.delta_seconds();
+
+    let end_rotation = Quat::from_axis_angle(Vec3::new(c.rotation_x, c.rotation_y, c.rotation_z), 
+                                             c.rotation_angle);
+    transform.rotation = transform.rotation.slerp(end_rotation, time.delta_seconds());
+}
 
 pub fn create_camera(
     mut commands: Commands,
@@ -187,7 +479,8 @@ pub fn create_camera(
             })
             .insert(MainCamera {
                 current_followx_target: None,
-                current_followy_target: None
+                current_followy_target: None,
+                zone_tween: None,
             })
             .with_children(|parent| {
                 parent.spawn_bundle(LightBundle {
@@ -220,6 +513,8 @@ pub struct CameraMovement {
 pub struct MainCamera {
     pub current_followx_target: Option<CameraMovement>,
     pub current_followy_target: Option<CameraMovement>,
+    // the timed move into whichever zone the player is standing in, keyed by the zone's shape index
+    pub zone_tween: Option<(usize, CameraTween)>,
 }
 
 static DEFAULT_FOV: f32 = 0.7853982; 
@@ -230,7 +525,7 @@ fn toggle_fly(
     mut windows: ResMut<Windows>,
     mut camera: Query<(Entity, &mut MainCamera, Option<&FlyCamera>, &mut Transform)>,
     mut cooldown: Local<f32>,
-    timer: Res<Time>,
+    timer: Res<replay::GameTime>,
 ) {
     *cooldown += timer.delta_seconds();
 
@@ -264,3 +559,50 @@ fn toggle_fly(
         *cooldown = 0.0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 0.0001
+    }
+
+    #[test]
+    fn easings_start_and_end_in_place() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut].iter() {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            // past either end it holds still
+            assert_eq!(easing.apply(-1.0), 0.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easings_halfway() {
+        assert_eq!(Easing::Linear.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseInOut.apply(0.25) < 0.25);
+    }
+
+    #[test]
+    fn spline_passes_through_every_point() {
+        let points = vec!(Vec3::ZERO, Vec3::new(1.0, 2.0, 0.0), Vec3::new(3.0, 0.0, 1.0));
+
+        assert!(close(catmull_rom(&points, 0.0), points[0]));
+        assert!(close(catmull_rom(&points, 0.5), points[1]));
+        assert!(close(catmull_rom(&points, 1.0), points[2]));
+    }
+
+    #[test]
+    fn spline_along_evenly_spaced_points_is_a_line() {
+        let points = vec!(Vec3::ZERO, Vec3::X, Vec3::X * 2.0, Vec3::X * 3.0);
+
+        // the end points get doubled up so only the middle stretch is straight
+        assert!(close(catmull_rom(&points, 0.5), Vec3::X * 1.5));
+        assert!(close(catmull_rom(&points, 1.25 / 3.0), Vec3::X * 1.25));
+    }
+}
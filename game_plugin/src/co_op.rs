@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use crate::{actions, follow_text, game_controller, player, GameState};
+
+static JOIN_TEXT: &str = "follow.joined";
+static LEAVE_TEXT: &str = "follow.left";
+
+// extra pads drop in by pressing Action and drop out with Pause, their kid goes back to following
+pub struct CoOpPlugin;
+impl Plugin for CoOpPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(join_and_leave.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(join_and_leave.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(join_and_leave.system())
+           );
+    }
+}
+
+pub fn join_and_leave(
+    pad_actions: Res<actions::PadActions>,
+    controllers: Res<game_controller::GameControllers>,
+    players: Query<(Entity, &player::Player)>,
+    mut game_state: ResMut<GameState>,
+    mut follow_text_event_writer: EventWriter<follow_text::FollowTextEvent>,
+) {
+    // unplugged pads and kids that already left the level can't be played
+    let remaining_kids = game_state.remaining_kids();
+    game_state.co_op.retain(|(pad, kid)| controllers.extra().contains(pad) && remaining_kids.contains(kid));
+
+    for (pad, actions) in pad_actions.pads.iter() {
+        let claimed = game_state.co_op.iter().position(|(p, _)| p == pad);
+        let changed_kid =
+            match claimed {
+                None if actions.just_pressed(actions::GameAction::Action) => {
+                    let free_kid = remaining_kids.iter().find(|kid| !game_state.is_controlled(**kid)).cloned();
+                    match free_kid {
+                        Some(kid) => {
+                            println!("Pad {:?} joined as {:?}", pad, kid);
+                            game_state.co_op.push((*pad, kid));
+                            Some((kid, JOIN_TEXT))
+                        },
+                        None => {
+                            println!("Pad {:?} tried to join but every kid is taken", pad);
+                            None
+                        }
+                    }
+                },
+                Some(index) if actions.just_pressed(actions::GameAction::Pause) => {
+                    let (_, kid) = game_state.co_op.remove(index);
+                    println!("Pad {:?} left, {:?} is following again", pad, kid);
+                    Some((kid, LEAVE_TEXT))
+                },
+                _ => None
+            };
+
+        if let Some((kid, text)) = changed_kid {
+            if let Some((entity, _)) = players.iter().find(|(_, p)| p.kid == kid) {
+                follow_text_event_writer.send(follow_text::FollowTextEvent {
+                    entity,
+                    value: text.to_string(),
+                    is_player: false,
+                    force: true,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::prelude::*;
+use crate::{actions, locale, menu::ButtonMaterials};
+
+pub struct ControlsPlugin;
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+               SystemSet::on_enter(crate::AppState::Controls)
+                   .with_system(setup_controls_menu.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Controls)
+                   .with_system(controls_menu.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::Controls)
+                   .with_system(cleanup_controls_menu.system())
+           );
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ControlsRow {
+    Rebind(actions::GameAction),
+    Defaults,
+    Back,
+}
+
+pub struct ControlsMenuData {
+    container: Entity,
+    rows: Vec::<(Entity, Entity, ControlsRow)>, // button, text, what it does
+    selected: usize,
+    // which of the selected action's bindings gets replaced, one past the end adds a new one
+    slot: usize,
+    waiting_for_binding: bool,
+}
+
+pub fn setup_controls_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    action_map: Res<actions::ActionMap>,
+) {
+    let mut row_types = action_map.bindings
+                                  .iter()
+                                  .map(|(action, _)| ControlsRow::Rebind(*action))
+                                  .collect::<Vec::<_>>();
+    row_types.push(ControlsRow::Defaults);
+    row_types.push(ControlsRow::Back);
+
+    let mut rows = vec!();
+    let container = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for row in row_types.iter() {
+                let mut text_entity = None;
+                let button_entity = parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(700.0), Val::Px(34.0)),
+                            margin: Rect::all(Val::Px(2.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        text_entity = Some(parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 26.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        }).id());
+                    })
+                    .id();
+
+                rows.push((button_entity, text_entity.unwrap(), *row));
+            }
+        })
+        .id();
+
+    commands.insert_resource(ControlsMenuData {
+        container,
+        rows,
+        selected: 0,
+        slot: 0,
+        waiting_for_binding: false,
+    });
+}
+
+pub fn cleanup_controls_menu(
+    mut commands: Commands,
+    menu_data: Res<ControlsMenuData>,
+    action_map: Res<actions::ActionMap>,
+) {
+    commands.entity(menu_data.container).despawn_recursive();
+    action_map.save();
+}
+
+pub fn controls_menu(
+    mut state: ResMut<State<crate::AppState>>,
+    mut menu_data: ResMut<ControlsMenuData>,
+    mut action_map: ResMut<actions::ActionMap>,
+    actions: Res<actions::ActionState>,
+    button_materials: Res<ButtonMaterials>,
+    strings: Res<locale::Strings>,
+    mut button_colors: Query<&mut Handle<ColorMaterial>, With<Button>>,
+    mut texts: Query<&mut Text>,
+    interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
+) {
+    if menu_data.waiting_for_binding {
+        // whatever they press next becomes the new binding
+        if let Some(binding) = actions.just_pressed_binding {
+            if let (_, _, ControlsRow::Rebind(action)) = menu_data.rows[menu_data.selected] {
+                println!("Binding {:?} to {:?} in slot {}", action, binding, menu_data.slot);
+                action_map.rebind(action, menu_data.slot, binding);
+            }
+            menu_data.waiting_for_binding = false;
+        }
+    } else {
+        let mut confirmed = actions.just_pressed(actions::GameAction::Confirm);
+        let previously_selected = menu_data.selected;
+
+        if actions.just_pressed(actions::GameAction::MoveUp) {
+            menu_data.selected = if menu_data.selected == 0 { menu_data.rows.len() - 1 } else { menu_data.selected - 1 };
+        }
+
+        if actions.just_pressed(actions::GameAction::MoveDown) {
+            menu_data.selected = (menu_data.selected + 1) % menu_data.rows.len();
+        }
+
+        // left and right pick which of the bindings to change
+        if let ControlsRow::Rebind(action) = menu_data.rows[menu_data.selected].2 {
+            let slots = action_map.get(action).map(|bindings| bindings.len()).unwrap_or(0) + 1;
+            if actions.just_pressed(actions::GameAction::MoveLeft) {
+                menu_data.slot = if menu_data.slot == 0 { slots - 1 } else { menu_data.slot - 1 };
+            }
+            if actions.just_pressed(actions::GameAction::MoveRight) {
+                menu_data.slot = (menu_data.slot + 1) % slots;
+            }
+        }
+
+        // mouse
+        for (button_entity, interaction) in interaction_query.iter() {
+            if let Some(index) = menu_data.rows.iter().position(|(e, _, _)| *e == button_entity) {
+                match *interaction {
+                    Interaction::Clicked => {
+                        menu_data.selected = index;
+                        confirmed = true;
+                    },
+                    Interaction::Hovered => menu_data.selected = index,
+                    _ => ()
+                }
+            }
+        }
+
+        if menu_data.selected != previously_selected {
+            menu_data.slot = 0;
+        }
+
+        if actions.just_pressed(actions::GameAction::Back) {
+            state.set(crate::AppState::MainMenu).unwrap();
+        } else if confirmed {
+            match menu_data.rows[menu_data.selected].2 {
+                ControlsRow::Rebind(_) => menu_data.waiting_for_binding = true,
+                ControlsRow::Defaults => *action_map = actions::ActionMap::default(),
+                ControlsRow::Back => state.set(crate::AppState::MainMenu).unwrap(),
+            }
+        }
+    }
+
+    for (i, (button_entity, text_entity, row)) in menu_data.rows.iter().enumerate() {
+        if let Ok(mut color) = button_colors.get_mut(*button_entity) {
+            *color = if i == menu_data.selected {
+                         button_materials.hovered.clone()
+                     } else {
+                         button_materials.normal.clone()
+                     };
+        }
+
+        if let Ok(mut text) = texts.get_mut(*text_entity) {
+            text.sections[0].value =
+                match row {
+                    ControlsRow::Rebind(action) => {
+                        if i == menu_data.selected && menu_data.waiting_for_binding {
+                            strings.get_with("controls.waiting", &[&strings.get(action.name())])
+                        } else {
+                            let mut names = action_map.get(*action)
+                                                      .map(|bindings| bindings.iter().map(|b| b.name(&strings)).collect::<Vec::<_>>())
+                                                      .unwrap_or_default();
+                            // the slot that would be changed gets brackets, past the end is a new one
+                            if i == menu_data.selected {
+                                names.push("+".to_string());
+                                if let Some(name) = names.get_mut(menu_data.slot) {
+                                    *name = format!("[{}]", name);
+                                }
+                            }
+                            let bindings = names.join(", ");
+                            strings.get_with("controls.binding", &[&strings.get(action.name()), &bindings])
+                        }
+                    },
+                    ControlsRow::Defaults => strings.get("controls.defaults"),
+                    ControlsRow::Back => strings.get("menu.back"),
+                };
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use crate::replay;
 
 pub struct CreditsEvent {}
 pub struct CreditsDelay(pub Timer);
@@ -53,7 +54,7 @@ pub struct EndCredits(f32);
 pub fn update_credits(
     mut commands: Commands,
     mut end_credits: Query<(Entity, &mut EndCredits, &mut Style)>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     mut state: ResMut<State<crate::AppState>>,
     mut windows: ResMut<Windows>,
 ) {
@@ -0,0 +1,436 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{actions, asset_loader, locale, menu::ButtonMaterials, player, rng, theater_outside, Colors, GameState, Kid,
+            SKIN_COLORS, LEG_COLORS, TORSO_COLORS, HAIR_COLORS, replay};
+
+static SAVE_FILE: &str = "kids.ron";
+static PREVIEW_SPIN_SPEED: f32 = 0.8;
+static MAX_NAME_LENGTH: usize = 12;
+
+// the hat is modeled for the grown ups, this puts it on a kid's head
+pub static CAP_DROP: f32 = -1.4;
+
+#[derive(Copy, Clone, PartialEq, Hash, Eq, Debug, Serialize, Deserialize)]
+pub enum Accessory {
+    Cap,
+    Backpack,
+}
+
+fn accessory_name(accessory: Option::<Accessory>) -> &'static str {
+    match accessory {
+        Some(Accessory::Cap) => "accessory.cap",
+        Some(Accessory::Backpack) => "accessory.backpack",
+        None => "accessory.none",
+    }
+}
+
+fn next_accessory(accessory: Option::<Accessory>, step: i32) -> Option::<Accessory> {
+    let all = [None, Some(Accessory::Cap), Some(Accessory::Backpack)];
+    let current = all.iter().position(|a| *a == accessory).unwrap_or(0) as i32;
+    let len = all.len() as i32;
+    all[(((current + step) % len + len) % len) as usize]
+}
+
+fn next_color(palette: &[&str], current: &str, step: i32) -> String {
+    let current = palette.iter().position(|c| *c == current).unwrap_or(0) as i32;
+    let len = palette.len() as i32;
+    palette[(((current + step) % len + len) % len) as usize].to_string()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedKid {
+    pub kid: Kid,
+    pub name: String,
+    pub colors: Colors,
+}
+
+// this is what gets saved to the kids file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SavedKids {
+    pub kids: Vec::<SavedKid>,
+}
+
+impl SavedKids {
+    pub fn load() -> Self {
+        crate::load_ron_file(SAVE_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        crate::save_ron_file(SAVE_FILE, self);
+    }
+
+    pub fn from_game_state(game_state: &GameState) -> Self {
+        SavedKids {
+            kids: game_state.roster
+                            .iter()
+                            .filter_map(|info| {
+                                game_state.kid_colors.get(&info.kid).map(|colors| {
+                                    SavedKid { kid: info.kid, name: info.name.clone(), colors: colors.clone() }
+                                })
+                            })
+                            .collect()
+        }
+    }
+
+    // kids that aren't in the roster anymore are just ignored
+    pub fn apply(&self, game_state: &mut GameState) {
+        for saved in self.kids.iter() {
+            if let Some(info) = game_state.roster.iter_mut().find(|info| info.kid == saved.kid) {
+                info.name = saved.name.clone();
+                game_state.kid_colors.insert(saved.kid, saved.colors.clone());
+            }
+        }
+    }
+}
+
+pub struct CustomizePlugin;
+impl Plugin for CustomizePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+               SystemSet::on_enter(crate::AppState::Customize)
+                   .with_system(setup_customize_menu.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Customize)
+                   .with_system(customize_menu.system().label("customize_menu"))
+                   .with_system(update_preview.system().after("customize_menu"))
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::Customize)
+                   .with_system(cleanup_customize_menu.system())
+           );
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum CustomizeRow {
+    Kid,
+    Name,
+    Skin,
+    HairColor,
+    HairStyle,
+    Torso,
+    Legs,
+    Accessory,
+    Back,
+}
+
+pub struct CustomizeMenuData {
+    container: Entity,
+    camera: Entity,
+    rows: Vec::<(Entity, Entity, CustomizeRow)>, // button, text, what it does
+    selected: usize,
+    kid_index: usize,
+    editing_name: bool,
+    preview: Option::<Entity>,
+    preview_is_stale: bool,
+    preview_angle: f32,
+}
+
+pub fn setup_customize_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    mut game_state: ResMut<GameState>,
+    mut rng: ResMut<rng::GameRng>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+) {
+    // the roster comes from the level data, which is already loaded by the time the menu is up
+    if let Some(level_info) = level_info_assets.get(&level_info_state.handle) {
+        game_state.load_roster(level_info, &mut rng);
+    }
+
+    let row_types = vec!(
+        CustomizeRow::Kid,
+        CustomizeRow::Name,
+        CustomizeRow::Skin,
+        CustomizeRow::HairColor,
+        CustomizeRow::HairStyle,
+        CustomizeRow::Torso,
+        CustomizeRow::Legs,
+        CustomizeRow::Accessory,
+        CustomizeRow::Back,
+    );
+
+    let mut rows = vec!();
+    let container = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(45.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            for row in row_types.iter() {
+                let mut text_entity = None;
+                let button_entity = parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(420.0), Val::Px(40.0)),
+                            margin: Rect::all(Val::Px(2.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        text_entity = Some(parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "",
+                                TextStyle {
+                                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                    font_size: 28.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        }).id());
+                    })
+                    .id();
+
+                rows.push((button_entity, text_entity.unwrap(), *row));
+            }
+        })
+        .id();
+
+    // the kid stands at the origin, this puts them on the right half of the screen
+    let camera = commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_xyz(3.0, 0.8, 1.0)
+                                 .looking_at(Vec3::new(0.0, 0.6, 1.0), Vec3::Y),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(LightBundle {
+                transform: Transform::from_xyz(0.0, 2.0, 2.0),
+                light: Light {
+                    fov: 180.0,
+                    intensity: 400.0,
+                    range: 100.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        })
+        .id();
+
+    commands.insert_resource(CustomizeMenuData {
+        container,
+        camera,
+        rows,
+        selected: 0,
+        kid_index: 0,
+        editing_name: false,
+        preview: None,
+        preview_is_stale: true,
+        preview_angle: 0.0,
+    });
+}
+
+pub fn cleanup_customize_menu(
+    mut commands: Commands,
+    menu_data: Res<CustomizeMenuData>,
+    mut game_state: ResMut<GameState>,
+) {
+    commands.entity(menu_data.container).despawn_recursive();
+    commands.entity(menu_data.camera).despawn_recursive();
+    if let Some(preview) = menu_data.preview {
+        commands.entity(preview).despawn_recursive();
+    }
+
+    game_state.saved_kids = SavedKids::from_game_state(&game_state);
+    game_state.saved_kids.save();
+}
+
+pub fn customize_menu(
+    mut state: ResMut<State<crate::AppState>>,
+    mut menu_data: ResMut<CustomizeMenuData>,
+    mut game_state: ResMut<GameState>,
+    actions: Res<actions::ActionState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    button_materials: Res<ButtonMaterials>,
+    strings: Res<locale::Strings>,
+    mut button_colors: Query<&mut Handle<ColorMaterial>, With<Button>>,
+    mut texts: Query<&mut Text>,
+    interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
+) {
+    if game_state.roster.is_empty() {
+        state.set(crate::AppState::MainMenu).unwrap();
+        return;
+    }
+    menu_data.kid_index = menu_data.kid_index.min(game_state.roster.len() - 1);
+    let kid = game_state.roster[menu_data.kid_index].kid;
+
+    if menu_data.editing_name {
+        // typing goes straight into the name until they hit enter
+        let kid_index = menu_data.kid_index;
+        for event in received_characters.iter() {
+            let name = &mut game_state.roster[kid_index].name;
+            if !event.char.is_control() && name.chars().count() < MAX_NAME_LENGTH {
+                name.push(event.char);
+            }
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Back) {
+            game_state.roster[kid_index].name.pop();
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Return) || keyboard_input.just_pressed(KeyCode::Escape) {
+            if game_state.roster[kid_index].name.is_empty() {
+                game_state.roster[kid_index].name = format!("{}", kid.0);
+            }
+            menu_data.editing_name = false;
+        }
+    } else {
+        // don't let whatever was typed before spill into the name later
+        for _ in received_characters.iter() {}
+
+        let mut confirmed = actions.just_pressed(actions::GameAction::Confirm);
+        let mut step = 0;
+
+        if actions.just_pressed(actions::GameAction::MoveUp) {
+            menu_data.selected = if menu_data.selected == 0 { menu_data.rows.len() - 1 } else { menu_data.selected - 1 };
+        }
+
+        if actions.just_pressed(actions::GameAction::MoveDown) {
+            menu_data.selected = (menu_data.selected + 1) % menu_data.rows.len();
+        }
+
+        if actions.just_pressed(actions::GameAction::MoveLeft) {
+            step = -1;
+        }
+
+        if actions.just_pressed(actions::GameAction::MoveRight) {
+            step = 1;
+        }
+
+        // mouse
+        for (button_entity, interaction) in interaction_query.iter() {
+            if let Some(index) = menu_data.rows.iter().position(|(e, _, _)| *e == button_entity) {
+                match *interaction {
+                    Interaction::Clicked => {
+                        menu_data.selected = index;
+                        confirmed = true;
+                    },
+                    Interaction::Hovered => menu_data.selected = index,
+                    _ => ()
+                }
+            }
+        }
+
+        let row = menu_data.rows[menu_data.selected].2;
+        if confirmed {
+            match row {
+                CustomizeRow::Name => menu_data.editing_name = true,
+                CustomizeRow::Back => {
+                    state.set(crate::AppState::MainMenu).unwrap();
+                    return;
+                },
+                // clicking anything else cycles it forward
+                _ => step = 1
+            }
+        }
+
+        if actions.just_pressed(actions::GameAction::Back) {
+            state.set(crate::AppState::MainMenu).unwrap();
+            return;
+        }
+
+        if step != 0 {
+            if row == CustomizeRow::Kid {
+                let len = game_state.roster.len() as i32;
+                menu_data.kid_index = (((menu_data.kid_index as i32 + step) % len + len) % len) as usize;
+            } else if let Some(colors) = game_state.kid_colors.get_mut(&kid) {
+                match row {
+                    CustomizeRow::Skin => colors.skin = next_color(&SKIN_COLORS, &colors.skin, step),
+                    CustomizeRow::HairColor => colors.hair = next_color(&HAIR_COLORS, &colors.hair, step),
+                    CustomizeRow::HairStyle => colors.is_long_hair = !colors.is_long_hair,
+                    CustomizeRow::Torso => colors.torso = next_color(&TORSO_COLORS, &colors.torso, step),
+                    CustomizeRow::Legs => colors.legs = next_color(&LEG_COLORS, &colors.legs, step),
+                    CustomizeRow::Accessory => colors.accessory = next_accessory(colors.accessory, step),
+                    _ => ()
+                }
+            }
+            menu_data.preview_is_stale = true;
+        }
+    }
+
+    let kid = game_state.roster[menu_data.kid_index].kid;
+    let name = game_state.roster[menu_data.kid_index].name.clone();
+    let colors = game_state.colors(kid);
+    for (i, (button_entity, text_entity, row)) in menu_data.rows.iter().enumerate() {
+        if let Ok(mut color) = button_colors.get_mut(*button_entity) {
+            *color = if i == menu_data.selected {
+                         button_materials.hovered.clone()
+                     } else {
+                         button_materials.normal.clone()
+                     };
+        }
+
+        if let Ok(mut text) = texts.get_mut(*text_entity) {
+            text.sections[0].value =
+                match row {
+                    CustomizeRow::Kid => strings.get_with("customize.kid", &[&(menu_data.kid_index + 1).to_string(),
+                                                                              &game_state.roster.len().to_string()]),
+                    CustomizeRow::Name => {
+                        if menu_data.editing_name {
+                            strings.get_with("customize.name_typing", &[&name])
+                        } else {
+                            strings.get_with("customize.name", &[&name])
+                        }
+                    },
+                    CustomizeRow::Skin => strings.get_with("customize.skin", &[&colors.skin]),
+                    CustomizeRow::HairColor => strings.get_with("customize.hair_color", &[&colors.hair]),
+                    CustomizeRow::HairStyle => {
+                        let style = strings.get(if colors.is_long_hair { "customize.hair_long" } else { "customize.hair_short" });
+                        strings.get_with("customize.hair", &[&style])
+                    },
+                    CustomizeRow::Torso => strings.get_with("customize.shirt", &[&colors.torso]),
+                    CustomizeRow::Legs => strings.get_with("customize.pants", &[&colors.legs]),
+                    CustomizeRow::Accessory => strings.get_with("customize.accessory", &[&strings.get(accessory_name(colors.accessory))]),
+                    CustomizeRow::Back => strings.get("menu.back"),
+                };
+        }
+    }
+}
+
+// rebuilds the kid whenever something changes and spins them around so you can see the back
+pub fn update_preview(
+    mut commands: Commands,
+    mut menu_data: ResMut<CustomizeMenuData>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
+    game_state: Res<GameState>,
+    mut transforms: Query<&mut Transform>,
+    time: Res<replay::GameTime>,
+) {
+    menu_data.preview_angle += PREVIEW_SPIN_SPEED * time.delta_seconds();
+
+    if menu_data.preview_is_stale {
+        if let Some(preview) = menu_data.preview {
+            commands.entity(preview).despawn_recursive();
+        }
+
+        menu_data.preview = game_state.roster.get(menu_data.kid_index).map(|info| {
+            player::spawn_kid(&mut commands, &mut materials, &theater_meshes, &game_state, info.kid, Vec3::ZERO)
+        });
+        menu_data.preview_is_stale = false;
+    }
+
+    if let Some(preview) = menu_data.preview {
+        if let Ok(mut transform) = transforms.get_mut(preview) {
+            *transform = Transform::from_rotation(Quat::from_axis_angle(Vec3::Y, menu_data.preview_angle));
+            transform.apply_non_uniform_scale(Vec3::new(player::SCALE, player::SCALE, player::SCALE));
+        }
+    }
+}
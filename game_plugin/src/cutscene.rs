@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 use bevy::app::AppExit;
 use bevy::app::Events;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use bevy::reflect::{TypeUuid};
-use crate::{player,asset_loader,AppState, game_controller, camera, ChangeStateEvent, GameState, 
-            LevelResetEvent, Kid, theater_outside, Mode, BlipEvent};
+use crate::{player,asset_loader,AppState, actions, camera, ChangeStateEvent, GameState, 
+            LevelResetEvent, Kid, theater_outside, Mode, flags, game_settings, ghost, menu, music, replay, backlog, actor, locale};
+use bevy_kira_audio::Audio;
 
 pub struct CutsceneEvent {
 }
@@ -19,30 +20,166 @@ pub struct SpeechText;
 pub struct SpeechBoxEvent {
     text: Option::<String> // hide if empty
 }
+pub struct ChoiceEvent {
+    options: Option::<Vec::<String>> // hide if empty
+}
+pub struct ChoiceList;
+pub struct NamePlate;
+pub struct NamePlateText;
+
+// only blip every so often, a blip per letter is a lot
+static BLIP_TIME: f32 = 0.08;
+
+// how much of the current line has been typed out
+#[derive(Default)]
+pub struct SpeechReveal {
+    text: String,
+    shown: f32,
+    blip_cooldown: f32,
+}
+
+impl SpeechReveal {
+    fn start(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.shown = 0.0;
+        self.blip_cooldown = 0.0;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.shown as usize >= self.text.chars().count()
+    }
+
+    pub fn finish(&mut self) {
+        self.shown = self.text.chars().count() as f32;
+    }
+}
+pub struct ChoiceButton(usize);
+
+// holding fast forward makes everything this much quicker
+static FAST_FORWARD_SCALE: f32 = 4.0;
+// a skip that's still going after this many segments in one frame is probably stuck in a Goto loop
+static MAX_SKIP_STEPS: usize = 1000;
+static SEEN_FILE: &str = "seen_cutscenes.ron";
+
+// ids of cutscenes that have been watched to the end, kept between runs for --auto-skip
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SeenCutscenes {
+    ids: Vec::<String>,
+}
+
+impl SeenCutscenes {
+    pub fn load() -> Self {
+        crate::load_ron_file(SEEN_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        crate::save_ron_file(SEEN_FILE, self);
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.iter().any(|i| i == id)
+    }
+}
 
 pub struct DebugCutsceneTriggerMesh;
 pub struct CurrentCutscene {
     segment_index: usize,
     waiting: Option::<CutsceneWait>,
     cutscene: Option::<Cutscene>,
+    // started by Track segments, these run alongside the main list
+    tracks: Vec::<CutsceneTrack>,
+    // runs straight to the end, anything that changes the game still happens
+    skipping: bool,
+    seen: SeenCutscenes,
+}
+
+pub struct CutsceneTrack {
+    name: String,
+    segments: Vec::<CutsceneSegment>,
+    index: usize,
+    waiting: Option::<CutsceneWait>,
 }
 
 impl CurrentCutscene {
     pub fn trigger(&mut self, segments: Vec::<CutsceneSegment>, level: Level) {
+        self.start(Cutscene {
+                       location: (Vec2::ZERO, 0.0), 
+                       level,
+                       has_been_triggered: false,
+                       segments,
+                       requires: None,
+                       id: None,
+                   });
+    }
+
+    fn start(&mut self, cutscene: Cutscene) {
         self.segment_index = 0;
         self.waiting = None;
-        self.cutscene = Some(Cutscene {
-                            location: (Vec2::ZERO, 0.0), 
-                            level,
-                            has_been_triggered: false,
-                            segments
-                        });
+        self.skipping = false;
+        self.tracks.clear();
+        self.cutscene = Some(cutscene);
+    }
+
+    // cutscenes triggered from code can still be remembered as seen
+    pub fn set_id(&mut self, id: &str) {
+        if let Some(cutscene) = &mut self.cutscene {
+            cutscene.id = Some(id.to_string());
+        }
+    }
+
+    fn has_been_seen(&self) -> bool {
+        match self.cutscene.as_ref().and_then(|c| c.id.as_ref()) {
+            Some(id) => self.seen.contains(id),
+            None => false
+        }
+    }
+
+    fn finish(&mut self) {
+        self.tracks.clear();
+        if let Some(id) = self.cutscene.take().and_then(|c| c.id) {
+            if !self.seen.contains(&id) {
+                self.seen.ids.push(id);
+                self.seen.save();
+            }
+        }
+    }
+
+    // lands on the label itself, the segment after it runs next
+    fn jump_to(&mut self, label: &str) {
+        if let Some(cutscene) = &self.cutscene {
+            match cutscene.segments.iter().position(|s| matches!(s, CutsceneSegment::Label(l) if l == label)) {
+                Some(index) => self.segment_index = index,
+                None => {
+                    println!("No label called {}, ending the cutscene", label);
+                    self.segment_index = cutscene.segments.len();
+                }
+            }
+        }
     }
 }
 
 pub enum CutsceneWait {
     Time(f32),
     Interaction,
+    Choice(ChoiceWait),
+    Camera(camera::CameraTween),
+    Track(String),
+    AllTracks,
+    Actor(usize), // an order handed to the ActorDirector
+}
+
+pub struct ChoiceWait {
+    gotos: Vec::<String>,
+    selected: usize,
+    chosen: Option::<usize>,
+    // the press that finished the last textbox shouldn't also pick an option
+    is_ready: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChoiceOption {
+    pub text: String,
+    pub goto: String,
 }
 
 pub struct CutscenePlugin;
@@ -52,9 +189,14 @@ impl Plugin for CutscenePlugin {
            .insert_resource(CurrentCutscene { 
                segment_index: 0,
                waiting: None,
-               cutscene: None 
+               cutscene: None,
+               tracks: vec!(),
+               skipping: false,
+               seen: SeenCutscenes::load(),
            })
            .add_event::<SpeechBoxEvent>()
+           .add_event::<ChoiceEvent>()
+           .init_resource::<SpeechReveal>()
            .add_event::<CharacterDisplayEvent>()
            .add_system_set(
                SystemSet::on_update(crate::AppState::InGame)
@@ -85,10 +227,14 @@ impl Plugin for CutscenePlugin {
            .add_system_set(
                SystemSet::on_update(crate::AppState::Cutscene)
                    .with_system(handle_speechbox_event.system())
+                   .with_system(reveal_speech.system())
+                   .with_system(handle_choice_event.system())
+                   .with_system(choose_option.system())
                    .with_system(handle_character_display_event.system())
                    .with_system(make_talk.system())
+                   .with_system(update_name_plate.system())
                    //.with_system(debug_move_character.system())
-                   .with_system(update_cutscene.system())
+                   .with_system(update_cutscene.system().label("update_cutscene"))
 
            );
     }
@@ -98,178 +244,510 @@ pub fn update_cutscene(
     mut game_state: ResMut<GameState>,
     mut current_cutscene: ResMut<CurrentCutscene>,
     mut state: ResMut<State<AppState>>,
-    mut exit: (ResMut<Events<AppExit>>, Res<Time>),
-    keyboard_input: Res<Input<KeyCode>>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<Input<GamepadButton>>,
-    gamepad: Option<Res<game_controller::GameController>>,
+    exit: (ResMut<Events<AppExit>>, Res<replay::GameTime>),
+    actions: Res<actions::ActionState>,
     mut speechbox_event_writer: EventWriter<SpeechBoxEvent>, 
+    mut choice_event_writer: EventWriter<ChoiceEvent>, 
     mut character_display_event_writer: EventWriter<CharacterDisplayEvent>, 
-    mut level_reset_event_writer: EventWriter<LevelResetEvent>,
+    resets: (EventWriter<LevelResetEvent>, EventWriter<ghost::AttemptFailedEvent>),
     mut change_state_event_writer: EventWriter<ChangeStateEvent>,
     mut cameras: Query<&mut Transform, With<camera::MainCamera>>,
-    mut action_buffer: Local<Option::<u128>>,
+    players: Query<(&Transform, &player::Player), Without<camera::MainCamera>>,
+    mut speech_reveal: ResMut<SpeechReveal>,
+    mut backlog: ResMut<backlog::Backlog>,
+    settings: Res<game_settings::GameSettings>,
+    mut director: ResMut<actor::ActorDirector>,
 ) {
     let time = exit.1;
     let mut exit = exit.0;
-    let time_buffer = 100;
-    let time_since_startup = time.time_since_startup().as_millis();
-    if let Some(time_since_action) = *action_buffer {
-        if time_since_startup - time_since_action > time_buffer {
-            *action_buffer = None;
-        }
-    }
+    let mut level_reset_event_writer = resets.0;
+    let mut attempt_failed_event_writer = resets.1;
 
-    match &mut current_cutscene.waiting {
-        Some(waiting) => {
-            match waiting {
-                CutsceneWait::Time(wait_time) => {
-                    *wait_time -=  time.delta_seconds();
-                    if *wait_time < 0.0 {
-                        current_cutscene.segment_index += 1;
-                        current_cutscene.waiting = None;
-                    } else {
-                        return;
-                    }
-                },
-                CutsceneWait::Interaction => {
-                    let pressed_buttons = game_controller::get_pressed_buttons(&axes, &buttons, gamepad);
-
-                    if (keyboard_input.just_pressed(KeyCode::Space) 
-                    || keyboard_input.just_pressed(KeyCode::Return) 
-                    || keyboard_input.just_pressed(KeyCode::J) 
-                    || pressed_buttons.contains(&game_controller::GameButton::Action))
-                    && action_buffer.is_none() {
-                        *action_buffer = Some(time.time_since_startup().as_millis());
+    // everything stops while they're reading back through old lines
+    if backlog.is_open {
+        return;
+    }
 
-                        current_cutscene.segment_index += 1;
-                        current_cutscene.waiting = None;
-                    } else {
-                        return;
-                    }
-                }
-            }
-        },
-        None => ()
+    if actions.just_pressed(actions::GameAction::SkipCutscene) {
+        current_cutscene.skipping = true;
+    }
+    if current_cutscene.segment_index == 0 && current_cutscene.waiting.is_none()
+    && settings.auto_skip_seen && current_cutscene.has_been_seen() {
+        println!("Already seen this one, skipping");
+        current_cutscene.skipping = true;
     }
 
-    let current_index = current_cutscene.segment_index;
-    println!("Current index: {}", current_index);
-    match &mut current_cutscene.cutscene {
-        Some(cutscene) =>  {
-            if let Some(segment) = cutscene.segments.get(current_index) {
-                println!("Segment: {:?}", segment);
-                match segment {
-                    CutsceneSegment::Debug(text) => {
-                        println!("{}", text);
-                        current_cutscene.waiting = Some(CutsceneWait::Time(2.0));
-                    },
-                    CutsceneSegment::Crash => {
-                        exit.send(AppExit);
-                    },
-                    CutsceneSegment::Textbox(text) => {
-                        if text.len() == 0 {
-                            speechbox_event_writer.send(SpeechBoxEvent { text: None });
-                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+    let fast_forward = actions.pressed(actions::GameAction::FastForward);
+    let delta = if fast_forward { time.delta_seconds() * FAST_FORWARD_SCALE } else { time.delta_seconds() };
+
+    // normally one segment runs per frame, skipping keeps going until the end or until someone has to pick something
+    let mut steps = 0;
+    loop {
+        let mut jump_to = None;
+        let skipping = current_cutscene.skipping;
+        steps += 1;
+
+        update_tracks(&mut current_cutscene.tracks, delta, skipping, &mut game_state, &mut cameras, &players, &mut director, &mut character_display_event_writer);
+        let running_tracks = current_cutscene.tracks.iter().map(|t| t.name.clone()).collect::<Vec::<_>>();
+
+        match &mut current_cutscene.waiting {
+            Some(waiting) => {
+                match waiting {
+                    CutsceneWait::Time(wait_time) => {
+                        *wait_time -= delta;
+                        if *wait_time < 0.0 || skipping {
+                            current_cutscene.segment_index += 1;
+                            current_cutscene.waiting = None;
                         } else {
-                            speechbox_event_writer.send(SpeechBoxEvent { text: Some(text.to_string()) });
-                            current_cutscene.waiting = Some(CutsceneWait::Interaction);
+                            return;
                         }
                     },
-                    CutsceneSegment::LevelReset => {
-                        level_reset_event_writer.send(LevelResetEvent);
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
-                    },
-                    CutsceneSegment::Delay(delay) => {
-                        current_cutscene.waiting = Some(CutsceneWait::Time(*delay));
-                    },
-                    CutsceneSegment::SetTalking(character) => {
-                        game_state.currently_talking = Some(*character);
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
-                    },
-                    CutsceneSegment::NoTalking => {
-                        game_state.currently_talking = None;
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
-                    },
-                    CutsceneSegment::CharacterPosition(character, position) => {
-                        character_display_event_writer.send(CharacterDisplayEvent {
-                            character_and_position: (character.clone(), position.clone())
-                        });
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                    CutsceneWait::Interaction => {
+                        if skipping {
+                            speech_reveal.finish();
+                            current_cutscene.segment_index += 1;
+                            current_cutscene.waiting = None;
+                        } else if fast_forward && speech_reveal.is_done() {
+                            current_cutscene.segment_index += 1;
+                            current_cutscene.waiting = None;
+                        } else if actions.just_pressed(actions::GameAction::Action)
+                        || actions.just_pressed(actions::GameAction::Confirm) {
+                            // the first press finishes the line, the next one moves on
+                            if !speech_reveal.is_done() {
+                                speech_reveal.finish();
+                                return;
+                            }
+
+                            current_cutscene.segment_index += 1;
+                            current_cutscene.waiting = None;
+                        } else {
+                            return;
+                        }
                     },
-                    CutsceneSegment::LevelSwitch(level) => {
-                        match level {
-                            Level::Lobby => {
-                                change_state_event_writer.send(ChangeStateEvent { target: AppState::Lobby });
-                            },
-                            Level::Movie => {
-                                change_state_event_writer.send(ChangeStateEvent { target: AppState::Movie });
-                            },
-                            Level::Outside => {
-                                change_state_event_writer.send(ChangeStateEvent { target: AppState::InGame });
+                    CutsceneWait::Choice(choice) => {
+                        match choice.chosen {
+                            Some(chosen) => {
+                                jump_to = choice.gotos.get(chosen).cloned();
+                                current_cutscene.waiting = None;
                             },
+                            None => return
                         }
                     },
-                    CutsceneSegment::SetHalfwayMovie => {
-                        game_state.has_seen_half_of_movie = true;
-                        game_state.mode = Mode::Switch;
-                        game_state.last_positions.insert(Kid::A, Some(Vec3::new(21.0, 16.0, 0.0)));
-                        game_state.last_positions.insert(Kid::B, Some(Vec3::new(21.0, 16.0, -1.0)));
-                        game_state.last_positions.insert(Kid::C, Some(Vec3::new(21.0, 16.0, -0.5)));
-                        game_state.last_positions.insert(Kid::D, Some(Vec3::new(21.0, 16.0, 0.5)));
-
-                        level_reset_event_writer.send(LevelResetEvent);
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                    CutsceneWait::Camera(tween) => {
+                        if !update_camera_tween(tween, &mut cameras, &players, &game_state, delta, skipping) {
+                            return;
+                        }
+                        current_cutscene.segment_index += 1;
+                        current_cutscene.waiting = None;
                     },
-                    CutsceneSegment::SetGameIsDone => {
-                        game_state.game_is_done = true;
-                        current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                    CutsceneWait::Track(name) => {
+                        if running_tracks.contains(name) {
+                            return;
+                        }
+                        current_cutscene.segment_index += 1;
+                        current_cutscene.waiting = None;
                     },
-                    CutsceneSegment::CameraPosition(x,y,z, rx, ry, rz, rw, speed) => {
-                        let mut reached_target = false;
-                        for mut transform in cameras.iter_mut() {
-                            transform.translation.x += 
-                                (x - transform.translation.x) 
-                               * speed
-                               * time.delta_seconds();
-                            transform.translation.y += 
-                                (y - transform.translation.y) 
-                               * speed
-                               * time.delta_seconds();
-                            transform.translation.z += 
-                                (z - transform.translation.z) 
-                               * speed
-                               * time.delta_seconds();
-                            let rotation = Quat::from_axis_angle(Vec3::new(*rx, *ry, *rz), *rw);
-                            transform.rotation = transform.rotation.slerp(rotation, time.delta_seconds());
+                    CutsceneWait::AllTracks => {
+                        if !running_tracks.is_empty() {
+                            return;
+                        }
+                        current_cutscene.segment_index += 1;
+                        current_cutscene.waiting = None;
+                    },
+                    CutsceneWait::Actor(id) => {
+                        if skipping {
+                            director.hurry(*id);
+                        } else if !director.is_done(*id) {
+                            return;
+                        }
+                        current_cutscene.segment_index += 1;
+                        current_cutscene.waiting = None;
+                    }
+                }
+            },
+            None => ()
+        }
 
+        if let Some(label) = jump_to {
+            choice_event_writer.send(ChoiceEvent { options: None });
+            current_cutscene.jump_to(&label);
+        }
 
-                            
-                            let translation = Vec3::new(*x, *y, *z);
-                            if transform.translation.distance(translation) < 0.5 && rotation.angle_between(transform.rotation) < 0.05 {
-                                reached_target = true; 
+        let current_index = current_cutscene.segment_index;
+        println!("Current index: {}", current_index);
+        match &mut current_cutscene.cutscene {
+            Some(cutscene) =>  {
+                if let Some(segment) = cutscene.segments.get(current_index) {
+                    println!("Segment: {:?}", segment);
+                    match segment {
+                        CutsceneSegment::Debug(text) => {
+                            println!("{}", text);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(2.0));
+                        },
+                        CutsceneSegment::Crash => {
+                            exit.send(AppExit);
+                        },
+                        CutsceneSegment::Textbox(text) => {
+                            if text.len() == 0 {
+                                speechbox_event_writer.send(SpeechBoxEvent { text: None });
+                                current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                            } else {
+                                if !skipping {
+                                    backlog.add(game_state.currently_talking.map(|c| c.name(&game_state)), text);
+                                }
+                                speechbox_event_writer.send(SpeechBoxEvent { text: Some(text.to_string()) });
+                                current_cutscene.waiting = Some(CutsceneWait::Interaction);
                             }
-                        }
+                        },
+                        CutsceneSegment::Speech(text, character) => {
+                            if !skipping {
+                                backlog.add(Some(character.name(&game_state)), text);
+                            }
+                            game_state.currently_talking = Some(*character);
+                            speechbox_event_writer.send(SpeechBoxEvent { text: Some(text.to_string()) });
+                            current_cutscene.waiting = Some(CutsceneWait::Interaction);
+                        },
+                        CutsceneSegment::Clear(character) => {
+                            character_display_event_writer.send(CharacterDisplayEvent {
+                                character_and_position: (*character, Position::Clear)
+                            });
+                            if game_state.currently_talking == Some(*character) {
+                                game_state.currently_talking = None;
+                            }
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::Choice(options) => {
+                            let gotos = options.iter().map(|o| o.goto.clone()).collect();
+                            choice_event_writer.send(ChoiceEvent { options: Some(options.iter().map(|o| o.text.clone()).collect()) });
+                            // skipping can't pick for them
+                            current_cutscene.skipping = false;
+                            current_cutscene.waiting = Some(CutsceneWait::Choice(ChoiceWait {
+                                                           gotos,
+                                                           selected: 0,
+                                                           chosen: None,
+                                                           is_ready: false,
+                                                       }));
+                        },
+                        CutsceneSegment::Label(_) => {
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::Goto(label) => {
+                            let label = label.clone();
+                            current_cutscene.jump_to(&label);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::SetFlag(name, value) => {
+                            game_state.flags.set(name, *value);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::IncrementFlag(name, amount) => {
+                            game_state.flags.increment(name, *amount);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::If(condition, label) => {
+                            if condition.check(&game_state.flags) {
+                                let label = label.clone();
+                                current_cutscene.jump_to(&label);
+                            }
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::End => {
+                            current_cutscene.finish();
+                        },
+                        CutsceneSegment::LevelReset => {
+                            level_reset_event_writer.send(LevelResetEvent);
+                            attempt_failed_event_writer.send(ghost::AttemptFailedEvent);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::Delay(delay) => {
+                            current_cutscene.waiting = Some(CutsceneWait::Time(*delay));
+                        },
+                        CutsceneSegment::Recruit(kid) => {
+                            if !game_state.party.contains(kid) {
+                                game_state.party.push(*kid);
+                            }
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::SetTalking(character) => {
+                            game_state.currently_talking = Some(*character);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::NoTalking => {
+                            game_state.currently_talking = None;
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::Emote(character, emotion) => {
+                            game_state.set_emotion(*character, *emotion);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::CharacterPosition(character, position) => {
+                            character_display_event_writer.send(CharacterDisplayEvent {
+                                character_and_position: (character.clone(), position.clone())
+                            });
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::LevelSwitch(level) => {
+                            match level {
+                                Level::Lobby => {
+                                    change_state_event_writer.send(ChangeStateEvent { target: AppState::Lobby });
+                                },
+                                Level::Movie => {
+                                    change_state_event_writer.send(ChangeStateEvent { target: AppState::Movie });
+                                },
+                                Level::Outside => {
+                                    change_state_event_writer.send(ChangeStateEvent { target: AppState::InGame });
+                                },
+                            }
+                        },
+                        CutsceneSegment::SetHalfwayMovie => {
+                            game_state.flags.set(flags::HAS_SEEN_HALF_OF_MOVIE, 1);
+                            game_state.mode = Mode::Switch;
+                            game_state.place_party(Vec3::new(21.0, 16.0, 0.0));
 
-                        if reached_target {
+                            level_reset_event_writer.send(LevelResetEvent);
                             current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
-                        }
-                    },
-                    _ => ()
+                        },
+                        CutsceneSegment::SetGameIsDone => {
+                            game_state.flags.set(flags::GAME_IS_DONE, 1);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::CameraPosition(x,y,z, rx, ry, rz, rw, speed) => {
+                            let target = Vec3::new(*x, *y, *z);
+                            let rotation = Quat::from_axis_angle(Vec3::new(*rx, *ry, *rz), *rw);
+                            if move_camera(&mut cameras, target, rotation, *speed, delta, skipping) {
+                                current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                            }
+                        },
+                        CutsceneSegment::CameraMove(camera_move) => {
+                            let from = cameras.iter_mut().next().map(|transform| *transform).unwrap_or_default();
+                            let tween = camera::CameraTween::new(&from, camera_move.clone());
+                            current_cutscene.waiting = Some(CutsceneWait::Camera(tween));
+                        },
+                        CutsceneSegment::Track(name, segments) => {
+                            let track = CutsceneTrack {
+                                            name: name.clone(),
+                                            segments: segments.clone(),
+                                            index: 0,
+                                            waiting: None,
+                                        };
+                            current_cutscene.tracks.push(track);
+                            current_cutscene.waiting = Some(CutsceneWait::Time(0.0));
+                        },
+                        CutsceneSegment::Wait(name) => {
+                            current_cutscene.waiting = Some(CutsceneWait::Track(name.clone()));
+                        },
+                        CutsceneSegment::Sync => {
+                            current_cutscene.waiting = Some(CutsceneWait::AllTracks);
+                        },
+                        CutsceneSegment::Walk(..) | CutsceneSegment::Face(..) | CutsceneSegment::Hop(_)
+                        | CutsceneSegment::SpawnNpc(..) | CutsceneSegment::DespawnNpc(_) => {
+                            // skipping still puts everyone where they end up, just without the walking
+                            let id = director.order(segment.actor_action().unwrap(), skipping);
+                            current_cutscene.waiting = Some(CutsceneWait::Actor(id));
+                        },
+                    }
+                } else {
+                    if current_cutscene.tracks.is_empty() {
+                        println!("Cutscene is over!");
+                        // cutscene must be over
+                        current_cutscene.finish();
+                    } else {
+                        // let anything still running finish first
+                        current_cutscene.waiting = Some(CutsceneWait::AllTracks);
+                    }
                 }
-            } else {
-                println!("Cutscene is over!");
-                // cutscene must be over
-                current_cutscene.cutscene = None;
+            },
+            None => {
+                current_cutscene.segment_index = 0;
+                state.pop().unwrap();
             }
-        },
-        None => {
-            current_cutscene.segment_index = 0;
-            state.pop().unwrap();
+        }
+
+        let keep_going = match current_cutscene.waiting {
+                             Some(CutsceneWait::Choice(_)) | None => false,
+                             _ => true
+                         };
+        if !current_cutscene.skipping || !keep_going {
+            break;
+        }
+        if steps > MAX_SKIP_STEPS {
+            println!("Skipping went on for {} segments, giving up", steps);
+            current_cutscene.skipping = false;
+            break;
         }
     }
 }
 
+// eases the camera toward where the segment wants it, true once it's close enough
+fn move_camera(
+    cameras: &mut Query<&mut Transform, With<camera::MainCamera>>,
+    target: Vec3,
+    rotation: Quat,
+    speed: f32,
+    delta: f32,
+    snap: bool,
+) -> bool {
+    // no camera to move means nothing to wait on, otherwise skipping a track spins on it forever
+    let mut reached_target = cameras.iter_mut().next().is_none();
+    for mut transform in cameras.iter_mut() {
+        if snap {
+            transform.translation = target;
+            transform.rotation = rotation;
+            reached_target = true;
+            continue;
+        }
+
+        transform.translation.x += (target.x - transform.translation.x) * speed * delta;
+        transform.translation.y += (target.y - transform.translation.y) * speed * delta;
+        transform.translation.z += (target.z - transform.translation.z) * speed * delta;
+        transform.rotation = transform.rotation.slerp(rotation, delta);
+
+        if transform.translation.distance(target) < 0.5 && rotation.angle_between(transform.rotation) < 0.05 {
+            reached_target = true; 
+        }
+    }
+
+    reached_target
+}
+
+// moves the camera along a CameraMove, true once it's done
+fn update_camera_tween(
+    tween: &mut camera::CameraTween,
+    cameras: &mut Query<&mut Transform, With<camera::MainCamera>>,
+    players: &Query<(&Transform, &player::Player), Without<camera::MainCamera>>,
+    game_state: &GameState,
+    delta: f32,
+    skipping: bool,
+) -> bool {
+    if skipping {
+        tween.finish();
+    }
+
+    let look_at = tween.look_at().and_then(|look_at| camera::look_at_position(look_at, players, game_state));
+    let mut done = true;
+    for mut transform in cameras.iter_mut() {
+        done = tween.update(&mut transform, delta, look_at);
+    }
+
+    done
+}
+
+// tracks only stage things, anything that talks or changes the story stays on the main list
+fn update_tracks(
+    tracks: &mut Vec::<CutsceneTrack>,
+    delta: f32,
+    skipping: bool,
+    game_state: &mut GameState,
+    cameras: &mut Query<&mut Transform, With<camera::MainCamera>>,
+    players: &Query<(&Transform, &player::Player), Without<camera::MainCamera>>,
+    director: &mut actor::ActorDirector,
+    character_display_event_writer: &mut EventWriter<CharacterDisplayEvent>,
+) {
+    for track in tracks.iter_mut() {
+        loop {
+            match &mut track.waiting {
+                Some(CutsceneWait::Time(wait_time)) => {
+                    *wait_time -= delta;
+                    if *wait_time < 0.0 || skipping {
+                        track.index += 1;
+                        track.waiting = None;
+                    } else {
+                        break;
+                    }
+                },
+                Some(CutsceneWait::Camera(tween)) => {
+                    if update_camera_tween(tween, cameras, players, game_state, delta, skipping) {
+                        track.index += 1;
+                        track.waiting = None;
+                    } else {
+                        break;
+                    }
+                },
+                Some(CutsceneWait::Actor(id)) => {
+                    if skipping {
+                        director.hurry(*id);
+                    } else if !director.is_done(*id) {
+                        break;
+                    }
+                    track.index += 1;
+                    track.waiting = None;
+                },
+                _ => ()
+            }
+
+            let segment = match track.segments.get(track.index) {
+                              Some(segment) => segment,
+                              None => break
+                          };
+
+            match segment {
+                CutsceneSegment::CameraPosition(x,y,z, rx, ry, rz, rw, speed) => {
+                    let target = Vec3::new(*x, *y, *z);
+                    let rotation = Quat::from_axis_angle(Vec3::new(*rx, *ry, *rz), *rw);
+                    if move_camera(cameras, target, rotation, *speed, delta, skipping) {
+                        track.waiting = Some(CutsceneWait::Time(0.0));
+                    }
+                },
+                CutsceneSegment::CameraMove(camera_move) => {
+                    let from = cameras.iter_mut().next().map(|transform| *transform).unwrap_or_default();
+                    track.waiting = Some(CutsceneWait::Camera(camera::CameraTween::new(&from, camera_move.clone())));
+                },
+                CutsceneSegment::CharacterPosition(character, position) => {
+                    character_display_event_writer.send(CharacterDisplayEvent {
+                        character_and_position: (*character, position.clone())
+                    });
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::Clear(character) => {
+                    character_display_event_writer.send(CharacterDisplayEvent {
+                        character_and_position: (*character, Position::Clear)
+                    });
+                    if game_state.currently_talking == Some(*character) {
+                        game_state.currently_talking = None;
+                    }
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::SetTalking(character) => {
+                    game_state.currently_talking = Some(*character);
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::NoTalking => {
+                    game_state.currently_talking = None;
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::Emote(character, emotion) => {
+                    game_state.set_emotion(*character, *emotion);
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::Delay(delay) => {
+                    track.waiting = Some(CutsceneWait::Time(*delay));
+                },
+                CutsceneSegment::Debug(text) => {
+                    println!("{}: {}", track.name, text);
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                },
+                CutsceneSegment::Walk(..) | CutsceneSegment::Face(..) | CutsceneSegment::Hop(_)
+                | CutsceneSegment::SpawnNpc(..) | CutsceneSegment::DespawnNpc(_) => {
+                    let id = director.order(segment.actor_action().unwrap(), skipping);
+                    track.waiting = Some(CutsceneWait::Actor(id));
+                },
+                _ => {
+                    // check_segments should have caught this when the level loaded
+                    println!("{:?} can't go in track {}, ignoring it", segment, track.name);
+                    track.waiting = Some(CutsceneWait::Time(0.0));
+                }
+            }
+
+            // like the main list it's one segment a frame unless skipping
+            if !skipping {
+                break;
+            }
+        }
+    }
+
+    tracks.retain(|track| track.index < track.segments.len());
+}
+
 static CENTER_X: f32 = -0.19999993;
 static CENTER_Y: f32 = -0.9;
 static CENTER_Z: f32 = -2.59;
@@ -348,14 +826,7 @@ pub fn handle_character_display_event(
             }
 
             let color = Color::hex("FCF300").unwrap(); 
-            let kid = match &event.character_and_position.0 {
-                         Character::Dude => Kid::A,
-                         Character::A => Kid::A,
-                         Character::B => Kid::B,
-                         Character::C => Kid::C,
-                         Character::D => Kid::D,
-                         _ => Kid::A,
-                      };
+            let kid = event.character_and_position.0.kid().unwrap_or(Kid::A);
 
             match event.character_and_position {
                 (_, Position::Left) => {
@@ -409,12 +880,13 @@ pub fn handle_character_display_event(
 
 pub fn make_talk(
     game_state: Res<GameState>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
     mut characters: Query<(&CharacterTracker, &Children)>,
-    mut faces: Query<(Entity, &mut Visible), (With<EyesMaterial>, Without<MouthMaterial>)>,
-    mut mouths: Query<(Entity, &mut Visible), (With<MouthMaterial>, Without<EyesMaterial>)>,
+    mut faces: Query<(Entity, &mut Visible, &mut Handle<StandardMaterial>), (With<EyesMaterial>, Without<MouthMaterial>)>,
+    mut mouths: Query<(Entity, &mut Visible, &mut Handle<StandardMaterial>), (With<MouthMaterial>, Without<EyesMaterial>)>,
     mut mouth_open: Local<bool>,
     mut sleep: Local<f32>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     *sleep -= time.delta_seconds();
 
@@ -424,26 +896,44 @@ pub fn make_talk(
     }
 
     for _ in characters.iter_mut() {
-        for (_, mut face) in faces.iter_mut() {
+        for (_, mut face, _) in faces.iter_mut() {
             face.is_visible = true;
         }
-        for (_, mut mouth) in mouths.iter_mut() {
+        for (_, mut mouth, _) in mouths.iter_mut() {
             mouth.is_visible = false;
         }
     }
+
+    // both the eyes and the talking mouth change with whatever face they're making
+    for (c, children) in characters.iter_mut() {
+        let (face_material, talk_material) = theater_meshes.face_materials(game_state.emotion(c.0));
+        for child_entity in children.iter() {
+            if let Ok((_, _, mut material)) = faces.get_mut(*child_entity) {
+                if *material != face_material {
+                    *material = face_material.clone();
+                }
+            }
+            if let Ok((_, _, mut material)) = mouths.get_mut(*child_entity) {
+                if *material != talk_material {
+                    *material = talk_material.clone();
+                }
+            }
+        }
+    }
+
     match game_state.currently_talking {
         Some(currently_talking) => {
             if *mouth_open {
                 for (c, children) in characters.iter_mut() {
                     if currently_talking == c.0 {
                         for child_entity in children.iter() {
-                            for (e, mut mouth) in mouths.iter_mut() {
+                            for (e, mut mouth, _) in mouths.iter_mut() {
                                 if e == *child_entity {
                                     mouth.is_visible = true;
                                 }
                             }
 
-                            for (e, mut face) in faces.iter_mut() {
+                            for (e, mut face, _) in faces.iter_mut() {
                                 if e == *child_entity {
                                     face.is_visible = false;
                                 }
@@ -457,10 +947,47 @@ pub fn make_talk(
     }
 }
 
+// the speaker's name over the corner of the speech box, in their color
+pub fn update_name_plate(
+    game_state: Res<GameState>,
+    speech_reveal: Res<SpeechReveal>,
+    strings: Res<locale::Strings>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+    mut plates: Query<(&Handle<ColorMaterial>, &mut Visible), With<NamePlate>>,
+    mut texts: Query<(&mut Text, &mut Visible), (With<NamePlateText>, Without<NamePlate>)>,
+) {
+    // nobody's name goes up while the box is hidden
+    let speaker = if speech_reveal.text.is_empty() { None } else { game_state.currently_talking };
+    let color = speaker.map(|c| c.color(&game_state));
+
+    for (material, mut visible) in plates.iter_mut() {
+        visible.is_visible = speaker.is_some();
+        if let (Some(color), Some(material)) = (color, color_materials.get_mut(material)) {
+            if material.color != color {
+                material.color = color;
+            }
+        }
+    }
+
+    for (mut text, mut visible) in texts.iter_mut() {
+        visible.is_visible = speaker.is_some();
+        if let (Some(speaker), Some(color)) = (speaker, color) {
+            let name = strings.get(&speaker.name(&game_state));
+            if text.sections[0].value != name {
+                text.sections[0].value = name;
+            }
+
+            // dark text on the light shirts
+            let is_light = color.r() * 0.3 + color.g() * 0.6 + color.b() * 0.1 > 0.5;
+            text.sections[0].style.color = if is_light { Color::BLACK } else { Color::WHITE };
+        }
+    }
+}
+
 pub fn debug_move_character(
     keyboard_input: Res<Input<KeyCode>>,
     mut characters: Query<&mut Transform, With<DebugCharacterMarker>>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     for mut transform in characters.iter_mut() {
         let mut print = false;
@@ -530,35 +1057,200 @@ pub fn debug_move_character(
 pub fn handle_speechbox_event(
     mut speechbox_event_reader: EventReader<SpeechBoxEvent>, 
     mut textbox_visibility: Query<&mut Visible, With<SpeechBox>>,
-    mut textbox_text: Query<&mut Text, With<SpeechText>>,
-    mut handle_blip_event_writer: EventWriter<BlipEvent>,
+    mut speech_reveal: ResMut<SpeechReveal>,
+    strings: Res<locale::Strings>,
 ) {
     for event in speechbox_event_reader.iter() {
+        // textboxes in the level files are string ids
         if let Some(text_to_display) = &event.text {
             println!("Got event to show textbox");
-            for mut textbox_text in textbox_text.iter_mut() {
-                textbox_text.sections[0].value = text_to_display.to_string();
-                handle_blip_event_writer.send(BlipEvent { blip_count: text_to_display.matches(" ").count() + 1 });
-            }
+            speech_reveal.start(&strings.get(text_to_display));
             for mut visibility in textbox_visibility.iter_mut() {
                 visibility.is_visible = true;
             }
         } else {
-            for mut textbox_text in textbox_text.iter_mut() {
-                textbox_text.sections[0].value = "".to_string();
-            }
+            speech_reveal.start("");
             for mut visibility in textbox_visibility.iter_mut() {
                 visibility.is_visible = false;
             }
-            handle_blip_event_writer.send(BlipEvent { blip_count: 0 });
         }
     }
 }
 
+// types the line out a letter at a time, blipping in the voice of whoever is talking
+pub fn reveal_speech(
+    mut speech_reveal: ResMut<SpeechReveal>,
+    mut textbox_text: Query<&mut Text, With<SpeechText>>,
+    game_state: Res<GameState>,
+    settings: Res<game_settings::GameSettings>,
+    time: Res<replay::GameTime>,
+    actions: Res<actions::ActionState>,
+    audio: Res<Audio>,
+    audio_state: Res<music::AudioState>,
+    asset_server: Res<AssetServer>,
+) {
+    let before = speech_reveal.shown as usize;
+    if !speech_reveal.is_done() {
+        let total = speech_reveal.text.chars().count() as f32;
+        let mut speed = settings.text_speed;
+        if actions.pressed(actions::GameAction::FastForward) {
+            speed *= FAST_FORWARD_SCALE;
+        }
+        speech_reveal.shown = (speech_reveal.shown + speed * time.delta_seconds()).min(total);
+    }
+    let after = speech_reveal.shown as usize;
+
+    speech_reveal.blip_cooldown -= time.delta_seconds();
+    let new_letters = speech_reveal.text.chars().skip(before).take(after - before).any(|c| !c.is_whitespace());
+    if new_letters && speech_reveal.blip_cooldown <= 0.0 {
+        speech_reveal.blip_cooldown = BLIP_TIME;
+        if let Some(character) = game_state.currently_talking {
+            music::play_voice(&audio, &audio_state, &asset_server, &character.voice());
+        }
+    }
+
+    let value = speech_reveal.text.chars().take(after).collect::<String>();
+    for mut text in textbox_text.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub fn handle_choice_event(
+    mut commands: Commands,
+    mut choice_event_reader: EventReader<ChoiceEvent>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<menu::ButtonMaterials>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    strings: Res<locale::Strings>,
+    choice_lists: Query<Entity, With<ChoiceList>>,
+) {
+    for event in choice_event_reader.iter() {
+        for entity in choice_lists.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        let options = match &event.options {
+                          Some(options) => options,
+                          None => continue
+                      };
+
+        // stacked on the right side of the speech box
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::ColumnReverse,
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Percent(3.0),
+                        right: Val::Percent(2.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                material: materials.add(Color::NONE.into()),
+                ..Default::default()
+            })
+            .insert(ChoiceList)
+            .insert(CutsceneTrashMarker)
+            .with_children(|parent| {
+                for (i, option) in options.iter().enumerate() {
+                    parent
+                        .spawn_bundle(ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(600.0), Val::Px(50.0)),
+                                margin: Rect::all(Val::Px(4.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            material: if i == 0 { button_materials.hovered.clone() } else { button_materials.normal.clone() },
+                            ..Default::default()
+                        })
+                        .insert(ChoiceButton(i))
+                        .with_children(|parent| {
+                            parent.spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    strings.get(option),
+                                    TextStyle {
+                                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                        font_size: 30.0,
+                                        color: Color::rgb(0.9, 0.9, 0.9),
+                                    },
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            });
+                        });
+                }
+            });
+    }
+}
+
+pub fn choose_option(
+    mut current_cutscene: ResMut<CurrentCutscene>,
+    actions: Res<actions::ActionState>,
+    button_materials: Res<menu::ButtonMaterials>,
+    backlog: Res<backlog::Backlog>,
+    interaction_query: Query<(&ChoiceButton, &Interaction), Changed<Interaction>>,
+    mut buttons: Query<(&ChoiceButton, &mut Handle<ColorMaterial>)>,
+) {
+    if backlog.is_open {
+        return;
+    }
+
+    let choice = match &mut current_cutscene.waiting {
+                     Some(CutsceneWait::Choice(choice)) => choice,
+                     _ => return
+                 };
+
+    if !choice.is_ready {
+        choice.is_ready = true;
+        return;
+    }
+
+    let count = choice.gotos.len();
+    if count == 0 {
+        return;
+    }
+
+    // keyboard and gamepad
+    if actions.just_pressed(actions::GameAction::MoveUp) {
+        choice.selected = (choice.selected + count - 1) % count;
+    }
+    if actions.just_pressed(actions::GameAction::MoveDown) {
+        choice.selected = (choice.selected + 1) % count;
+    }
+    if actions.just_pressed(actions::GameAction::Action)
+    || actions.just_pressed(actions::GameAction::Confirm) {
+        choice.chosen = Some(choice.selected);
+    }
+
+    // mouse
+    for (button, interaction) in interaction_query.iter() {
+        match *interaction {
+            Interaction::Clicked => choice.chosen = Some(button.0),
+            Interaction::Hovered => choice.selected = button.0,
+            _ => ()
+        }
+    }
+
+    for (button, mut material) in buttons.iter_mut() {
+        *material = if button.0 == choice.selected {
+                        button_materials.hovered.clone()
+                    } else {
+                        button_materials.normal.clone()
+                    };
+    }
+}
+
 pub fn cleanup_cutscene(
     trash: Query<Entity, With<CutsceneTrashMarker>>,
+    mut game_state: ResMut<GameState>,
     mut commands: Commands,
 ) {
+    game_state.emotions.clear();
     for entity in trash.iter() {
         commands.entity(entity).despawn_recursive();
     }
@@ -577,14 +1269,15 @@ pub fn check_for_cutscene(
             let player_position = Vec2::new(player.translation.x, player.translation.z);
             for mut cutscene in levels_asset.cutscenes.cutscenes.iter_mut() {
                 if cutscene.level != game_state.current_level || cutscene.has_been_triggered { continue; }
+                if let Some(requires) = &cutscene.requires {
+                    if !requires.check(&game_state.flags) { continue; }
+                }
 
                 let (location, distance) = cutscene.location;
                 if player_position.distance(location) < distance {
                     println!("Cutscene hit");
                     cutscene.has_been_triggered = true;
-                    current_cutscene.cutscene = Some(cutscene.clone());
-                    current_cutscene.segment_index = 0;
-                    current_cutscene.waiting = None;
+                    current_cutscene.start(cutscene.clone());
                     state.push(AppState::Cutscene).unwrap();
                 }
             }
@@ -673,6 +1366,48 @@ pub fn setup_cutscene(
                         });
                 });
         });
+
+    // update_name_plate fills in who's talking
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Percent(30.0),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(8.0)),
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(NamePlate)
+        .insert(CutsceneTrashMarker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(NamePlateText);
+        });
 }
 
 pub fn debug_draw_cutscene_triggers(
@@ -733,25 +1468,109 @@ pub struct Cutscenes {
     cutscenes: Vec::<Cutscene>
 }
 
+impl Cutscenes {
+    pub fn check(&self) -> Result<(), String> {
+        for (i, cutscene) in self.cutscenes.iter().enumerate() {
+            check_segments(&cutscene.segments).map_err(|e| format!("cutscene {}: {}", i, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+// catches anything that would leave a cutscene stuck, runs when the level loads
+pub fn check_segments(segments: &[CutsceneSegment]) -> Result<(), String> {
+    let labels = segments.iter()
+                         .filter_map(|s| match s {
+                             CutsceneSegment::Label(label) => Some(label),
+                             _ => None
+                         })
+                         .collect::<Vec::<_>>();
+
+    for (i, label) in labels.iter().enumerate() {
+        if labels[..i].contains(label) {
+            return Err(format!("label \"{}\" is used more than once", label));
+        }
+    }
+
+    let check_label = |label: &String| {
+        if labels.contains(&label) {
+            Ok(())
+        } else {
+            Err(format!("there's no label called \"{}\"", label))
+        }
+    };
+
+    let tracks = segments.iter()
+                         .filter_map(|s| match s {
+                             CutsceneSegment::Track(name, _) => Some(name),
+                             _ => None
+                         })
+                         .collect::<Vec::<_>>();
+
+    for segment in segments.iter() {
+        match segment {
+            CutsceneSegment::Goto(label) | CutsceneSegment::If(_, label) => check_label(label)?,
+            CutsceneSegment::Track(name, track_segments) => {
+                for track_segment in track_segments.iter() {
+                    match track_segment {
+                        CutsceneSegment::CameraPosition(..) | CutsceneSegment::CameraMove(_) | CutsceneSegment::CharacterPosition(..)
+                        | CutsceneSegment::Clear(_) | CutsceneSegment::SetTalking(_) | CutsceneSegment::NoTalking | CutsceneSegment::Emote(..)
+                        | CutsceneSegment::Delay(_) | CutsceneSegment::Debug(_)
+                        | CutsceneSegment::Walk(..) | CutsceneSegment::Face(..) | CutsceneSegment::Hop(_)
+                        | CutsceneSegment::SpawnNpc(..) | CutsceneSegment::DespawnNpc(_) => (),
+                        _ => return Err(format!("track \"{}\" can only move the camera and characters, it has {:?}", name, track_segment)),
+                    }
+                }
+            },
+            CutsceneSegment::Wait(name) => {
+                if !tracks.contains(&name) {
+                    return Err(format!("there's no track called \"{}\"", name));
+                }
+            },
+            CutsceneSegment::Choice(options) => {
+                if options.len() < 2 || options.len() > 4 {
+                    return Err(format!("choices need 2 to 4 options, one has {}", options.len()));
+                }
+
+                for option in options.iter() {
+                    check_label(&option.goto)?;
+                }
+            },
+            _ => ()
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone, Deserialize, TypeUuid)]
 #[uuid = "49cbdc56-aa9c-3543-8640-a018b74b5052"]
 pub struct Cutscene {
     location: (Vec2, f32), // X,Z and distance to trigger
     level: Level,
     has_been_triggered: bool,
-    segments: Vec::<CutsceneSegment>
+    segments: Vec::<CutsceneSegment>,
+    // only triggers once the story flags match
+    #[serde(default)]
+    requires: Option::<flags::Condition>,
+    // cutscenes with an id are remembered once they've been watched, see --auto-skip
+    #[serde(default)]
+    id: Option::<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, TypeUuid)]
 #[uuid = "49cbdf56-aa9c-3543-8640-bbbbb74b5052"]
 pub enum CutsceneSegment {
     CameraPosition(f32, f32, f32, f32, f32, f32, f32, f32), // position, rotation, speed
+    CameraMove(camera::CameraMove), // takes exactly as long as it says
     Textbox(String),
     Crash,
     CharacterPosition(Character, Position),
     LevelSwitch(Level),
     SetTalking(Character),
     NoTalking,
+    Emote(Character, Emotion), // sticks until it's changed again or the cutscene ends
     Speech(String, Character),
     Clear(Character),
     LevelReset,
@@ -759,6 +1578,36 @@ pub enum CutsceneSegment {
     SetGameIsDone, 
     Debug(String),
     Delay(f32),
+    Recruit(Kid),
+    Choice(Vec::<ChoiceOption>),
+    Label(String),
+    Goto(String),
+    End,
+    SetFlag(String, i32),
+    IncrementFlag(String, i32),
+    If(flags::Condition, String), // jumps to the label when it's true
+    Track(String, Vec::<CutsceneSegment>), // runs alongside everything after it
+    Wait(String), // waits for the named track to finish
+    Sync, // waits for every track to finish
+    Walk(actor::Actor, f32, f32, f32), // x, z, speed
+    Face(actor::Actor, actor::Facing),
+    Hop(actor::Actor),
+    SpawnNpc(String, actor::NpcLook, f32, f32), // name, look, x, z
+    DespawnNpc(String),
+}
+
+impl CutsceneSegment {
+    // the segments that move someone standing in the level
+    fn actor_action(&self) -> Option::<actor::ActorAction> {
+        match self {
+            CutsceneSegment::Walk(actor, x, z, speed) => Some(actor::ActorAction::Walk(actor.clone(), Vec2::new(*x, *z), *speed)),
+            CutsceneSegment::Face(actor, facing) => Some(actor::ActorAction::Face(actor.clone(), facing.clone())),
+            CutsceneSegment::Hop(actor) => Some(actor::ActorAction::Hop(actor.clone())),
+            CutsceneSegment::SpawnNpc(name, look, x, z) => Some(actor::ActorAction::Spawn(name.clone(), look.clone(), Vec2::new(*x, *z))),
+            CutsceneSegment::DespawnNpc(name) => Some(actor::ActorAction::Despawn(name.clone())),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Deserialize, TypeUuid, PartialEq)]
@@ -769,6 +1618,18 @@ pub enum Level {
     Movie,
 }
 
+impl Level {
+    // on_enter systems can run before the level's own load has set current_level
+    pub fn from_state(state: &AppState) -> Option::<Level> {
+        match state {
+            AppState::InGame => Some(Level::Outside),
+            AppState::Lobby => Some(Level::Lobby),
+            AppState::Movie => Some(Level::Movie),
+            _ => None
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, TypeUuid)]
 #[uuid = "4bbbdf56-aa9c-3543-8640-bbbbb74b5052"]
 pub enum Position {
@@ -789,6 +1650,68 @@ pub enum Character {
     C,
     D,
     Mom,
+    Kid(Kid),
+}
+
+impl Character {
+    pub fn from_kid(kid: Kid) -> Character {
+        match kid {
+            Kid::A => Character::A,
+            Kid::B => Character::B,
+            Kid::C => Character::C,
+            Kid::D => Character::D,
+            _ => Character::Kid(kid),
+        }
+    }
+
+    pub fn voice(&self) -> music::Voice {
+        match self {
+            Character::Dude => music::Voice { sound: "sounds/blip.wav", pitch: 0.8 },
+            Character::A => music::Voice { sound: "sounds/blip.wav", pitch: 1.0 },
+            Character::B => music::Voice { sound: "sounds/blip.wav", pitch: 1.15 },
+            Character::C => music::Voice { sound: "sounds/blip.wav", pitch: 1.3 },
+            Character::D => music::Voice { sound: "sounds/blip.wav", pitch: 0.9 },
+            Character::Mom => music::Voice { sound: "sounds/blip.wav", pitch: 0.7 },
+            // recruits are a bit squeakier than the main four so nobody sounds like anybody else
+            Character::Kid(kid) => music::Voice { sound: "sounds/blip.wav", pitch: 1.4 + (kid.0 % 4) as f32 * 0.05 },
+        }
+    }
+
+    // kid names are whatever the player typed in, mom's is a string id
+    pub fn name(&self, game_state: &GameState) -> String {
+        match self.kid() {
+            Some(kid) => game_state.kid_name(kid),
+            None => "name.mom".to_string(),
+        }
+    }
+
+    // the name plate is whatever they're wearing on top
+    pub fn color(&self, game_state: &GameState) -> Color {
+        match self.kid().and_then(|kid| game_state.kid_colors.get(&kid)) {
+            Some(colors) => Color::hex(colors.torso.clone()).unwrap(),
+            None => Color::hex("e63946").unwrap(),
+        }
+    }
+
+    pub fn kid(&self) -> Option::<Kid> {
+        match self {
+            Character::Dude | Character::A => Some(Kid::A),
+            Character::B => Some(Kid::B),
+            Character::C => Some(Kid::C),
+            Character::D => Some(Kid::D),
+            Character::Kid(kid) => Some(*kid),
+            Character::Mom => None,
+        }
+    }
+}
+
+// which eyes and mouth the portrait uses, see TheaterMeshes::face_materials
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq)]
+pub enum Emotion {
+    Neutral,
+    Happy,
+    Worried,
+    Shocked,
 }
 
 fn spawn_mom(
@@ -802,8 +1725,8 @@ fn spawn_mom(
     let leg_color = Color::hex("293241").unwrap(); 
     let torso_color = Color::hex("e63946").unwrap(); 
     let hat_color = Color::hex("e63946").unwrap();
-    let mom_skin = Color::hex(game_state.kid_colors[&Kid::D].skin.clone()).unwrap();
-    let hair_color = Color::hex(game_state.kid_colors[&Kid::D].hair.clone()).unwrap();
+    let mom_skin = Color::hex(game_state.colors(Kid::D).skin).unwrap();
+    let hair_color = Color::hex(game_state.colors(Kid::D).hair).unwrap();
 
             commands.spawn_bundle(PbrBundle {
                         transform,
@@ -858,11 +1781,7 @@ fn spawn_character(
     kid: Kid,
     character: Character,
 ) -> Entity {
-    let leg_color = Color::hex(game_state.kid_colors[&kid].legs.clone()).unwrap();
-    let torso_color = Color::hex(game_state.kid_colors[&kid].torso.clone()).unwrap();
-    let skin_color = Color::hex(game_state.kid_colors[&kid].skin.clone()).unwrap();
-    let hair_color = Color::hex(game_state.kid_colors[&kid].hair.clone()).unwrap();
-    let is_long_hair = game_state.kid_colors[&kid].is_long_hair;
+    let colors = game_state.colors(kid);
 
     commands.spawn_bundle(PbrBundle {
                 transform,
@@ -872,35 +1791,7 @@ fn spawn_character(
             .insert(CutsceneTrashMarker)
             .insert(DebugCharacterMarker)
             .with_children(|parent|  {
-                parent.spawn_bundle(PbrBundle {
-                    mesh: theater_meshes.kid_legs.clone(),
-                    material: materials.add(leg_color.into()),
-                    ..Default::default()
-                });
-                parent.spawn_bundle(PbrBundle {
-                    mesh: theater_meshes.kid_torso.clone(),
-                    material: materials.add(torso_color.into()),
-                    ..Default::default()
-                });
-                parent.spawn_bundle(PbrBundle {
-                    mesh: theater_meshes.kid_headhand.clone(),
-                    material: materials.add(skin_color.into()),
-                    ..Default::default()
-                });
-                parent.spawn_bundle(PbrBundle {
-                    mesh: if kid == Kid::D {
-                            theater_meshes.kid_hairtwo.clone()
-                          } else {
-                              if is_long_hair {
-                                  theater_meshes.kid_hairtwo.clone()
-                              } else {
-                                  println!("hair one!");
-                                  theater_meshes.kid_hairone.clone()
-                              }
-                          },
-                    material: materials.add(hair_color.into()),
-                    ..Default::default()
-                });
+                player::spawn_kid_parts(parent, &colors, theater_meshes, materials);
                 parent.spawn_bundle(PbrBundle {
                     mesh: theater_meshes.kid_face.clone(),
                     material: theater_meshes.face_material.clone(),
@@ -920,3 +1811,43 @@ fn spawn_character(
 pub struct EyesMaterial;
 pub struct MouthMaterial;
 pub struct CharacterTracker(Character);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // written the same way the level files write them
+    fn check(segments: &str) -> Result<(), String> {
+        check_segments(&ron::de::from_str::<Vec::<CutsceneSegment>>(segments).unwrap())
+    }
+
+    #[test]
+    fn jumps_need_a_label_to_land_on() {
+        assert!(check(r#"[Label("start"), Delay(1.0), Goto("start")]"#).is_ok());
+        assert!(check(r#"[If(Set("met_mom"), "end"), Label("end")]"#).is_ok());
+
+        assert!(check(r#"[Goto("nowhere")]"#).unwrap_err().contains("nowhere"));
+        assert!(check(r#"[If(Set("met_mom"), "nowhere")]"#).is_err());
+    }
+
+    #[test]
+    fn labels_are_only_used_once() {
+        assert!(check(r#"[Label("again"), Label("again")]"#).unwrap_err().contains("more than once"));
+    }
+
+    #[test]
+    fn tracks_only_move_things_and_can_be_waited_on() {
+        assert!(check(r#"[Track("walk", [Delay(1.0), NoTalking]), Wait("walk")]"#).is_ok());
+
+        assert!(check(r#"[Track("walk", [Goto("end")]), Label("end")]"#).is_err());
+        assert!(check(r#"[Wait("walk")]"#).unwrap_err().contains("walk"));
+    }
+
+    #[test]
+    fn choices_have_two_to_four_options_that_go_somewhere() {
+        assert!(check(r#"[Choice([(text: "a", goto: "a"), (text: "b", goto: "b")]), Label("a"), Label("b")]"#).is_ok());
+
+        assert!(check(r#"[Choice([(text: "a", goto: "a")]), Label("a")]"#).is_err());
+        assert!(check(r#"[Choice([(text: "a", goto: "a"), (text: "b", goto: "nowhere")]), Label("a")]"#).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use crate::{player, asset_loader, theater_outside, GameState, level_collision, cutscene, AppState, follow_text::FollowTextEvent, get_colors, Kid};
+use crate::{player, asset_loader, theater_outside, GameState, level_collision, cutscene, AppState, follow_text::FollowTextEvent, get_colors, party, rng, flags, Kid, replay};
 use bevy::render::pipeline::PrimitiveTopology;
 use serde::Deserialize;
 use bevy::reflect::{TypeUuid};
@@ -34,6 +34,8 @@ pub enum EnemyType {
 }
 
 pub struct Cone { }
+// a fainter, longer cone drawn past the real one when a lookout is being played
+pub struct LookoutCone { }
 pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut AppBuilder) {
@@ -49,6 +51,8 @@ impl Plugin for EnemyPlugin {
                     .with_system(update_enemy.system())
                     .with_system(scale_cone.system())
                     .with_system(check_for_player.system())
+                    .with_system(listen_for_players.system())
+                    .with_system(show_lookout_cones.system())
             )
             .add_system_set(
                SystemSet::on_update(crate::AppState::Movie)
@@ -56,6 +60,8 @@ impl Plugin for EnemyPlugin {
                     .with_system(update_enemy.system())
                     .with_system(scale_cone.system())
                     .with_system(check_for_player.system())
+                    .with_system(listen_for_players.system())
+                    .with_system(show_lookout_cones.system())
             )
             .add_system_set(
                SystemSet::on_update(crate::AppState::InGame)
@@ -63,6 +69,8 @@ impl Plugin for EnemyPlugin {
                     .with_system(update_enemy.system())
                     .with_system(scale_cone.system())
                     .with_system(check_for_player.system())
+                    .with_system(listen_for_players.system())
+                    .with_system(show_lookout_cones.system())
             );
     }
 }
@@ -89,13 +97,11 @@ pub fn spawn_enemies(
     theater_meshes: ResMut<theater_outside::TheaterMeshes>,
 
     game_state: Res<GameState>,
+    mut rng: ResMut<rng::GameRng>,
     level_info_state: Res<asset_loader::LevelInfoState>, 
     level_info_assets: ResMut<Assets<asset_loader::LevelInfo>>,
 ) {
-    let leg_color = Color::hex("293241").unwrap(); 
-    let torso_color = Color::hex("e63946").unwrap(); 
-    let hat_color = Color::hex("e63946").unwrap();
-    let other_colors = get_colors();
+    let other_colors = get_colors(rng.rng());
     let vision_color = Color::hex("fdffb6").unwrap();
 
     if let Some(levels_asset) = level_info_assets.get(&level_info_state.handle) {
@@ -103,8 +109,8 @@ pub fn spawn_enemies(
             if enemy_spawn.level != game_state.current_level { continue; }
 
             let skin_color = Color::hex(other_colors.skin.to_string()).unwrap();
-            let mom_skin = Color::hex(game_state.kid_colors[&Kid::D].skin.clone()).unwrap();
-            let hair_color = Color::hex(game_state.kid_colors[&Kid::D].hair.clone()).unwrap();
+            let mom_skin = Color::hex(game_state.colors(Kid::D).skin).unwrap();
+            let hair_color = Color::hex(game_state.colors(Kid::D).hair).unwrap();
 
             let mut transform = Transform::from_translation(Vec3::new(enemy_spawn.location.x as f32, 
                                                                       0.0 as f32, 
@@ -123,48 +129,15 @@ pub fn spawn_enemies(
                         velocity: Vec3::default(),
                         is_patroling: true,
                         is_distracted: false,
+                        distracted_time: 0.0,
+                        investigating: None,
                         enemy_spawn: enemy_spawn.clone()
                     })
                     .with_children(|parent|  {
-                        parent.spawn_bundle(PbrBundle {
-                            mesh: theater_meshes.legs.clone(),
-                            material: materials.add(leg_color.into()),
-                            ..Default::default()
-                        });
-                        parent.spawn_bundle(PbrBundle {
-                            mesh: theater_meshes.torso.clone(),
-                            material: materials.add(torso_color.into()),
-                            ..Default::default()
-                        });
-                        parent.spawn_bundle(PbrBundle {
-                            mesh: theater_meshes.headhand.clone(),
-                            material: match enemy_spawn.enemy_type {
-                                        EnemyType::Mom(_) => materials.add(mom_skin.into()),
-                                        _ => materials.add(skin_color.into()),
-                                      },
-                            ..Default::default()
-                        });
                         match enemy_spawn.enemy_type {
-                            EnemyType::Mom(_) => {
-                                parent.spawn_bundle(PbrBundle {
-                                    mesh: theater_meshes.hairtwo.clone(),
-                                    material: materials.add(hair_color.into()),
-                                    ..Default::default()
-                                });
-                            }, 
-                            _ => {
-                                parent.spawn_bundle(PbrBundle {
-                                    mesh: theater_meshes.hat.clone(),
-                                    material: materials.add(hat_color.into()),
-                                    ..Default::default()
-                                });
-                            }
+                            EnemyType::Mom(_) => spawn_grown_up_parts(parent, &mut materials, &theater_meshes, mom_skin, Some(hair_color)),
+                            _ => spawn_grown_up_parts(parent, &mut materials, &theater_meshes, skin_color, None),
                         }
-                        parent.spawn_bundle(PbrBundle {
-                            mesh: theater_meshes.face.clone(),
-                            material: theater_meshes.face_material.clone(),
-                            ..Default::default()
-                        });
 
                         match enemy_spawn.enemy_type {
                             EnemyType::Mom(_) | EnemyType::Patrol(_) => {
@@ -185,6 +158,25 @@ pub fn spawn_enemies(
                                     },
                                     ..Default::default()
                                 }).insert(Cone {});
+
+                                // just a heads up, it doesn't change how far they actually see
+                                let warning_color = Color::rgba(vision_color.r(), vision_color.g(), vision_color.b(), 0.25);
+                                parent.spawn_bundle(PbrBundle {
+                                    mesh: enemy_meshes.fov_cone.clone(),
+                                    material: materials.add(warning_color.into()),
+                                    visible: Visible {
+                                        is_visible: false,
+                                        is_transparent: true,
+                                    },
+                                    transform: {
+                                        let mut t = Transform::from_xyz(0.0, -2.25, 0.0);
+                                        let length = VIEW_DISTANCE * party::LOOKOUT_CONE_SCALE;
+                                        t.scale = Vec3::new(length, VIEW_DISTANCE, length);
+
+                                        t
+                                    },
+                                    ..Default::default()
+                                }).insert(LookoutCone {});
                             },
                             _ => ()
                         }
@@ -193,9 +185,59 @@ pub fn spawn_enemies(
     }
 }
 
+// guards get a hat, mom gets her hair
+pub fn spawn_grown_up_parts(
+    parent: &mut ChildBuilder,
+    materials: &mut Assets<StandardMaterial>,
+    theater_meshes: &theater_outside::TheaterMeshes,
+    skin_color: Color,
+    hair_color: Option::<Color>,
+) {
+    let leg_color = Color::hex("293241").unwrap(); 
+    let torso_color = Color::hex("e63946").unwrap(); 
+    let hat_color = Color::hex("e63946").unwrap();
+
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.legs.clone(),
+        material: materials.add(leg_color.into()),
+        ..Default::default()
+    });
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.torso.clone(),
+        material: materials.add(torso_color.into()),
+        ..Default::default()
+    });
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.headhand.clone(),
+        material: materials.add(skin_color.into()),
+        ..Default::default()
+    });
+    match hair_color {
+        Some(hair_color) => {
+            parent.spawn_bundle(PbrBundle {
+                mesh: theater_meshes.hairtwo.clone(),
+                material: materials.add(hair_color.into()),
+                ..Default::default()
+            });
+        }, 
+        None => {
+            parent.spawn_bundle(PbrBundle {
+                mesh: theater_meshes.hat.clone(),
+                material: materials.add(hat_color.into()),
+                ..Default::default()
+            });
+        }
+    }
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.face.clone(),
+        material: theater_meshes.face_material.clone(),
+        ..Default::default()
+    });
+}
+
 pub fn update_enemy(
     mut enemies: Query<(Entity, &mut Transform, &mut Enemy)>, 
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     mut game_state: ResMut<GameState>,
     mut follow_text_event_writer: EventWriter<FollowTextEvent>,
     level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
@@ -204,19 +246,43 @@ pub fn update_enemy(
     for (entity, mut transform, mut enemy) in enemies.iter_mut() {
         match &enemy.enemy_spawn.enemy_type {
              EnemyType::Mom(waypoints) | EnemyType::Patrol(waypoints) => {
+                let waypoints = waypoints.clone();
                 // this is pretty bad but it lets me end the game easier
                 if enemy.target_waypoint >= waypoints.len() - 1
                 && game_state.current_level == cutscene::Level::Movie
-                && !game_state.has_avoided_movie_guard {
-                    game_state.has_avoided_movie_guard = true;
+                && !game_state.flags.is_set(flags::HAS_AVOIDED_MOVIE_GUARD) {
+                    game_state.flags.set(flags::HAS_AVOIDED_MOVIE_GUARD, 1);
                 }
 
-                if game_state.has_avoided_movie_guard { return; }
+                if game_state.flags.is_set(flags::HAS_AVOIDED_MOVIE_GUARD) { return; }
+
+                // something like a whoopee cushion has them stopped in their tracks
+                if enemy.distracted_time > 0.0 {
+                    enemy.distracted_time -= time.delta_seconds();
+                    if enemy.distracted_time <= 0.0 {
+                        enemy.distracted_time = 0.0;
+                        enemy.is_distracted = false;
+                    }
+                    continue;
+                }
 
                 let current_position = Vec2::new(transform.translation.x, transform.translation.z); 
-                if let Some(point) = waypoints.get(enemy.target_waypoint) {
+
+                // they heard something, go check it out before going back to patrolling
+                let target = if let Some((point, wait_time)) = enemy.investigating {
+                                 if current_position.distance(point) < 0.5 {
+                                     let wait_time = wait_time - time.delta_seconds();
+                                     enemy.investigating = if wait_time > 0.0 { Some((point, wait_time)) } else { None };
+                                     continue;
+                                 }
+                                 Some(point)
+                             } else {
+                                 waypoints.get(enemy.target_waypoint).cloned()
+                             };
+
+                if let Some(point) = target.as_ref() {
                     let distance = current_position.distance(*point);
-                    if distance < 0.1 {
+                    if distance < 0.1 && enemy.investigating.is_none() {
                         enemy.target_waypoint 
                             = if enemy.target_waypoint >= waypoints.len() - 1 {
                                   0
@@ -244,7 +310,7 @@ pub fn update_enemy(
                         let levels_asset = level_info_assets.get(&level_info_state.handle);
                         if let Some(level_asset) = levels_asset  {
                             let temp_new_translation = new_translation;
-                            let new_translation = level_collision::fit_in_level(&level_asset, &game_state, transform.translation, new_translation);
+                            let new_translation = level_collision::fit_in_level(&level_asset, &game_state, transform.translation, new_translation, false);
                             if temp_new_translation.x != new_translation.x {
                                 enemy.velocity.x = 0.0;
                             }
@@ -272,10 +338,18 @@ pub fn update_enemy(
                 }
             },
             EnemyType::Ticket(_actually_checks) => {
+                if enemy.distracted_time > 0.0 {
+                    enemy.distracted_time -= time.delta_seconds();
+                    if enemy.distracted_time <= 0.0 {
+                        enemy.distracted_time = 0.0;
+                        enemy.is_distracted = false;
+                    }
+                }
+
                 if enemy.is_distracted {
                     follow_text_event_writer.send(FollowTextEvent {
                         entity,
-                        value: "I'm distracted!".to_string(),
+                        value: "follow.distracted".to_string(),
                         is_player: false,
                         force: false,
                     });
@@ -286,6 +360,23 @@ pub fn update_enemy(
     }
 }
 
+// every guard's view is always shown at how far they really see, a lookout being played also
+// gets a fainter cone past that so there's a warning before walking right up to the edge of it
+fn show_lookout_cones(
+    mut cones: Query<&mut Visible, With<LookoutCone>>,
+    game_state: Res<GameState>,
+) {
+    let has_lookout = game_state.party
+                                .iter()
+                                .any(|kid| game_state.is_controlled(*kid) && game_state.has_trait(*kid, party::KidTrait::Lookout));
+
+    for mut visible in cones.iter_mut() {
+        if visible.is_visible != has_lookout {
+            visible.is_visible = has_lookout;
+        }
+    }
+}
+
 fn scale_cone(
     keyboard_input: Res<Input<KeyCode>>,
     mut cones: Query<&mut Transform, With<Cone>>,
@@ -316,42 +407,28 @@ pub fn check_for_player(
     mut state: ResMut<State<AppState>>,
     mut follow_text_event_writer: EventWriter<FollowTextEvent>,
     game_state: Res<GameState>,
-    player: Query<&Transform, With<player::Player>>,
+    player: Query<(&Transform, &player::Player)>,
 ) {
     for (entity, enemy, transform, children) in enemies.iter() {
         match enemy.enemy_spawn.enemy_type {
             EnemyType::Patrol(_) | EnemyType::Mom(_) => {
+                if enemy.distracted_time > 0.0 { continue; }
 
-     
-                let (axis, mut angle) = transform.rotation.to_axis_angle();
-                if axis.y >= -0.0 {
-                    angle = -angle;
-                } 
-                let view_angle = if game_state.current_level == cutscene::Level::Movie {
-                                    0.1
-                                } else {
-                                    VIEW_ANGLE
-                                };
-                let left_angle = angle - view_angle;
-                let right_angle = angle + view_angle;
-
-                let view_distance = if game_state.current_level == cutscene::Level::Movie {
-                                        VIEW_DISTANCE - 2.7
-                                    } else {
-                                        VIEW_DISTANCE - 0.7
-                                    };
-                let left_vector = Vec2::new(left_angle.cos(), left_angle.sin()).normalize() * (view_distance);
-                let right_vector = Vec2::new(right_angle.cos(), right_angle.sin()).normalize() * (view_distance);
-
-                for p_transform in player.iter() {
-                    let enemy_position = Vec2::new(transform.translation.x, transform.translation.z);
+                for (p_transform, p) in player.iter() {
+                    // a kid sitting still in a box is just a box
+                    if p.is_disguised && p.velocity.length() < player::DISGUISE_MOVEMENT_THRESHOLD { continue; }
+
+                    // they can't see into hiding spots unless they watched the kid get in
+                    if p.is_hidden && !p.seen_hiding { continue; }
+
+                    // sneaking kids are harder to spot, sprinting ones are easier
+                    let triangle = vision_triangle(transform, &game_state, p.movement_mode.detection_scale());
                     let player_position = Vec2::new(p_transform.translation.x, p_transform.translation.z);
-                    let triangle: (Vec2, Vec2, Vec2) = (enemy_position, enemy_position + left_vector, enemy_position + right_vector); 
                     if point_in_triangle(player_position, triangle) {
 
                         follow_text_event_writer.send(FollowTextEvent {
                             entity,
-                            value: "Hey!".to_string(),
+                            value: "follow.hey".to_string(),
                             is_player: false,
                             force: true,
                         });
@@ -360,6 +437,7 @@ pub fn check_for_player(
                             level_collision::random_death_two(&game_state),
                             game_state.current_level
                         );
+                        current_cutscene.set_id("spotted");
                         state.push(AppState::Cutscene).unwrap();
                             
 //                        println!("TRUE {:?} {:?}", player_position, triangle);
@@ -373,7 +451,64 @@ pub fn check_for_player(
     }
 }
 
-fn point_in_triangle(
+static HEARD_INVESTIGATE_TIME: f32 = 2.0;
+
+// guards that hear a kid running around come over to take a look
+pub fn listen_for_players(
+    mut enemies: Query<(&mut Enemy, &Transform), Without<player::Player>>,
+    players: Query<(&player::Player, &Transform), Without<Enemy>>,
+) {
+    for (p, p_transform) in players.iter() {
+        if p.movement_mode != player::MovementMode::Sprint || p.velocity.length() < 0.0001 { continue; }
+
+        let player_position = Vec2::new(p_transform.translation.x, p_transform.translation.z);
+        for (mut enemy, transform) in enemies.iter_mut() {
+            if enemy.distracted_time > 0.0 || enemy.investigating.is_some() { continue; }
+
+            match enemy.enemy_spawn.enemy_type {
+                EnemyType::Patrol(_) | EnemyType::Mom(_) => {
+                    let enemy_position = Vec2::new(transform.translation.x, transform.translation.z);
+                    if enemy_position.distance(player_position) < player::SPRINT_NOISE_DISTANCE {
+                        println!("Guard heard {:?}", p.kid);
+                        enemy.investigating = Some((player_position, HEARD_INVESTIGATE_TIME));
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+}
+
+pub fn vision_triangle(
+    transform: &Transform,
+    game_state: &GameState,
+    distance_scale: f32,
+) -> (Vec2, Vec2, Vec2) {
+    let (axis, mut angle) = transform.rotation.to_axis_angle();
+    if axis.y >= -0.0 {
+        angle = -angle;
+    } 
+    let view_angle = if game_state.current_level == cutscene::Level::Movie {
+                        0.1
+                    } else {
+                        VIEW_ANGLE
+                    };
+    let left_angle = angle - view_angle;
+    let right_angle = angle + view_angle;
+
+    let view_distance = if game_state.current_level == cutscene::Level::Movie {
+                            VIEW_DISTANCE - 2.7
+                        } else {
+                            VIEW_DISTANCE - 0.7
+                        };
+    let left_vector = Vec2::new(left_angle.cos(), left_angle.sin()).normalize() * (view_distance * distance_scale);
+    let right_vector = Vec2::new(right_angle.cos(), right_angle.sin()).normalize() * (view_distance * distance_scale);
+
+    let enemy_position = Vec2::new(transform.translation.x, transform.translation.z);
+    (enemy_position, enemy_position + left_vector, enemy_position + right_vector)
+}
+
+pub fn point_in_triangle(
     p: Vec2,
     t: (Vec2, Vec2, Vec2)
 ) -> bool {
@@ -405,4 +540,6 @@ pub struct Enemy {
     pub is_patroling: bool,
     pub velocity: Vec3,
     pub is_distracted: bool,
+    pub distracted_time: f32,
+    pub investigating: Option::<(Vec2, f32)>,
 }
@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// the flags the game itself checks, anything else is up to the level data
+pub static HAS_SEEN_HALF_OF_MOVIE: &str = "has_seen_half_of_movie";
+pub static HAS_AVOIDED_MOVIE_GUARD: &str = "has_avoided_movie_guard";
+pub static GAME_IS_DONE: &str = "game_is_done";
+
+// story state, flags that were never set are 0
+#[derive(Debug, Clone, Default)]
+pub struct StoryFlags {
+    values: HashMap<String, i32>,
+}
+
+impl StoryFlags {
+    pub fn get(&self, name: &str) -> i32 {
+        *self.values.get(name).unwrap_or(&0)
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        self.get(name) != 0
+    }
+
+    pub fn set(&mut self, name: &str, value: i32) {
+        println!("Flag {} = {}", name, value);
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn increment(&mut self, name: &str, amount: i32) {
+        let value = self.get(name) + amount;
+        self.set(name, value);
+    }
+}
+
+// used by If segments and by cutscene triggers that need something to have happened first
+#[derive(Debug, Clone, Deserialize)]
+pub enum Condition {
+    Set(String),
+    NotSet(String),
+    Equals(String, i32),
+    AtLeast(String, i32),
+    LessThan(String, i32),
+    Not(Box::<Condition>),
+    All(Vec::<Condition>),
+    Any(Vec::<Condition>),
+}
+
+impl Condition {
+    pub fn check(&self, flags: &StoryFlags) -> bool {
+        match self {
+            Condition::Set(name) => flags.is_set(name),
+            Condition::NotSet(name) => !flags.is_set(name),
+            Condition::Equals(name, value) => flags.get(name) == *value,
+            Condition::AtLeast(name, value) => flags.get(name) >= *value,
+            Condition::LessThan(name, value) => flags.get(name) < *value,
+            Condition::Not(condition) => !condition.check(flags),
+            Condition::All(conditions) => conditions.iter().all(|c| c.check(flags)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.check(flags)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags() -> StoryFlags {
+        let mut flags = StoryFlags::default();
+        flags.set("met_mom", 1);
+        flags.set("tickets", 3);
+        flags
+    }
+
+    #[test]
+    fn unset_flags_are_zero() {
+        let flags = flags();
+
+        assert!(Condition::Set("met_mom".to_string()).check(&flags));
+        assert!(!Condition::Set("never_set".to_string()).check(&flags));
+        assert!(Condition::NotSet("never_set".to_string()).check(&flags));
+        assert!(Condition::Equals("never_set".to_string(), 0).check(&flags));
+    }
+
+    #[test]
+    fn compares_counts() {
+        let flags = flags();
+
+        assert!(Condition::Equals("tickets".to_string(), 3).check(&flags));
+        assert!(Condition::AtLeast("tickets".to_string(), 3).check(&flags));
+        assert!(!Condition::AtLeast("tickets".to_string(), 4).check(&flags));
+        assert!(Condition::LessThan("tickets".to_string(), 4).check(&flags));
+        assert!(!Condition::LessThan("tickets".to_string(), 3).check(&flags));
+    }
+
+    #[test]
+    fn combines_conditions() {
+        let flags = flags();
+        let met_mom = Condition::Set("met_mom".to_string());
+        let never_set = Condition::Set("never_set".to_string());
+
+        assert!(!Condition::Not(Box::new(met_mom.clone())).check(&flags));
+        assert!(Condition::All(vec!(met_mom.clone(), Condition::AtLeast("tickets".to_string(), 1))).check(&flags));
+        assert!(!Condition::All(vec!(met_mom.clone(), never_set.clone())).check(&flags));
+        assert!(Condition::Any(vec!(never_set.clone(), met_mom)).check(&flags));
+        assert!(!Condition::Any(vec!(never_set)).check(&flags));
+
+        // nothing to check counts as all of them passing and none of them passing
+        assert!(Condition::All(vec!()).check(&flags));
+        assert!(!Condition::Any(vec!()).check(&flags));
+    }
+
+    #[test]
+    fn increments_from_zero() {
+        let mut flags = StoryFlags::default();
+        flags.increment("tickets", 2);
+        flags.increment("tickets", -1);
+
+        assert_eq!(flags.get("tickets"), 1);
+    }
+}
@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 use bevy::render::camera::Camera;
 
-use crate::{camera, player, GameState};
+use crate::{camera, locale, player, GameState, replay};
 
 pub struct FollowTextEvent {
     pub entity: Entity,
+    // a string id, it gets looked up when it's shown
     pub value: String,
     pub is_player: bool,
     pub force: bool,
@@ -41,9 +42,10 @@ pub fn handle_follow_text_event(
 pub fn update_follow_text(
     windows: Res<Windows>,
     mut follow_text: ResMut<FollowText>,
+    strings: Res<locale::Strings>,
     players: Query<(&Transform, &player::Player)>,
     game_state: ResMut<GameState>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     mut player_text_query: Query<(&mut Style, &CalculatedSize, &mut Text,), (With<FollowTextPlayerMarker>, Without<FollowTextMarker>)>,
     mut text_query: Query<(&mut Style, &CalculatedSize, &mut Text,), (With<FollowTextMarker>, Without<FollowTextPlayerMarker>)>,
     camera_query: Query<(&Camera, &GlobalTransform), With<camera::MainCamera>>,
@@ -54,7 +56,7 @@ pub fn update_follow_text(
         for (mut style, calculated, mut text) in player_text_query.iter_mut() {
             for (transform, player) in players.iter() {
                 if player.kid == game_state.controlling {
-                    text.sections[0].value = follow_text.player_value.clone();
+                    text.sections[0].value = strings.get(&follow_text.player_value);
 
                     match camera.world_to_screen(&windows, camera_transform, transform.translation)
                     {
@@ -78,7 +80,7 @@ pub fn update_follow_text(
         }
 
         for (mut style, calculated, mut text) in text_query.iter_mut() {
-            text.sections[0].value = follow_text.value.clone();
+            text.sections[0].value = strings.get(&follow_text.value);
 
             if let Some(entity) = follow_text.entity {
                 if let Ok(mesh) = mesh_query.get(entity) {
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use crate::{actions, follow_text, player, GameState, Kid, LevelResetEvent};
+
+// the leader drops a breadcrumb every this many units
+static TRAIL_SPACING: f32 = 0.5;
+static MAX_TRAIL_POINTS: usize = 60;
+
+static FILE_GAP: f32 = 1.6;
+static CLUSTER_GAP: f32 = 1.5;
+static CLUSTER_RADIUS: f32 = 1.0;
+static SPREAD_GAP: f32 = 1.0;
+static SPREAD_WIDTH: f32 = 1.8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Formation {
+    SingleFile,
+    Cluster,
+    Spread,
+}
+
+impl Formation {
+    pub fn next(&self) -> Formation {
+        match self {
+            Formation::SingleFile => Formation::Cluster,
+            Formation::Cluster => Formation::Spread,
+            Formation::Spread => Formation::SingleFile,
+        }
+    }
+
+    pub fn shout(&self) -> &'static str {
+        match self {
+            Formation::SingleFile => "shout.single_file",
+            Formation::Cluster => "shout.cluster",
+            Formation::Spread => "shout.spread",
+        }
+    }
+}
+
+pub struct Squad {
+    pub formation: Formation,
+    pub holding: bool,
+    leader: Option::<Kid>,
+    // where the leader has actually walked, newest first
+    trail: VecDeque::<Vec3>,
+}
+
+impl Default for Squad {
+    fn default() -> Self {
+        Squad {
+            formation: Formation::SingleFile,
+            holding: false,
+            leader: None,
+            trail: VecDeque::new(),
+        }
+    }
+}
+
+impl Squad {
+    pub fn clear_trail(&mut self) {
+        self.trail.clear();
+        self.leader = None;
+    }
+
+    // how far back along the trail to walk before stopping
+    fn point_behind_leader(&self, leader_position: Vec3, distance: f32) -> Vec3 {
+        let mut walked = 0.0;
+        let mut previous = leader_position;
+        for point in self.trail.iter() {
+            let step = previous.distance(*point);
+            if walked + step >= distance && step > 0.0 {
+                return previous + (*point - previous) * ((distance - walked) / step);
+            }
+            walked += step;
+            previous = *point;
+        }
+
+        previous
+    }
+
+    // where the nth of however many followers should be standing
+    pub fn follower_target(&self, leader_position: Vec3, index: usize, follower_count: usize) -> Vec3 {
+        match self.formation {
+            Formation::SingleFile => {
+                self.point_behind_leader(leader_position, FILE_GAP * (index + 1) as f32)
+            },
+            Formation::Cluster => {
+                let center = self.point_behind_leader(leader_position, CLUSTER_GAP);
+                let angle = index as f32 * std::f32::consts::PI * 2.0 / follower_count.max(1) as f32;
+                center + Vec3::new(angle.cos(), 0.0, angle.sin()) * CLUSTER_RADIUS
+            },
+            Formation::Spread => {
+                let center = self.point_behind_leader(leader_position, SPREAD_GAP);
+                let heading = Vec3::new(leader_position.x - center.x, 0.0, leader_position.z - center.z);
+                let side = if heading.length() > 0.001 {
+                               Vec3::new(-heading.z, 0.0, heading.x).normalize()
+                           } else {
+                               Vec3::Z
+                           };
+                let offset = index as f32 - (follower_count as f32 - 1.0) / 2.0;
+                center + side * offset * SPREAD_WIDTH
+            },
+        }
+    }
+}
+
+pub struct FormationPlugin;
+impl Plugin for FormationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Squad>()
+           .add_system(clear_trail_on_level_reset.system())
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::InGame)
+                   .with_system(clear_trail.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Lobby)
+                   .with_system(clear_trail.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Movie)
+                   .with_system(clear_trail.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(formation_input.system())
+                   .with_system(record_leader_trail.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(formation_input.system())
+                   .with_system(record_leader_trail.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(formation_input.system())
+                   .with_system(record_leader_trail.system())
+           );
+    }
+}
+
+pub fn clear_trail(
+    mut squad: ResMut<Squad>,
+) {
+    squad.clear_trail();
+    squad.holding = false;
+}
+
+pub fn clear_trail_on_level_reset(
+    mut level_reset_event_reader: EventReader<LevelResetEvent>,
+    squad: ResMut<Squad>,
+) {
+    if level_reset_event_reader.iter().count() > 0 {
+        clear_trail(squad);
+    }
+}
+
+pub fn formation_input(
+    actions: Res<actions::ActionState>,
+    players: Query<(Entity, &player::Player)>,
+    game_state: Res<GameState>,
+    mut squad: ResMut<Squad>,
+    mut follow_text_event_writer: EventWriter<follow_text::FollowTextEvent>,
+) {
+    let mut shout = None;
+
+    if actions.just_pressed(actions::GameAction::Formation) {
+        squad.formation = squad.formation.next();
+        println!("Formation {:?}", squad.formation);
+        shout = Some(squad.formation.shout());
+    }
+
+    if actions.just_pressed(actions::GameAction::Hold) {
+        squad.holding = !squad.holding;
+        shout = Some(if squad.holding { "shout.hold" } else { "shout.come_on" });
+    }
+
+    if let Some(shout) = shout {
+        if let Some((entity, _)) = players.iter().find(|(_, p)| p.kid == game_state.controlling) {
+            follow_text_event_writer.send(follow_text::FollowTextEvent {
+                entity,
+                value: shout.to_string(),
+                is_player: false,
+                force: true,
+            });
+        }
+    }
+}
+
+pub fn record_leader_trail(
+    players: Query<(&player::Player, &Transform)>,
+    game_state: Res<GameState>,
+    mut squad: ResMut<Squad>,
+) {
+    // a new leader starts a new trail, the old one leads back to someone else
+    if squad.leader != Some(game_state.controlling) {
+        squad.trail.clear();
+        squad.leader = Some(game_state.controlling);
+    }
+
+    if let Some((_, transform)) = players.iter().find(|(p, _)| p.kid == game_state.controlling) {
+        let position = transform.translation;
+        let far_enough = squad.trail
+                              .front()
+                              .map(|last| last.distance(position) >= TRAIL_SPACING)
+                              .unwrap_or(true);
+        if far_enough {
+            squad.trail.push_front(position);
+            squad.trail.truncate(MAX_TRAIL_POINTS);
+        }
+    }
+}
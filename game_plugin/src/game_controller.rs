@@ -1,10 +1,28 @@
 use bevy::prelude::*;
 
-pub struct GameController(Gamepad);
+// every connected pad in the order they showed up, the first one drives player one along with the keyboard
+#[derive(Default)]
+pub struct GameControllers {
+    pub gamepads: Vec::<Gamepad>,
+}
+
+impl GameControllers {
+    pub fn main(&self) -> Option::<Gamepad> {
+        self.gamepads.first().cloned()
+    }
+
+    // pads past the first one can drop in and claim their own kid
+    pub fn extra(&self) -> &[Gamepad] {
+        if self.gamepads.is_empty() {
+            &[]
+        } else {
+            &self.gamepads[1..]
+        }
+    }
+}
 
 pub fn gamepad_connections(
-    mut commands: Commands,
-    my_gamepad: Option<Res<GameController>>,
+    mut controllers: ResMut<GameControllers>,
     mut gamepad_evr: EventReader<GamepadEvent>,
 ) {
     for GamepadEvent(id, kind) in gamepad_evr.iter() {
@@ -12,21 +30,13 @@ pub fn gamepad_connections(
             GamepadEventType::Connected => {
                 println!("New gamepad connected with ID: {:?}", id);
 
-                // if we don't have any gamepad yet, use this one
-                if my_gamepad.is_none() {
-                    commands.insert_resource(GameController(*id));
+                if !controllers.gamepads.contains(id) {
+                    controllers.gamepads.push(*id);
                 }
             }
             GamepadEventType::Disconnected => {
                 println!("Lost gamepad connection with ID: {:?}", id);
-
-                // if it's the one we previously associated with the player,
-                // disassociate it:
-                if let Some(GameController(old_id)) = my_gamepad.as_deref() {
-                    if old_id == id {
-                        commands.remove_resource::<GameController>();
-                    }
-                }
+                controllers.gamepads.retain(|gamepad| gamepad != id);
             }
             // other events are irrelevant
             _ => {}
@@ -34,23 +44,16 @@ pub fn gamepad_connections(
     }
 }
 
-#[derive(PartialEq)]
-pub enum GameButton {
-    Up, Down, Left, Right, Action, Switch, Nothing, Start
-}
-
-pub fn get_pressed_buttons(
+// the analog stick as one vector, x is right and y is up
+pub fn get_movement(
     axes: &Res<Axis<GamepadAxis>>,
-    buttons: &Res<Input<GamepadButton>>,
-    gamepad: Option<Res<GameController>>,
-) -> Vec::<GameButton> {
-    let mut pressed_buttons = vec!();
+    gamepad: Option<Gamepad>,
+    deadzone: f32,
+) -> Vec2 {
     let gamepad = if let Some(gp) = gamepad {
-        // a gamepad is connected, we have the id
-        gp.0
+        gp
     } else {
-        // no gamepad is connected
-        return pressed_buttons;
+        return Vec2::ZERO;
     };
 
     // The joysticks are represented using a separate axis for X and Y
@@ -58,88 +61,26 @@ pub fn get_pressed_buttons(
     let axis_ly = GamepadAxis(gamepad, GamepadAxisType::LeftStickY);
 
     if let (Some(x), Some(y)) = (axes.get(axis_lx), axes.get(axis_ly)) {
-        // combine X and Y into one vector
         let left_stick_pos = Vec2::new(x, y);
+        let length = left_stick_pos.length();
 
-        // implement a dead-zone to ignore small inputs
-        if left_stick_pos.length() > 0.1 {
-            // do something with the position of the left stick
-            if x > 0.0 {
-                pressed_buttons.push(GameButton::Right);
-            }
-            if x < 0.0 {
-                pressed_buttons.push(GameButton::Left);
-            }
-            if y > 0.0 {
-                pressed_buttons.push(GameButton::Up);
-            }
-            if y < 0.0 {
-                pressed_buttons.push(GameButton::Down);
-            }
+        // rescale so the edge of the deadzone is zero instead of jumping straight to it
+        if length > deadzone {
+            let magnitude = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+            return left_stick_pos.normalize() * magnitude;
         }
     }
 
+    // some pads report the dpad as an axis instead of buttons
     let axis_dx = GamepadAxis(gamepad, GamepadAxisType::DPadX);
     let axis_dy = GamepadAxis(gamepad, GamepadAxisType::DPadY);
 
     if let (Some(x), Some(y)) = (axes.get(axis_dx), axes.get(axis_dy)) {
-        // combine X and Y into one vector
-        let left_stick_pos = Vec2::new(x, y);
-
-        // implement a dead-zone to ignore small inputs
-        if left_stick_pos.length() > 0.1 {
-            // do something with the position of the left stick
-            if x > 0.0 {
-                pressed_buttons.push(GameButton::Right);
-            }
-            if x < 0.0 {
-                pressed_buttons.push(GameButton::Left);
-            }
-            if y > 0.0 {
-                pressed_buttons.push(GameButton::Up);
-            }
-            if y < 0.0 {
-                pressed_buttons.push(GameButton::Down);
-            }
+        let dpad = Vec2::new(x, y);
+        if dpad.length() > deadzone {
+            return dpad.normalize();
         }
     }
 
-    let dpad_up = GamepadButton(gamepad, GamepadButtonType::DPadUp);
-    let dpad_down = GamepadButton(gamepad, GamepadButtonType::DPadDown);
-    let dpad_left = GamepadButton(gamepad, GamepadButtonType::DPadLeft);
-    let dpad_right = GamepadButton(gamepad, GamepadButtonType::DPadRight);
-
-    if buttons.pressed(dpad_up) {
-        pressed_buttons.push(GameButton::Up);
-    }
-
-    if buttons.pressed(dpad_down) {
-        pressed_buttons.push(GameButton::Down);
-    }
-
-    if buttons.pressed(dpad_left) {
-        pressed_buttons.push(GameButton::Left);
-    }
-
-    if buttons.pressed(dpad_right) {
-        pressed_buttons.push(GameButton::Right);
-    }
-
-    let action_1 = GamepadButton(gamepad, GamepadButtonType::South);
-    let action_2 = GamepadButton(gamepad, GamepadButtonType::East);
-
-    if buttons.just_pressed(action_1) {
-        pressed_buttons.push(GameButton::Action);
-    }
-
-    if buttons.just_pressed(action_2) {
-        pressed_buttons.push(GameButton::Switch);
-    }
-
-    let start_button = GamepadButton(gamepad, GamepadButtonType::Start);
-    if buttons.just_pressed(start_button) {
-        pressed_buttons.push(GameButton::Start);
-    }
-
-    pressed_buttons
+    Vec2::ZERO
 }
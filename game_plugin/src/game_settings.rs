@@ -3,6 +3,13 @@ use bevy::prelude::*;
 pub struct GameSettings {
     pub player_speed: f32,
     pub player_friction: f32,
+    pub stick_deadzone: f32,
+    // how many failed attempts show up as ghosts, --ghosts 5 on the command line
+    pub ghost_count: usize,
+    // letters per second in cutscenes, --text-speed 80 on the command line
+    pub text_speed: f32,
+    // cutscenes that have been watched before skip themselves, --auto-skip on the command line
+    pub auto_skip_seen: bool,
 }
 
 pub struct GameSettingsPlugin;
@@ -11,6 +18,10 @@ impl Plugin for GameSettingsPlugin {
         app.insert_resource(GameSettings {
                player_speed: 3.0,
                player_friction: 0.15,
+               stick_deadzone: 0.2,
+               ghost_count: crate::arg_value("--ghosts").and_then(|n| n.parse::<usize>().ok()).unwrap_or(3),
+               text_speed: crate::arg_value("--text-speed").and_then(|n| n.parse::<f32>().ok()).unwrap_or(40.0),
+               auto_skip_seen: std::env::args().any(|arg| arg == "--auto-skip"),
            })
            .add_system_set(
               SystemSet::on_update(crate::AppState::InGame)
@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+use crate::{cutscene, game_settings, player, replay, theater_outside, AppState, GameState, Kid, LevelResetEvent};
+
+// how often everyone's position gets written down during an attempt
+static GHOST_SAMPLE_TIME: f32 = 0.1;
+// ten minutes of wandering around is plenty
+static MAX_GHOST_SAMPLES: usize = 6000;
+static GHOST_ALPHA: f32 = 0.3;
+
+// sent when getting caught sends everyone back to the last checkpoint
+pub struct AttemptFailedEvent;
+
+#[derive(Debug, Clone)]
+pub struct GhostPath {
+    kid: Kid,
+    // which sample they showed up on, recruits can join partway through
+    start: usize,
+    points: Vec::<(Vec3, Quat)>,
+}
+
+// the "NO NO NO that won't work" part, earlier tries play back while the kids plan the next one
+#[derive(Default)]
+pub struct Ghosts {
+    level: Option::<cutscene::Level>,
+    // oldest first
+    attempts: Vec::<Vec::<GhostPath>>,
+    current: Vec::<GhostPath>,
+    clock: f32,
+    samples: usize,
+}
+
+impl Ghosts {
+    fn restart_attempt(&mut self) {
+        self.current.clear();
+        self.clock = 0.0;
+        self.samples = 0;
+    }
+
+    fn forget(&mut self) {
+        self.attempts.clear();
+        self.level = None;
+        self.restart_attempt();
+    }
+}
+
+pub struct Ghost {
+    path: GhostPath,
+}
+
+pub struct GhostPlugin;
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Ghosts>()
+           .add_event::<AttemptFailedEvent>()
+           .add_system(handle_failed_attempt.system())
+           .add_system(make_ghosts_translucent.system())
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::MainMenu)
+                   .with_system(forget_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::InGame)
+                   .with_system(enter_level.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Lobby)
+                   .with_system(enter_level.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Movie)
+                   .with_system(enter_level.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::InGame)
+                   .with_system(despawn_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::Lobby)
+                   .with_system(despawn_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::Movie)
+                   .with_system(despawn_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(record_attempt.system())
+                   .with_system(move_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(record_attempt.system())
+                   .with_system(move_ghosts.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(record_attempt.system())
+                   .with_system(move_ghosts.system())
+           );
+    }
+}
+
+fn spawn_ghosts(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    theater_meshes: &theater_outside::TheaterMeshes,
+    game_state: &GameState,
+    ghosts: &Ghosts,
+) {
+    for path in ghosts.attempts.iter().flatten() {
+        let position = match path.points.first() {
+                           Some((position, _)) => *position,
+                           None => continue
+                       };
+
+        let entity = player::spawn_kid(commands, materials, theater_meshes, game_state, path.kid, position);
+        commands.entity(entity).insert(Ghost { path: path.clone() });
+    }
+}
+
+pub fn forget_ghosts(
+    mut ghosts: ResMut<Ghosts>,
+) {
+    ghosts.forget();
+}
+
+// a new level is a new checkpoint, coming back into the same one keeps the old attempts around
+pub fn enter_level(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
+    game_state: Res<GameState>,
+    mut ghosts: ResMut<Ghosts>,
+    state: Res<State<AppState>>,
+) {
+    let level = cutscene::Level::from_state(state.current());
+    if ghosts.level != level {
+        ghosts.forget();
+        ghosts.level = level;
+    }
+
+    ghosts.restart_attempt();
+    spawn_ghosts(&mut commands, &mut materials, &theater_meshes, &game_state, &ghosts);
+}
+
+pub fn despawn_ghosts(
+    mut commands: Commands,
+    ghost_entities: Query<Entity, With<Ghost>>,
+) {
+    for entity in ghost_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn record_attempt(
+    mut ghosts: ResMut<Ghosts>,
+    players: Query<(&player::Player, &Transform)>,
+    time: Res<replay::GameTime>,
+) {
+    ghosts.clock += time.delta_seconds();
+    if ghosts.samples >= MAX_GHOST_SAMPLES || ghosts.clock < ghosts.samples as f32 * GHOST_SAMPLE_TIME {
+        return;
+    }
+
+    let sample = ghosts.samples;
+    for (player, transform) in players.iter() {
+        let point = (transform.translation, transform.rotation);
+        match ghosts.current.iter_mut().find(|path| path.kid == player.kid) {
+            Some(path) => path.points.push(point),
+            None => ghosts.current.push(GhostPath { kid: player.kid, start: sample, points: vec!(point) }),
+        }
+    }
+    ghosts.samples += 1;
+}
+
+pub fn handle_failed_attempt(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
+    game_state: Res<GameState>,
+    settings: Res<game_settings::GameSettings>,
+    mut ghosts: ResMut<Ghosts>,
+    mut attempt_failed_event_reader: EventReader<AttemptFailedEvent>,
+    mut level_reset_event_reader: EventReader<LevelResetEvent>,
+    ghost_entities: Query<Entity, With<Ghost>>,
+) {
+    let failed = attempt_failed_event_reader.iter().count() > 0;
+    let reset = level_reset_event_reader.iter().count() > 0;
+    if !failed && !reset {
+        return;
+    }
+
+    if failed && !ghosts.current.is_empty() {
+        let attempt = std::mem::take(&mut ghosts.current);
+        ghosts.attempts.push(attempt);
+    }
+
+    let extra = ghosts.attempts.len().saturating_sub(settings.ghost_count);
+    ghosts.attempts.drain(..extra);
+
+    // anything else that resets (the pause menu, the halfway point of the movie) just starts over
+    ghosts.restart_attempt();
+
+    for entity in ghost_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_ghosts(&mut commands, &mut materials, &theater_meshes, &game_state, &ghosts);
+}
+
+// spawn_kid gives them solid colors, ghosts get their own see-through copies
+pub fn make_ghosts_translucent(
+    new_ghosts: Query<&Children, Added<Ghost>>,
+    pivots: Query<&Children, With<player::KidPivot>>,
+    mut parts: Query<(&mut Handle<StandardMaterial>, &mut Visible)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for children in new_ghosts.iter() {
+        // the meshes are all under the kid's pivot
+        let pivot_children = children.iter().filter_map(|child| pivots.get(*child).ok());
+        for child in pivot_children.flat_map(|pivot_children| pivot_children.iter()) {
+            if let Ok((mut material, mut visible)) = parts.get_mut(*child) {
+                let (mut base_color, base_color_texture) =
+                    match materials.get(&*material) {
+                        Some(m) => (m.base_color, m.base_color_texture.clone()),
+                        None => continue
+                    };
+                base_color.set_a(GHOST_ALPHA);
+
+                let ghost_material = StandardMaterial {
+                                         base_color,
+                                         base_color_texture,
+                                         ..Default::default()
+                                     };
+                *material = materials.add(ghost_material);
+                visible.is_transparent = true;
+            }
+        }
+    }
+}
+
+pub fn move_ghosts(
+    ghosts: Res<Ghosts>,
+    mut ghost_entities: Query<(&Ghost, &mut Transform)>,
+) {
+    let sample = ghosts.clock / GHOST_SAMPLE_TIME;
+
+    for (ghost, mut transform) in ghost_entities.iter_mut() {
+        let path = &ghost.path;
+        let along = (sample - path.start as f32).max(0.0);
+        let index = along as usize;
+
+        // they stay where they got caught once their attempt runs out
+        if index + 1 >= path.points.len() {
+            if let Some((position, rotation)) = path.points.last() {
+                transform.translation = *position;
+                transform.rotation = *rotation;
+            }
+            continue;
+        }
+
+        let (from_position, from_rotation) = path.points[index];
+        let (to_position, to_rotation) = path.points[index + 1];
+        let t = along - index as f32;
+
+        transform.translation = from_position + (to_position - from_position) * t;
+        transform.rotation = from_rotation.slerp(to_rotation, t);
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use std::collections::HashSet;
+use crate::{asset_loader, enemy, follow_text, level_collision::CollisionShape, player, GameState, Kid};
+
+static HIDING_TEXT: &str = "follow.hiding";
+static HIDE_TEXT: &str = "follow.hide";
+
+// guards notice a kid ducking into a spot a little further out than they'd catch them
+static SEEN_HIDING_DISTANCE_SCALE: f32 = 1.5;
+
+pub struct HideEvent {
+    pub kid: Kid,
+}
+
+pub struct HidingPlugin;
+impl Plugin for HidingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<HideEvent>()
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                    .with_system(handle_hide_event.system())
+                    .with_system(update_hidden_kids.system())
+            )
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                    .with_system(handle_hide_event.system())
+                    .with_system(update_hidden_kids.system())
+            )
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                    .with_system(handle_hide_event.system())
+                    .with_system(update_hidden_kids.system())
+            );
+    }
+}
+
+pub fn is_in_hiding_spot(
+    level_info: &asset_loader::LevelInfo,
+    game_state: &GameState,
+    position: Vec3,
+) -> bool {
+    level_info.collision_info.shapes
+              .iter()
+              .filter(|(level, _)| *level == game_state.current_level)
+              .any(|(_, shape)| {
+                  match shape {
+                      CollisionShape::HidingSpot((r, _)) => {
+                          position.x >= r.bottom_x
+                          && position.x <= r.top_x
+                          && position.z <= r.right_z
+                          && position.z >= r.left_z
+                      },
+                      _ => false
+                  }
+              })
+}
+
+pub fn handle_hide_event(
+    mut hide_event_reader: EventReader<HideEvent>,
+    mut players: Query<(Entity, &mut player::Player, &Transform), Without<enemy::Enemy>>,
+    enemies: Query<(&enemy::Enemy, &Transform), Without<player::Player>>,
+    mut follow_text_event_writer: EventWriter<follow_text::FollowTextEvent>,
+    game_state: Res<GameState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+) {
+    for event in hide_event_reader.iter() {
+        let level_info = match level_info_assets.get(&level_info_state.handle) {
+                             Some(level_info) => level_info,
+                             None => continue
+                         };
+
+        for (entity, mut player, transform) in players.iter_mut() {
+            if player.kid != event.kid { continue; }
+
+            if player.is_hidden {
+                player.is_hidden = false;
+                player.seen_hiding = false;
+                follow_text_event_writer.send(follow_text::FollowTextEvent {
+                    entity,
+                    value: "".to_string(),
+                    is_player: player.kid == game_state.controlling,
+                    force: true,
+                });
+            } else if is_in_hiding_spot(level_info, &game_state, transform.translation) {
+                let player_position = Vec2::new(transform.translation.x, transform.translation.z);
+                player.seen_hiding =
+                    enemies.iter()
+                           .filter(|(enemy, _)| enemy.distracted_time <= 0.0)
+                           .any(|(enemy, enemy_transform)| {
+                               match enemy.enemy_spawn.enemy_type {
+                                   enemy::EnemyType::Patrol(_) | enemy::EnemyType::Mom(_) => {
+                                       let triangle = enemy::vision_triangle(enemy_transform, &game_state, SEEN_HIDING_DISTANCE_SCALE);
+                                       enemy::point_in_triangle(player_position, triangle)
+                                   },
+                                   _ => false
+                               }
+                           });
+                if player.seen_hiding {
+                    println!("Guard saw {:?} hide", player.kid);
+                }
+
+                player.is_hidden = true;
+                player.velocity = Vec3::default();
+                player.movement = None;
+                follow_text_event_writer.send(follow_text::FollowTextEvent {
+                    entity,
+                    value: HIDING_TEXT.to_string(),
+                    is_player: player.kid == game_state.controlling,
+                    force: true,
+                });
+            }
+        }
+    }
+}
+
+// hidden kids disappear into the spot, the controlling kid gets a prompt when they can hide
+pub fn update_hidden_kids(
+    players: Query<(Entity, &player::Player, &Transform, &Children)>,
+    pivots: Query<&Children, With<player::KidPivot>>,
+    mut parts: Query<&mut Visible, With<player::KidPart>>,
+    mut follow_text_event_writer: EventWriter<follow_text::FollowTextEvent>,
+    follow_text: Res<follow_text::FollowText>,
+    game_state: Res<GameState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    mut hidden: Local<HashSet<Entity>>,
+) {
+    for (entity, player, transform, children) in players.iter() {
+        // only the body goes away, the box and whatever they're wearing are left alone
+        if player.is_hidden != hidden.contains(&entity) {
+            if player.is_hidden {
+                hidden.insert(entity);
+            } else {
+                hidden.remove(&entity);
+            }
+
+            for pivot_children in children.iter().filter_map(|child| pivots.get(*child).ok()) {
+                for part in pivot_children.iter() {
+                    if let Ok(mut visible) = parts.get_mut(*part) {
+                        visible.is_visible = !player.is_hidden;
+                    }
+                }
+            }
+        }
+
+        if player.kid != game_state.controlling { continue; }
+
+        if let Some(level_info) = level_info_assets.get(&level_info_state.handle) {
+            let in_spot = is_in_hiding_spot(level_info, &game_state, transform.translation);
+            let text =
+                if player.is_hidden {
+                    if follow_text.player_value != HIDING_TEXT { Some(HIDING_TEXT) } else { None }
+                } else if in_spot && follow_text.player_value.is_empty() {
+                    Some(HIDE_TEXT)
+                } else if !in_spot && follow_text.player_value == HIDE_TEXT {
+                    Some("")
+                } else {
+                    None
+                };
+
+            if let Some(text) = text {
+                follow_text_event_writer.send(follow_text::FollowTextEvent {
+                    entity,
+                    value: text.to_string(),
+                    is_player: true,
+                    force: true,
+                });
+            }
+        }
+    }
+}
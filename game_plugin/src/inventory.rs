@@ -0,0 +1,582 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::{asset_loader, cutscene, enemy, follow_text::FollowTextEvent, locale, player, GameState, Kid, LevelResetEvent, replay};
+
+static PICKUP_DISTANCE: f32 = 1.0;
+static POPCORN_THROW_DISTANCE: f32 = 3.0;
+static POPCORN_NOISE_DISTANCE: f32 = 6.0;
+static POPCORN_LIFETIME: f32 = 8.0;
+static INVESTIGATE_TIME: f32 = 3.0;
+static CUSHION_TRIGGER_DISTANCE: f32 = 1.0;
+static CUSHION_DISTRACT_TIME: f32 = 4.0;
+static HAND_OFF_DISTANCE: f32 = 2.0;
+static DROP_DISTANCE: f32 = 1.5;
+
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq)]
+pub enum Item {
+    Popcorn,
+    CardboardBox,
+    FakeTicket,
+    WhoopeeCushion,
+    Ticket,
+    TicketStub,
+}
+
+impl Item {
+    // a string id, see locale.rs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::Popcorn => "item.popcorn",
+            Item::CardboardBox => "item.cardboard_box",
+            Item::FakeTicket => "item.fake_ticket",
+            Item::WhoopeeCushion => "item.whoopee_cushion",
+            Item::Ticket => "item.ticket",
+            Item::TicketStub => "item.ticket_stub",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Item::Popcorn => Color::hex("fff3b0").unwrap(),
+            Item::CardboardBox => Color::hex("b08968").unwrap(),
+            Item::FakeTicket => Color::hex("ef476f").unwrap(),
+            Item::WhoopeeCushion => Color::hex("f15bb5").unwrap(),
+            Item::Ticket => Color::hex("ffd166").unwrap(),
+            Item::TicketStub => Color::hex("c9a227").unwrap(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PickupSpawnPoint {
+    pub level: cutscene::Level,
+    pub location: Vec2,
+    pub item: Item,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Inventory {
+    pub items: Vec::<Item>,
+    pub selected: usize,
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: Item) {
+        self.items.push(item);
+    }
+
+    pub fn contains(&self, item: Item) -> bool {
+        self.items.contains(&item)
+    }
+
+    // a kid only ever holds the one ticket, torn or not
+    pub fn has_ticket(&self) -> bool {
+        self.contains(Item::Ticket) || self.contains(Item::TicketStub)
+    }
+
+    pub fn selected_item(&self) -> Option::<Item> {
+        self.items.get(self.selected).copied()
+    }
+
+    pub fn take_selected(&mut self) -> Option::<Item> {
+        if self.selected < self.items.len() {
+            let item = self.items.remove(self.selected);
+            if self.selected >= self.items.len() {
+                self.selected = 0;
+            }
+            Some(item)
+        } else {
+            None
+        }
+    }
+
+    // removes the first matching item, used when something is consumed without being selected
+    pub fn remove(&mut self, item: Item) -> bool {
+        if let Some(index) = self.items.iter().position(|i| *i == item) {
+            self.items.remove(index);
+            if self.selected >= self.items.len() {
+                self.selected = 0;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        if self.items.is_empty() {
+            self.selected = 0;
+        } else {
+            self.selected = (self.selected + 1) % self.items.len();
+        }
+    }
+}
+
+pub struct UseGadgetEvent {
+    pub kid: Kid,
+}
+
+// everything this module spawns into a level gets this so the levels can clean it up
+pub struct ItemMarker;
+pub struct Pickup {
+    pub item: Item,
+    // which of the level's pickups this is, dropped items don't have one
+    pub spawn_point: Option::<usize>,
+}
+pub struct ThrownPopcorn {
+    lifetime: f32,
+}
+pub struct PlacedCushion;
+pub struct DisguiseBox;
+pub struct InventoryHudMarker;
+
+pub struct InventoryPlugin;
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app
+            .add_event::<UseGadgetEvent>()
+            .add_system(reset_tickets_on_level_reset.system())
+            .add_system_set(
+               SystemSet::on_enter(crate::AppState::InGame)
+                    .with_system(create_inventory_hud.system())
+            )
+            .add_system_set(
+               SystemSet::on_enter(crate::AppState::Credits)
+                    .with_system(cleanup_inventory_hud.system())
+            )
+            .add_system_set(
+               SystemSet::on_enter(crate::AppState::MainMenu)
+                    .with_system(cleanup_inventory_hud.system())
+            )
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                    .with_system(pickup_items.system())
+                    .with_system(handle_use_gadget_event.system())
+                    .with_system(update_gadgets.system())
+                    .with_system(update_inventory_hud.system())
+            )
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                    .with_system(pickup_items.system())
+                    .with_system(handle_use_gadget_event.system())
+                    .with_system(update_gadgets.system())
+                    .with_system(update_inventory_hud.system())
+            )
+            .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                    .with_system(pickup_items.system())
+                    .with_system(handle_use_gadget_event.system())
+                    .with_system(update_gadgets.system())
+                    .with_system(update_inventory_hud.system())
+            );
+    }
+}
+
+pub fn spawn_pickups(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    game_state: Res<GameState>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+) {
+    if let Some(levels_asset) = level_info_assets.get(&level_info_state.handle) {
+        for (i, pickup) in levels_asset.pickups.iter().enumerate() {
+            if pickup.level != game_state.current_level { continue; }
+            // already in someone's pocket from an earlier visit
+            if game_state.collected_pickups.contains(&i) { continue; }
+
+            spawn_pickup(&mut commands, &mut meshes, &mut materials, pickup.item,
+                         Vec3::new(pickup.location.x, 0.0, pickup.location.y), Some(i));
+        }
+    }
+}
+
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    item: Item,
+    position: Vec3,
+    spawn_point: Option::<usize>,
+) -> Entity {
+    commands.spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.4 })),
+                material: materials.add(item.color().into()),
+                transform: Transform::from_xyz(position.x, position.y + 0.5, position.z),
+                ..Default::default()
+            })
+            .insert(Pickup { item, spawn_point })
+            .insert(ItemMarker)
+            .id()
+}
+
+pub fn pickup_items(
+    mut commands: Commands,
+    mut pickups: Query<(Entity, &mut Transform, &Pickup), Without<player::Player>>,
+    players: Query<(&Transform, &player::Player), Without<Pickup>>,
+    mut game_state: ResMut<GameState>,
+    time: Res<replay::GameTime>,
+) {
+    for (entity, mut pickup_transform, pickup) in pickups.iter_mut() {
+        pickup_transform.rotate(Quat::from_rotation_y(time.delta_seconds()));
+
+        for (player_transform, player) in players.iter() {
+            if !game_state.is_controlled(player.kid) { continue; }
+
+            let pickup_position = Vec2::new(pickup_transform.translation.x, pickup_transform.translation.z);
+            let player_position = Vec2::new(player_transform.translation.x, player_transform.translation.z);
+            if pickup_position.distance(player_position) < PICKUP_DISTANCE {
+                println!("Picked up {:?}", pickup.item);
+                game_state.inventories.entry(player.kid).or_insert_with(Inventory::default).add(pickup.item);
+                if let Some(spawn_point) = pickup.spawn_point {
+                    game_state.collected_pickups.push(spawn_point);
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub fn handle_use_gadget_event(
+    mut commands: Commands,
+    mut use_gadget_event_reader: EventReader<UseGadgetEvent>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut players: Query<(Entity, &mut player::Player, &Transform), Without<enemy::Enemy>>,
+    mut enemies: Query<(&mut enemy::Enemy, &Transform), Without<player::Player>>,
+    mut follow_text_event_writer: EventWriter<FollowTextEvent>,
+    mut game_state: ResMut<GameState>,
+) {
+    for event in use_gadget_event_reader.iter() {
+        let kid_positions = players.iter_mut()
+                                   .map(|(_, player, transform)| (player.kid, transform.translation))
+                                   .collect::<Vec::<_>>();
+
+        for (entity, mut player, transform) in players.iter_mut() {
+            if player.kid != event.kid { continue; }
+
+            let inventory = game_state.inventories.entry(player.kid).or_insert_with(Inventory::default);
+            match inventory.selected_item() {
+                Some(Item::Popcorn) => {
+                    inventory.take_selected();
+
+                    let forward = transform.rotation * Vec3::X;
+                    let forward = Vec3::new(forward.x, 0.0, forward.z).normalize();
+                    let landing = transform.translation + forward * POPCORN_THROW_DISTANCE;
+                    let landing_point = Vec2::new(landing.x, landing.z);
+
+                    commands.spawn_bundle(PbrBundle {
+                                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.2 })),
+                                material: materials.add(Item::Popcorn.color().into()),
+                                transform: Transform::from_xyz(landing.x, transform.translation.y + 0.1, landing.z),
+                                ..Default::default()
+                            })
+                            .insert(ThrownPopcorn { lifetime: POPCORN_LIFETIME })
+                            .insert(ItemMarker);
+
+                    for (mut enemy, enemy_transform) in enemies.iter_mut() {
+                        let enemy_position = Vec2::new(enemy_transform.translation.x, enemy_transform.translation.z);
+                        if enemy_position.distance(landing_point) > POPCORN_NOISE_DISTANCE { continue; }
+
+                        match enemy.enemy_spawn.enemy_type {
+                            enemy::EnemyType::Patrol(_) | enemy::EnemyType::Mom(_) => {
+                                enemy.investigating = Some((landing_point, INVESTIGATE_TIME));
+                            },
+                            enemy::EnemyType::Ticket(_) => {
+                                enemy.is_distracted = true;
+                                enemy.distracted_time = INVESTIGATE_TIME;
+                            },
+                            _ => ()
+                        }
+                    }
+                },
+                Some(Item::CardboardBox) => {
+                    // the box isn't used up, it's just worn or taken off
+                    player.is_disguised = !player.is_disguised;
+                    if player.is_disguised {
+                        let box_entity =
+                            commands.spawn_bundle(PbrBundle {
+                                        mesh: meshes.add(Mesh::from(shape::Cube { size: 4.0 })),
+                                        material: materials.add(Item::CardboardBox.color().into()),
+                                        transform: Transform::from_xyz(0.0, 2.0, 0.0),
+                                        ..Default::default()
+                                    })
+                                    .insert(DisguiseBox)
+                                    .id();
+                        commands.entity(entity).push_children(&[box_entity]);
+                    }
+                },
+                Some(Item::FakeTicket) => {
+                    // this gets checked by the ticket takers, there's nothing to do with it here
+                    follow_text_event_writer.send(FollowTextEvent {
+                        entity,
+                        value: "follow.fake_ticket".to_string(),
+                        is_player: false,
+                        force: true,
+                    });
+                },
+                Some(Item::WhoopeeCushion) => {
+                    inventory.take_selected();
+                    commands.spawn_bundle(PbrBundle {
+                                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.3 })),
+                                material: materials.add(Item::WhoopeeCushion.color().into()),
+                                transform: Transform::from_xyz(transform.translation.x,
+                                                               transform.translation.y + 0.1,
+                                                               transform.translation.z),
+                                ..Default::default()
+                            })
+                            .insert(PlacedCushion)
+                            .insert(ItemMarker);
+                },
+                Some(Item::Ticket) | Some(Item::TicketStub) => {
+                    let item = inventory.take_selected().unwrap();
+
+                    // hand it to whoever is closest that can take it, otherwise just put it down
+                    let nearest_kid = 
+                        kid_positions.iter()
+                                     .filter(|(kid, _)| *kid != player.kid)
+                                     .filter(|(kid, _)| !game_state.inventories.get(kid).map(|i| i.has_ticket()).unwrap_or(false))
+                                     .map(|(kid, position)| (*kid, position.distance(transform.translation)))
+                                     .filter(|(_, distance)| *distance < HAND_OFF_DISTANCE)
+                                     .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                                     .map(|(kid, _)| kid);
+
+                    if let Some(kid) = nearest_kid {
+                        println!("Gave {:?} to {:?}", item, kid);
+                        game_state.inventories.entry(kid).or_insert_with(Inventory::default).add(item);
+                        follow_text_event_writer.send(FollowTextEvent {
+                            entity,
+                            value: "follow.give_item".to_string(),
+                            is_player: false,
+                            force: true,
+                        });
+                    } else {
+                        // drop it in front so we don't immediately pick it back up
+                        let forward = transform.rotation * Vec3::X;
+                        let forward = Vec3::new(forward.x, 0.0, forward.z).normalize();
+                        let drop_position = transform.translation + forward * DROP_DISTANCE;
+                        spawn_pickup(&mut commands, &mut meshes, &mut materials, item, drop_position, None);
+                    }
+                },
+                None => ()
+            }
+        }
+    }
+}
+
+// tickets only count for the attempt they were bought in
+pub fn reset_tickets_on_level_reset(
+    mut commands: Commands,
+    mut level_reset_event_reader: EventReader<LevelResetEvent>,
+    pickups: Query<(Entity, &Pickup)>,
+    mut game_state: ResMut<GameState>,
+) {
+    for _ in level_reset_event_reader.iter() {
+        for inventory in game_state.inventories.values_mut() {
+            while inventory.remove(Item::Ticket) {}
+            while inventory.remove(Item::TicketStub) {}
+        }
+        game_state.admitted = vec!();
+        game_state.tickets_sold = 0;
+
+        for (entity, pickup) in pickups.iter() {
+            match pickup.item {
+                Item::Ticket | Item::TicketStub => commands.entity(entity).despawn_recursive(),
+                _ => ()
+            }
+        }
+    }
+}
+
+pub fn update_gadgets(
+    mut commands: Commands,
+    mut popcorn: Query<(Entity, &mut ThrownPopcorn)>,
+    cushions: Query<(Entity, &Transform), (With<PlacedCushion>, Without<enemy::Enemy>)>,
+    mut enemies: Query<(Entity, &mut enemy::Enemy, &Transform), Without<PlacedCushion>>,
+    players: Query<(Entity, &player::Player, &Children)>,
+    boxes: Query<Entity, With<DisguiseBox>>,
+    mut follow_text_event_writer: EventWriter<FollowTextEvent>,
+    time: Res<replay::GameTime>,
+) {
+    for (entity, mut popcorn) in popcorn.iter_mut() {
+        popcorn.lifetime -= time.delta_seconds();
+        if popcorn.lifetime <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (cushion_entity, cushion_transform) in cushions.iter() {
+        let cushion_position = Vec2::new(cushion_transform.translation.x, cushion_transform.translation.z);
+        for (enemy_entity, mut enemy, enemy_transform) in enemies.iter_mut() {
+            let enemy_position = Vec2::new(enemy_transform.translation.x, enemy_transform.translation.z);
+            if enemy_position.distance(cushion_position) < CUSHION_TRIGGER_DISTANCE {
+                enemy.is_distracted = true;
+                enemy.distracted_time = CUSHION_DISTRACT_TIME;
+                follow_text_event_writer.send(FollowTextEvent {
+                    entity: enemy_entity,
+                    value: "follow.whoopee".to_string(),
+                    is_player: false,
+                    force: true,
+                });
+                commands.entity(cushion_entity).despawn_recursive();
+                break;
+            }
+        }
+    }
+
+    // take the box off of any kid that isn't wearing it anymore
+    for (_, player, children) in players.iter() {
+        if player.is_disguised { continue; }
+        for child in children.iter() {
+            if let Ok(box_entity) = boxes.get(*child) {
+                commands.entity(box_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+// the hud sticks around through the lobby and the movie, it only goes away once
+// the game is over or back on the main menu
+pub fn cleanup_inventory_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<InventoryHudMarker>>,
+) {
+    for entity in huds.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn create_inventory_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    existing_huds: Query<Entity, With<InventoryHudMarker>>,
+) {
+    if existing_huds.iter().count() > 0 {
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "".to_string(),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    ..Default::default()
+                }
+            ),
+            ..Default::default()
+        })
+        .insert(InventoryHudMarker);
+}
+
+pub fn update_inventory_hud(
+    game_state: Res<GameState>,
+    strings: Res<locale::Strings>,
+    mut hud_text: Query<&mut Text, With<InventoryHudMarker>>,
+) {
+    let value =
+        match game_state.inventories.get(&game_state.controlling) {
+            Some(inventory) if !inventory.items.is_empty() => {
+                inventory.items
+                         .iter()
+                         .enumerate()
+                         .map(|(i, item)| {
+                             if i == inventory.selected {
+                                 strings.get_with("hud.selected_item", &[&strings.get(item.name())])
+                             } else {
+                                 strings.get(item.name())
+                             }
+                         })
+                         .collect::<Vec::<_>>()
+                         .join("  ")
+            },
+            _ => "".to_string()
+        };
+
+    for mut text in hud_text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory(items: &[Item]) -> Inventory {
+        let mut inventory = Inventory::default();
+        for item in items.iter() {
+            inventory.add(*item);
+        }
+        inventory
+    }
+
+    #[test]
+    fn removes_the_first_match() {
+        let mut inventory = inventory(&[Item::FakeTicket, Item::Popcorn, Item::FakeTicket]);
+
+        assert!(inventory.remove(Item::FakeTicket));
+        assert_eq!(inventory.items, vec!(Item::Popcorn, Item::FakeTicket));
+        assert!(!inventory.remove(Item::CardboardBox));
+        assert_eq!(inventory.items.len(), 2);
+    }
+
+    #[test]
+    fn selection_stays_in_bounds_after_removing() {
+        let mut inventory = inventory(&[Item::Popcorn, Item::FakeTicket]);
+        inventory.cycle();
+        assert_eq!(inventory.selected_item(), Some(Item::FakeTicket));
+
+        assert!(inventory.remove(Item::FakeTicket));
+        assert_eq!(inventory.selected_item(), Some(Item::Popcorn));
+
+        assert!(inventory.remove(Item::Popcorn));
+        assert_eq!(inventory.selected_item(), None);
+        assert_eq!(inventory.selected, 0);
+    }
+
+    #[test]
+    fn takes_the_selected_item() {
+        let mut inventory = inventory(&[Item::Popcorn, Item::WhoopeeCushion]);
+        inventory.cycle();
+
+        assert_eq!(inventory.take_selected(), Some(Item::WhoopeeCushion));
+        assert_eq!(inventory.selected_item(), Some(Item::Popcorn));
+        assert_eq!(inventory.take_selected(), Some(Item::Popcorn));
+        assert_eq!(inventory.take_selected(), None);
+        assert!(!inventory.contains(Item::Popcorn));
+    }
+
+    #[test]
+    fn a_stub_still_counts_as_a_ticket() {
+        assert!(!inventory(&[Item::FakeTicket]).has_ticket());
+        assert!(inventory(&[Item::TicketStub]).has_ticket());
+        assert!(inventory(&[Item::Popcorn, Item::Ticket]).has_ticket());
+    }
+
+    #[test]
+    fn cycling_wraps_around() {
+        let mut inventory = inventory(&[Item::Popcorn, Item::FakeTicket]);
+        inventory.cycle();
+        inventory.cycle();
+        assert_eq!(inventory.selected, 0);
+
+        let mut empty = Inventory::default();
+        empty.cycle();
+        assert_eq!(empty.selected, 0);
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::{prelude::*,};
 use serde::Deserialize;
 use bevy::reflect::{TypeUuid};
-use crate::{asset_loader, camera, lerp, cutscene, GameState, Kid, player, AppState, enemy};
+use crate::{asset_loader, camera, lerp, cutscene, GameState, Kid, player, AppState, enemy, inventory};
 
 #[derive(Debug, Clone, Deserialize, TypeUuid)]
 #[uuid = "40cadc56-aa9c-4543-8640-a018b74b5052"]
@@ -18,6 +18,9 @@ pub enum CollisionShape {
     TicketCheck((RectangleCollision, Option::<camera::CameraPosition>)),
     Stair(RectangleCollision),
     DespawnPlayer((RectangleCollision, Vec3)),
+    HidingSpot((RectangleCollision, Option::<camera::CameraPosition>)),
+    // only small kids fit through these
+    Vent(RectangleCollision),
     LevelSwitch((RectangleCollision, Option::<camera::CameraPosition>))
 }
 
@@ -32,6 +35,9 @@ pub struct RectangleCollision {
     pub base_height: f32,
 }
 
+// the desk only has one ticket left, the kids have to share
+static MAX_TICKETS_SOLD: usize = 1;
+
 pub fn ticket_checker(
     mut game_state: ResMut<GameState>,
     players: Query<(&Transform, &player::Player)>,
@@ -41,14 +47,10 @@ pub fn ticket_checker(
     level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
     level_info_state: Res<asset_loader::LevelInfoState>, 
 ) {
-    if game_state.has_ticket.contains(&game_state.controlling) {
-        return;
-    }
-
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_info) = levels_asset {
         for (transform, player) in players.iter() {
-            if player.kid != game_state.controlling { continue; }
+            if !game_state.is_controlled(player.kid) || game_state.admitted.contains(&player.kid) { continue; }
 
             for (level, shape) in level_info.collision_info.shapes.iter() {
                 if *level == game_state.current_level {
@@ -68,10 +70,46 @@ pub fn ticket_checker(
                                     && transform.translation.x <= r.top_x 
                                     && transform.translation.z <= r.right_z
                                     && transform.translation.z >= r.left_z {
+                                        let actually_checks =
+                                            match enemy.enemy_spawn.enemy_type {
+                                                enemy::EnemyType::Ticket(actually_checks) => actually_checks,
+                                                _ => false
+                                            };
+                                        let kid_inventory = game_state.inventories.entry(player.kid).or_insert_with(inventory::Inventory::default);
+
+                                        if kid_inventory.contains(inventory::Item::Ticket) {
+                                            // the taker tears it and hands back the stub
+                                            if actually_checks {
+                                                println!("Ticket torn");
+                                                kid_inventory.remove(inventory::Item::Ticket);
+                                                kid_inventory.add(inventory::Item::TicketStub);
+                                            }
+                                            game_state.admitted.push(player.kid);
+                                            return;
+                                        }
+
+                                        if kid_inventory.remove(inventory::Item::FakeTicket) {
+                                            println!("Fake ticket worked!");
+                                            game_state.admitted.push(player.kid);
+                                            return;
+                                        }
+
+                                        if actually_checks && kid_inventory.contains(inventory::Item::TicketStub) {
+                                            // somebody already got in with this one
+                                            current_cutscene.trigger(
+                                                reused_stub_death(player.kid),
+                                                game_state.current_level
+                                            );
+                                            current_cutscene.set_id("reused_stub");
+                                            state.push(AppState::Cutscene).unwrap();
+                                            return;
+                                        }
+
                                         current_cutscene.trigger(
-                                            random_death(&game_state),
+                                            random_death(player.kid),
                                             game_state.current_level
                                         );
+                                        current_cutscene.set_id("no_ticket");
                                         state.push(AppState::Cutscene).unwrap();
                                     }
                                 }
@@ -82,7 +120,15 @@ pub fn ticket_checker(
                                 && transform.translation.x <= r.top_x 
                                 && transform.translation.z <= r.right_z
                                 && transform.translation.z >= r.left_z {
-                                    game_state.has_ticket.push(player.kid.clone());
+                                    let tickets_sold = game_state.tickets_sold;
+                                    let kid_inventory = game_state.inventories.entry(player.kid).or_insert_with(inventory::Inventory::default);
+                                    if tickets_sold < MAX_TICKETS_SOLD
+                                    && !kid_inventory.contains(inventory::Item::Ticket)
+                                    && !kid_inventory.contains(inventory::Item::TicketStub) {
+                                        println!("Bought a ticket");
+                                        kid_inventory.add(inventory::Item::Ticket);
+                                        game_state.tickets_sold += 1;
+                                    }
                                 }
                             },
                         _ => ()
@@ -94,20 +140,29 @@ pub fn ticket_checker(
 }
 
 pub fn random_death(
-    game_state: &ResMut<GameState>,
+    kid: Kid,
+) -> Vec::<cutscene::CutsceneSegment> {
+
+    let character = cutscene::Character::from_kid(kid);
+    vec!(
+        cutscene::CutsceneSegment::CharacterPosition(character, cutscene::Position::Left),
+        cutscene::CutsceneSegment::SetTalking(character),
+        cutscene::CutsceneSegment::Textbox("no_ticket.1".to_string()),
+        cutscene::CutsceneSegment::Textbox("death.try_this".to_string()),
+        cutscene::CutsceneSegment::LevelReset,
+    )
+}
+
+pub fn reused_stub_death(
+    kid: Kid,
 ) -> Vec::<cutscene::CutsceneSegment> {
 
-    let character = match game_state.controlling {
-                        Kid::A => cutscene::Character::A,
-                        Kid::B => cutscene::Character::B,
-                        Kid::C => cutscene::Character::C,
-                        Kid::D => cutscene::Character::D,
-                    };
+    let character = cutscene::Character::from_kid(kid);
     vec!(
         cutscene::CutsceneSegment::CharacterPosition(character, cutscene::Position::Left),
         cutscene::CutsceneSegment::SetTalking(character),
-        cutscene::CutsceneSegment::Textbox("Ahh that won't work, I don't have a ticket!".to_string()),
-        cutscene::CutsceneSegment::Textbox("I know, I'll try this...".to_string()),
+        cutscene::CutsceneSegment::Textbox("reused_stub.1".to_string()),
+        cutscene::CutsceneSegment::Textbox("death.try_this".to_string()),
         cutscene::CutsceneSegment::LevelReset,
     )
 }
@@ -116,17 +171,12 @@ pub fn random_death_two(
     game_state: &Res<GameState>,
 ) -> Vec::<cutscene::CutsceneSegment> {
 
-    let character = match game_state.controlling {
-                        Kid::A => cutscene::Character::A,
-                        Kid::B => cutscene::Character::B,
-                        Kid::C => cutscene::Character::C,
-                        Kid::D => cutscene::Character::D,
-                    };
+    let character = cutscene::Character::from_kid(game_state.controlling);
     vec!(
         cutscene::CutsceneSegment::CharacterPosition(character, cutscene::Position::Left),
         cutscene::CutsceneSegment::SetTalking(character),
-        cutscene::CutsceneSegment::Textbox("Oh no, they'll catch me!".to_string()),
-        cutscene::CutsceneSegment::Textbox("I know, I'll try this...".to_string()),
+        cutscene::CutsceneSegment::Textbox("spotted.1".to_string()),
+        cutscene::CutsceneSegment::Textbox("death.try_this".to_string()),
         cutscene::CutsceneSegment::LevelReset,
     )
 }
@@ -137,16 +187,19 @@ pub fn fit_in_level(
     game_state: &ResMut<GameState>,
     current: Vec3,
     target: Vec3,
+    can_use_vents: bool,
 ) -> Vec3 {
                                                       // isStair
     let mut current_shapes: Vec::<(&RectangleCollision, bool)> = vec!();
     for (level, shape) in level_info.collision_info.shapes.iter() {
         if *level == game_state.current_level {
             match shape {
+                CollisionShape::Vent(r) if !can_use_vents => (),
                 CollisionShape::Rect((r, _)) 
               | CollisionShape::LevelSwitch((r, _)) 
               | CollisionShape::TicketCheck((r, _)) 
-              | CollisionShape::GetTicket((r, _)) => {
+              | CollisionShape::GetTicket((r, _))
+              | CollisionShape::Vent(r) => {
                     if target.x >= r.bottom_x 
                     && target.x <= r.top_x 
                     && target.z <= r.right_z
@@ -238,7 +291,9 @@ pub fn debug_draw_level_colliders(
                             | CollisionShape::Stair(r) 
                             | CollisionShape::LevelSwitch((r, _)) 
                             | CollisionShape::TicketCheck((r, _)) 
-                            | CollisionShape::GetTicket((r, _)) => {
+                            | CollisionShape::GetTicket((r, _))
+                            | CollisionShape::HidingSpot((r, _))
+                            | CollisionShape::Vent(r) => {
                                 let color = Color::hex("FF0000").unwrap(); 
                                 let color = Color::rgba(color.r(), color.g(), color.b(), 0.5);
 
@@ -4,9 +4,11 @@ use bevy::app::Events;
 //use bevy_prototype_debug_lines::*;
 use bevy::reflect::{TypeUuid};
 use bevy::window::WindowMode;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use bevy_kira_audio::{AudioChannel, Audio, AudioPlugin, AudioSource};
+use bevy_kira_audio::AudioPlugin;
+use rand::Rng;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
 mod camera;
@@ -24,15 +26,30 @@ pub mod game_settings;
 pub mod level_collision;
 pub mod lobby;
 pub mod follow_text;
+pub mod inventory;
+pub mod hiding;
+pub mod actions;
+pub mod co_op;
+pub mod orders;
+pub mod formation;
+pub mod pathing;
+pub mod party;
+pub mod customize;
+pub mod rng;
+pub mod replay;
+pub mod ghost;
+pub mod flags;
+pub mod music;
+pub mod backlog;
+pub mod actor;
+pub mod locale;
+mod controls;
 mod menu;
 mod theater_outside; 
 
 use camera::*;
 
 pub static COLOR_BLACK: &str = "000000";
-pub struct BlipEvent {
-    blip_count: usize
-}
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
@@ -51,39 +68,93 @@ pub enum AppState {
     ResetMovie,
     RestartLevel,
     Credits,
+    Controls,
+    Customize,
 }
 
 pub struct GamePlugin;
 
+pub static SKIN_COLORS: [&str; 5] = ["fefae0", "b08968", "e6ccb2", "7f5539", "c49e85"];
+pub static LEG_COLORS: [&str; 4] = ["788aa3", "b2c9ab", "8e9aaf", "ccd5ae"];
+pub static TORSO_COLORS: [&str; 7] = ["e8e8e4", "efe9ae", "a8dadc", "caffbf", "43aa8b", "006d77", "ef476f"];
+pub static HAIR_COLORS: [&str; 5] = ["ebcfb2", "424b54", "81583a", "bb4d73", "ff9100"];
+
 pub fn get_colors(
+    rng: &mut StdRng,
 ) -> Colors { 
-    let mut skin_colors = vec!("fefae0", "b08968", "e6ccb2", "7f5539", "c49e85");
-    let mut leg_colors = vec!("788aa3", "b2c9ab", "8e9aaf", "ccd5ae");
-    let mut torso_colors = vec!("e8e8e4", "efe9ae", "a8dadc", "caffbf", "43aa8b", "006d77", "ef476f");
-    let mut hair_colors = vec!("ebcfb2", "424b54", "81583a", "bb4d73", "ff9100");
-
-    let mut rng = rand::thread_rng();
-    skin_colors.shuffle(&mut rng);
-    leg_colors.shuffle(&mut rng);
-    torso_colors.shuffle(&mut rng);
-    hair_colors.shuffle(&mut rng);
-
-    let mut rng = rand::thread_rng();
-    let mut nums: Vec<i32> = (0..100).collect();
-    nums.shuffle(&mut rng);
-    let is_long_hair = *nums.last().unwrap() % 2 == 0;
-
     Colors {
-        legs: leg_colors.last().unwrap().to_string(),
-        torso: torso_colors.last().unwrap().to_string(),
-        skin: skin_colors.last().unwrap().to_string(),
-        hair: hair_colors.last().unwrap().to_string(),
-        is_long_hair
+        legs: LEG_COLORS.choose(rng).unwrap().to_string(),
+        torso: TORSO_COLORS.choose(rng).unwrap().to_string(),
+        skin: SKIN_COLORS.choose(rng).unwrap().to_string(),
+        hair: HAIR_COLORS.choose(rng).unwrap().to_string(),
+        is_long_hair: rng.gen::<bool>(),
+        accessory: None,
+    }
+}
+
+// "--flag value" or "--flag=value" from the command line
+pub fn arg_value(flag: &str) -> Option::<String> {
+    let args = std::env::args().collect::<Vec::<_>>();
+    let prefix = format!("{}=", flag);
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| {
+            if arg == flag {
+                args.get(i + 1).cloned()
+            } else {
+                arg.strip_prefix(&prefix).map(|s| s.to_string())
+            }
+        })
+}
+
+// the little settings files that sit next to the game. a missing one is normal on the first
+// run, a broken one gets mentioned and then treated the same so it can't stop the game
+pub fn load_ron_file<T: serde::de::DeserializeOwned>(file: &str) -> Option::<T> {
+    let contents = std::fs::read_to_string(file).ok()?;
+    match ron::de::from_str::<T>(&contents) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            println!("Couldn't read {}, ignoring it: {:?}", file, e);
+            None
+        }
+    }
+}
+
+pub fn save_ron_file<T: Serialize>(file: &str, value: &T) {
+    match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new()) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(file, contents) {
+                println!("Couldn't save {}: {:?}", file, e);
+            }
+        },
+        Err(e) => println!("Couldn't serialize {}: {:?}", file, e)
     }
 }
 
+// the original four, anyone else gets theirs when the roster loads
+pub fn default_kid_colors(
+    rng: &mut rng::GameRng,
+) -> HashMap<Kid, Colors> {
+    let rng = rng.kids();
+    [
+        (Kid::A, get_colors(rng)),
+        (Kid::B, get_colors(rng)),
+        (Kid::C, get_colors(rng)),
+        // D always had the long hair
+        (Kid::D, Colors { is_long_hair: true, ..get_colors(rng) }),
+    ].iter().cloned().collect()
+}
+
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
+        let mut replay = replay::Replay::from_args();
+        let mut rng = match replay.seed() {
+                          Some(seed) => rng::GameRng::new(seed),
+                          None => rng::GameRng::from_args()
+                      };
+        let mut kid_colors = default_kid_colors(&mut rng);
+        let mut saved_kids = customize::SavedKids::load();
+        replay.start(&rng, &mut kid_colors, &mut saved_kids);
 
         app.add_plugins(DefaultPlugins)
 //         .add_plugin(DebugLinesPlugin)
@@ -95,7 +166,6 @@ impl Plugin for GamePlugin {
            .add_event::<LevelResetEvent>()
            .add_event::<player::DistractEvent>()
            .add_event::<follow_text::FollowTextEvent>()
-           .add_event::<BlipEvent>()
 
            .init_resource::<player::PersonMeshes>()
            .add_event::<credits::CreditsEvent>()
@@ -118,7 +188,6 @@ impl Plugin for GamePlugin {
            .add_system_set(
                SystemSet::on_update(AppState::MainMenu)
                    .with_system(menu::menu.system())
-                   .with_system(game_controller::gamepad_connections.system())
            )
            .add_system_set(
                SystemSet::on_exit(AppState::MainMenu)
@@ -149,7 +218,6 @@ impl Plugin for GamePlugin {
            .add_system(handle_change_state_event.system())
            .add_system(debug_move_entity.system())
            .add_system(handle_level_reset_event.system())
-           .add_system(handle_blip_event.system())
            .add_system(follow_text::update_follow_text.system())
            .add_system(follow_text::handle_follow_text_event.system())
            .add_system(player::player_interact_check.system())
@@ -158,27 +226,51 @@ impl Plugin for GamePlugin {
            .add_plugin(movie::MoviePlugin)
            .add_plugin(camera::CameraPlugin)
            .add_plugin(game_settings::GameSettingsPlugin)
+           .add_plugin(inventory::InventoryPlugin)
+           .add_plugin(hiding::HidingPlugin)
+           .add_plugin(co_op::CoOpPlugin)
+           .add_plugin(orders::OrdersPlugin)
+           .add_plugin(formation::FormationPlugin)
+           .add_plugin(party::PartyPlugin)
+           .add_plugin(customize::CustomizePlugin)
+           .add_plugin(actions::ActionsPlugin)
+           .add_plugin(controls::ControlsPlugin)
+           .add_plugin(pause::PausePlugin)
+           .add_plugin(replay::ReplayPlugin)
+           .add_plugin(ghost::GhostPlugin)
+           .add_plugin(backlog::BacklogPlugin)
+           .add_plugin(actor::ActorPlugin)
+           .add_plugin(locale::LocalePlugin)
 
           //.add_startup_system(setup.system())
+           .add_startup_system(music::setup_audio.system())
           //.add_system(print_on_load.system())
 
            .init_resource::<asset_loader::AssetsLoading>()
+           .insert_resource(rng)
+           .insert_resource(replay)
            .insert_resource(GameState {
                current_level: cutscene::Level::Outside,
                mode: Mode::Follow,
                controlling: Kid::A,
+               co_op: vec!(),
+               party: vec!(),
+               roster: vec!(),
                last_positions: HashMap::new(),
-               kid_colors: [
-                   (Kid::A, get_colors()),
-                   (Kid::B, get_colors()),
-                   (Kid::C, get_colors()),
-                   (Kid::D, get_colors()),
+               kid_colors,
+               saved_kids,
+               admitted: vec!(),
+               tickets_sold: 0,
+               inventories: [
+                   (Kid::A, inventory::Inventory::default()),
+                   (Kid::B, inventory::Inventory::default()),
+                   (Kid::C, inventory::Inventory::default()),
+                   (Kid::D, inventory::Inventory::default()),
                ].iter().cloned().collect(),
-               has_ticket: vec!(),
-               has_seen_half_of_movie: false,
-               has_avoided_movie_guard: false,
-               game_is_done: false,
+               collected_pickups: vec!(),
+               flags: flags::StoryFlags::default(),
                currently_talking: None,
+               emotions: vec!(),
            })
            .add_asset::<asset_loader::LevelInfo>()
            .init_asset_loader::<asset_loader::LevelsAssetLoader>()
@@ -193,30 +285,185 @@ pub struct GameState {
     pub current_level: cutscene::Level,
     pub mode: Mode, 
     pub controlling: Kid,
+    pub co_op: Vec::<(Gamepad, Kid)>,
+    // everyone currently in the group, in the order they got there
+    pub party: Vec::<Kid>,
+    // every kid the level data knows about, recruited or not
+    pub roster: Vec::<party::KidInfo>,
     pub last_positions: HashMap<Kid, Option::<Vec3>>,
     pub kid_colors: HashMap<Kid, Colors>,
-    pub has_ticket: Vec::<Kid>,
-    pub has_seen_half_of_movie: bool,
-    pub has_avoided_movie_guard: bool,
-    pub game_is_done: bool,
+    // what was picked on the customize screen, read from the kids file at startup
+    pub saved_kids: customize::SavedKids,
+    pub admitted: Vec::<Kid>,
+    pub tickets_sold: usize,
+    pub inventories: HashMap<Kid, inventory::Inventory>,
+    // the level's pickups that have been taken, by where they are in the level file
+    pub collected_pickups: Vec::<usize>,
+    pub flags: flags::StoryFlags,
     pub currently_talking: Option::<cutscene::Character>,
+    // faces the cutscene portraits are making, anyone not in here is Neutral
+    pub emotions: Vec::<(cutscene::Character, cutscene::Emotion)>,
 }
 
-#[derive(Clone, PartialEq, Hash, Eq, Debug)]
+impl GameState {
+    // true for player one's kid and any kid a co-op pad has claimed
+    pub fn is_controlled(&self, kid: Kid) -> bool {
+        self.controlling == kid || self.co_op.iter().any(|(_, k)| *k == kid)
+    }
+
+    pub fn remaining_kids(&self) -> Vec::<Kid> {
+        self.party
+            .iter()
+            .filter(|kid| matches!(self.last_positions.get(kid), Some(Some(_))))
+            .cloned()
+            .collect()
+    }
+
+    // only happens once, after that the party is whatever the player has built up
+    pub fn load_roster(&mut self, level_info: &asset_loader::LevelInfo, rng: &mut rng::GameRng) {
+        if !self.roster.is_empty() {
+            return;
+        }
+
+        self.roster = if level_info.kids.is_empty() {
+                          party::default_roster()
+                      } else {
+                          level_info.kids.clone()
+                      };
+        self.party = self.roster.iter().filter(|k| k.in_party).map(|k| k.kid).collect();
+
+        for info in self.roster.iter() {
+            self.kid_colors.entry(info.kid).or_insert_with(|| get_colors(rng.kids()));
+            self.inventories.entry(info.kid).or_insert_with(inventory::Inventory::default);
+        }
+
+        // whatever they picked on the customize screen last time
+        let saved_kids = self.saved_kids.clone();
+        saved_kids.apply(self);
+    }
+
+    // a new seed means new kids, anything saved from the customize screen still wins when the roster reloads
+    pub fn reroll_kids(&mut self, rng: &mut rng::GameRng) {
+        self.kid_colors = default_kid_colors(rng);
+        self.roster.clear();
+        self.party.clear();
+    }
+
+    pub fn kid_name(&self, kid: Kid) -> String {
+        self.roster
+            .iter()
+            .find(|k| k.kid == kid)
+            .map(|k| k.name.clone())
+            .unwrap_or_else(|| format!("{}", kid.0))
+    }
+
+    pub fn emotion(&self, character: cutscene::Character) -> cutscene::Emotion {
+        self.emotions
+            .iter()
+            .find(|(c, _)| *c == character)
+            .map(|(_, emotion)| *emotion)
+            .unwrap_or(cutscene::Emotion::Neutral)
+    }
+
+    pub fn set_emotion(&mut self, character: cutscene::Character, emotion: cutscene::Emotion) {
+        self.emotions.retain(|(c, _)| *c != character);
+        if emotion != cutscene::Emotion::Neutral {
+            self.emotions.push((character, emotion));
+        }
+    }
+
+    pub fn kid_trait(&self, kid: Kid) -> Option::<party::KidTrait> {
+        self.roster
+            .iter()
+            .find(|k| k.kid == kid)
+            .and_then(|k| k.ability)
+    }
+
+    pub fn has_trait(&self, kid: Kid, ability: party::KidTrait) -> bool {
+        self.kid_trait(kid) == Some(ability)
+    }
+
+    // a kid that never got colors rolled for them still shows up, just in the first of everything
+    pub fn colors(&self, kid: Kid) -> Colors {
+        match self.kid_colors.get(&kid) {
+            Some(colors) => colors.clone(),
+            None => {
+                println!("No colors for {:?}", kid);
+                Colors {
+                    legs: LEG_COLORS[0].to_string(),
+                    torso: TORSO_COLORS[0].to_string(),
+                    skin: SKIN_COLORS[0].to_string(),
+                    hair: HAIR_COLORS[0].to_string(),
+                    is_long_hair: false,
+                    accessory: None,
+                }
+            }
+        }
+    }
+
+    // lines the party up around a spot, first kid in the middle
+    pub fn place_party(&mut self, origin: Vec3) {
+        self.last_positions.clear();
+        for (i, kid) in self.party.iter().enumerate() {
+            let offset = match i {
+                             0 => 0.0,
+                             1 => -1.0,
+                             2 => 0.5,
+                             3 => -0.5,
+                             _ => if i % 2 == 0 { -(i as f32 / 2.0) } else { i as f32 / 2.0 }
+                         };
+            self.last_positions.insert(*kid, Some(origin + Vec3::new(0.0, 0.0, offset)));
+        }
+    }
+
+    // the next kid nobody is playing as, or the same kid if everyone's taken
+    pub fn next_free_kid(&self, from: Kid) -> Kid {
+        let remaining_kids = self.remaining_kids();
+        let current = remaining_kids.iter().position(|k| *k == from).unwrap_or(0);
+
+        for i in 1..=remaining_kids.len() {
+            let kid = remaining_kids[(current + i) % remaining_kids.len()];
+            if kid == from || !self.is_controlled(kid) {
+                return kid;
+            }
+        }
+
+        from
+    }
+
+    // moves whoever is playing as this kid over to the next free one
+    pub fn switch_kid(&mut self, from: Kid) -> Kid {
+        let next_kid = self.next_free_kid(from);
+        if self.controlling == from {
+            self.controlling = next_kid;
+        } else if let Some((_, kid)) = self.co_op.iter_mut().find(|(_, kid)| *kid == from) {
+            *kid = next_kid;
+        }
+
+        next_kid
+    }
+}
+
+#[derive(Clone, PartialEq, Hash, Eq, Debug, Serialize, Deserialize)]
 pub struct Colors {
     pub legs: String,
     pub torso: String,
     pub skin: String,
     pub hair: String,
-    pub is_long_hair: bool
+    pub is_long_hair: bool,
+    #[serde(default)]
+    pub accessory: Option::<customize::Accessory>,
 }
 
-#[derive(Copy, Clone, PartialEq, Hash, Eq, Debug)]
-pub enum Kid {
-    A,
-    B,
-    C,
-    D,
+#[derive(Copy, Clone, PartialEq, Hash, Eq, Debug, Serialize, Deserialize, PartialOrd, Ord)]
+pub struct Kid(pub usize);
+
+// the four the story starts with, anyone else comes from the level data
+impl Kid {
+    pub const A: Kid = Kid(0);
+    pub const B: Kid = Kid(1);
+    pub const C: Kid = Kid(2);
+    pub const D: Kid = Kid(3);
 }
 
 pub enum Mode {
@@ -224,8 +471,10 @@ pub enum Mode {
     Switch
 }
 
-fn exit(keys: Res<Input<KeyCode>>, mut exit: ResMut<Events<AppExit>>) {
-    if keys.just_pressed(KeyCode::Q) {
+fn exit(keys: Res<Input<KeyCode>>, mut exit: ResMut<Events<AppExit>>, state: Res<State<AppState>>) {
+    // these menus take typed names, seeds and rebinds so Q is just a letter there, the main menu has quit anyway
+    let typing = matches!(state.current(), AppState::MainMenu | AppState::Customize | AppState::Controls);
+    if keys.just_pressed(KeyCode::Q) && !typing {
         exit.send(AppExit);
     }
 }
@@ -300,7 +549,7 @@ pub fn handle_change_state_event(
     mut state: ResMut<State<AppState>>,
     mut change_state_event_reader: EventReader<ChangeStateEvent>,
     mut queued_state_change: Local<Option::<AppState>>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     mut delay: Local<f32>,
     player: Query<Entity, With<player::Player>>,
 ) {
@@ -331,45 +580,12 @@ pub fn handle_change_state_event(
     }
 }
 
-// this function really is setup just to quickly play
-// the one sound added to this game. This isn't a scalable pattern
-pub fn handle_blip_event(
-    mut current_blip_count: Local<usize>,
-    mut blip_event_reader: EventReader<BlipEvent>,
-    asset_server: Res<AssetServer>, 
-    mut blip_handle: Local<Option::<Handle<AudioSource>>>,
-    audio: Res<Audio>,
-    time: Res<Time>,
-    mut blip_buffer: Local<f32>,
-) {
-    for event in blip_event_reader.iter() {
-        *current_blip_count = event.blip_count;
-    }
-
-    *blip_buffer -= time.delta_seconds();
-
-    if *blip_buffer < 0.0 {
-        *blip_buffer = 0.10;
-
-        if *current_blip_count > 0 {
-            *current_blip_count -= 1;
-            if blip_handle.is_none() {
-                *blip_handle = Some(asset_server.load("sounds/blip.wav"));
-            }
-
-            //audio.play(blip_handle.as_ref().unwrap().clone());
-        } else {
-            *current_blip_count = 0;
-        }
-    }
-}
-
 pub fn handle_level_reset_event(
     mut level_reset_event_reader: EventReader<LevelResetEvent>,
     mut players: Query<(&mut player::Player, &mut Transform), Without<enemy::Enemy>>,
     mut enemies: Query<(&mut enemy::Enemy, &mut Transform), Without<player::Player>>,
     mut follow_text: ResMut<follow_text::FollowText>,
-    mut game_state: ResMut<GameState>,
+    game_state: Res<GameState>,
 ) {
     for _ in level_reset_event_reader.iter() {
         for (mut player, mut transform) in players.iter_mut() {
@@ -378,12 +594,18 @@ pub fn handle_level_reset_event(
             player.velocity = Vec3::default();
             player.movement = None;
             player.is_distracting = None;
+            player.is_disguised = false;
+            player.is_hidden = false;
+            player.seen_hiding = false;
+            player.movement_mode = player::MovementMode::Walk;
+            player.stamina = player::MAX_STAMINA;
 
-            game_state.has_ticket = vec!();
             follow_text.lock = 0.0;
         }
         for (mut enemy, mut transform) in enemies.iter_mut() {
             enemy.is_distracted = false;
+            enemy.distracted_time = 0.0;
+            enemy.investigating = None;
             if game_state.current_level == cutscene::Level::Movie {
                 transform.translation.x = 0.0;
                 transform.translation.z = -9.0;
@@ -396,7 +618,7 @@ pub fn handle_level_reset_event(
 pub fn debug_move_entity(
     keyboard_input: Res<Input<KeyCode>>,
     mut entities: Query<&mut Transform, With<cutscene::DebugCharacterMarker>>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     return;
     for mut transform in entities.iter_mut() {
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use crate::{enemy, cutscene, player, theater_outside, asset_loader, camera, level_collision, GameState, AppState, Mode, Kid, };
+use crate::{enemy, cutscene, player, theater_outside, asset_loader, camera, level_collision, GameState, AppState, Mode, inventory, replay};
 use bevy_kira_audio::{AudioChannel, Audio, AudioPlugin};
 
 pub struct LobbyPlugin;
@@ -12,6 +12,7 @@ impl Plugin for LobbyPlugin {
                     .with_system(crate::camera::create_camera.system().after("loading_level"))
                     .with_system(set_clear_color.system().after("loading_level"))
                     .with_system(enemy::spawn_enemies.system().after("loading_level"))
+                    .with_system(inventory::spawn_pickups.system().after("loading_level"))
                     .with_system(play_music.system().after("loading_level"))
             )
             .add_system_set(
@@ -31,6 +32,7 @@ impl Plugin for LobbyPlugin {
                     .with_system(level_collision::ticket_checker.system())
                     .with_system(check_for_level_exit.system())
                     .with_system(player::player_movement_update.system())
+                    .with_system(player::update_kid_poses.system())
                     .with_system(listen_for_level_reset.system())
             );
     }
@@ -65,7 +67,7 @@ fn listen_for_level_reset(
 fn reset_level( 
     mut state: ResMut<State<crate::AppState>>,
     mut timer: Local<f32>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     *timer += time.delta_seconds();
 
@@ -87,6 +89,7 @@ fn load_level(
     mut level_info_state: ResMut<asset_loader::LevelInfoState>, 
     level_info_assets: ResMut<Assets<asset_loader::LevelInfo>>,
     mut game_state: ResMut<GameState>,
+    mut rng: ResMut<crate::rng::GameRng>,
 
     mut state: ResMut<State<crate::AppState>>,
 
@@ -95,6 +98,7 @@ fn load_level(
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
         println!("Level loaded");
+        game_state.load_roster(level_asset, &mut rng);
     } else {
         // try again later?
         println!("failed to load lobby");
@@ -165,12 +169,7 @@ fn load_level(
                 });
             }).id();
 
-    game_state.last_positions = [
-                (Kid::A, Some(Vec3::new(0.0, 0.0, 0.0))), 
-                (Kid::B, Some(Vec3::new(0.0, 0.0, -1.0))),
-                (Kid::C, Some(Vec3::new(0.0, 0.0, 0.5))), 
-                (Kid::D, Some(Vec3::new(0.0, 0.0, -0.5))),
-    ].iter().cloned().collect();
+    game_state.place_party(Vec3::ZERO);
     player::spawn_player(&mut commands, &mut materials, &mut meshes, 
                          &person_meshes, &theater_meshes, &game_state);
 }
@@ -188,6 +187,7 @@ fn cleanup_environment(
     enemy: Query<Entity, With<enemy::Enemy>>,
     camera: Query<Entity, With<camera::MainCamera>>,
     collision_meshes: Query<Entity, With<level_collision::DebugLevelCollisionMesh>>, 
+    items: Query<Entity, With<inventory::ItemMarker>>,
 ) {
     for entity in level_mesh.iter() {
         commands.entity(entity).despawn_recursive();
@@ -208,6 +208,10 @@ fn cleanup_environment(
     for entity in collision_meshes.iter() {
         commands.entity(entity).despawn_recursive();
     }
+
+    for entity in items.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
 pub fn check_for_level_exit(
@@ -245,13 +249,18 @@ pub fn check_for_level_exit(
                                 } else {
                                     commands.entity(entity).despawn_recursive();
                                     game_state.last_positions.insert(player.kid, None);
-                                    game_state.controlling = game_state.last_positions
-                                                                       .iter()
-                                                                       .filter(|(_key, value)| !value.is_none())
-                                                                       .map(|(key, _)| key)
-                                                                       .last()
-                                                                       .unwrap()
-                                                                       .clone();
+                                    game_state.co_op.retain(|(_, kid)| *kid != player.kid);
+                                    if game_state.controlling == player.kid {
+                                        // if the co-op kids are all that's left, player one takes one of them over
+                                        let next_kid = game_state.next_free_kid(player.kid);
+                                        game_state.controlling = if next_kid != player.kid { 
+                                                                     next_kid 
+                                                                 } else { 
+                                                                     game_state.remaining_kids().first().cloned().unwrap_or(next_kid)
+                                                                 };
+                                        let controlling = game_state.controlling;
+                                        game_state.co_op.retain(|(_, kid)| *kid != controlling);
+                                    }
                                 }
 
                             }
@@ -0,0 +1,279 @@
+use bevy::prelude::*;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::reflect::{TypeUuid};
+use bevy::utils::{BoxedFuture};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::{asset_loader, AppState};
+
+static LANGUAGE_FILE: &str = "language.ron";
+// every string has an english version, anything another language is missing falls back to it
+static DEFAULT_LANGUAGE: &str = "en";
+// not a real language, english with accents and padding so text that's too long for its box stands out
+static PSEUDO_LANGUAGE: &str = "pseudo";
+// roughly how much longer german or finnish tends to come out than english
+static PSEUDO_PADDING: f32 = 0.4;
+
+// code and what the selector shows, the strings are in assets/lang/<code>.ron
+pub static LANGUAGES: [(&str, &str); 3] = [
+    ("en", "English"),
+    ("es", "Español"),
+    ("pseudo", "Pseudo"),
+];
+
+// one language's worth of strings, id -> text
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "7d1e3a52-ab9c-4543-8640-c6f2b74b5052"]
+pub struct StringTable {
+    pub strings: HashMap<String, String>,
+}
+
+// so the string tables hot reload like the level does
+#[derive(Default)]
+pub struct StringsAssetLoader;
+impl AssetLoader for StringsAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let strings = match ron::de::from_bytes::<HashMap<String, String>>(bytes) {
+                              Ok(strings) => strings,
+                              Err(e) => {
+                                  println!("Couldn't read the strings in {:?}: {}", load_context.path(), e);
+                                  return Err(e.into());
+                              }
+                          };
+            load_context.set_default_asset(LoadedAsset::new(StringTable { strings }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron", ]
+    }
+}
+
+// language code and its table, kept around so the tables don't get dropped
+#[derive(Default)]
+pub struct StringTableHandles {
+    pub handles: Vec::<(String, Handle<StringTable>)>,
+}
+
+// this is what gets saved to the language file
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SavedLanguage {
+    pub language: String,
+}
+
+impl SavedLanguage {
+    pub fn load() -> Option::<Self> {
+        crate::load_ron_file(LANGUAGE_FILE)
+    }
+
+    pub fn save(&self) {
+        crate::save_ron_file(LANGUAGE_FILE, self);
+    }
+}
+
+// player facing text is looked up by id, "menu.start" or "intro.3" or whatever
+pub struct Strings {
+    pub language: String,
+    // filled in by language code as the tables finish loading
+    tables: HashMap<String, HashMap<String, String>>,
+}
+
+// "Start" -> "[Štàrt··]", the brackets show if the end got cut off
+fn pseudo_localize(text: &str) -> String {
+    let mut pseudo = String::new();
+    let mut in_placeholder = false;
+    for c in text.chars() {
+        // {} gets filled in after, it has to stay as is
+        in_placeholder = match c {
+                             '{' => true,
+                             '}' => false,
+                             _ => in_placeholder
+                         };
+        if in_placeholder {
+            pseudo.push(c);
+            continue;
+        }
+
+        pseudo.push(match c {
+                        'a' => 'à', 'e' => 'é', 'i' => 'î', 'o' => 'ö', 'u' => 'ü', 'c' => 'ç', 'n' => 'ñ', 's' => 'š', 'y' => 'ý',
+                        'A' => 'Å', 'E' => 'É', 'I' => 'Î', 'O' => 'Ö', 'U' => 'Ü', 'C' => 'Ç', 'N' => 'Ñ', 'S' => 'Š', 'Y' => 'Ý',
+                        _ => c
+                    });
+    }
+
+    let padding = (text.chars().count() as f32 * PSEUDO_PADDING).ceil() as usize;
+    format!("[{}{}]", pseudo, "·".repeat(padding))
+}
+
+impl Strings {
+    pub fn new(language: &str) -> Self {
+        let language = if LANGUAGES.iter().any(|(code, _)| *code == language) {
+                           language.to_string()
+                       } else {
+                           println!("Don't know the language {}, using {}", language, DEFAULT_LANGUAGE);
+                           DEFAULT_LANGUAGE.to_string()
+                       };
+
+        Strings {
+            tables: HashMap::new(),
+            language,
+        }
+    }
+
+    // --lang es or --lang=es on the command line, otherwise whatever was picked last time
+    pub fn from_args() -> Self {
+        let language = crate::arg_value("--lang")
+                           .or_else(|| SavedLanguage::load().map(|saved| saved.language))
+                           .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+
+        Strings::new(&language)
+    }
+
+    // for the selector, steps through LANGUAGES and wraps around
+    pub fn step_language(&mut self, step: i32) {
+        let len = LANGUAGES.len() as i32;
+        let current = LANGUAGES.iter().position(|(code, _)| *code == self.language).unwrap_or(0) as i32;
+        let (code, _) = LANGUAGES[(((current + step) % len + len) % len) as usize];
+
+        self.language = code.to_string();
+        SavedLanguage { language: self.language.clone() }.save();
+    }
+
+    pub fn language_name(&self) -> &'static str {
+        LANGUAGES.iter()
+                 .find(|(code, _)| *code == self.language)
+                 .map(|(_, name)| *name)
+                 .unwrap_or("")
+    }
+
+    // anything that isn't in a table comes back as is, so levels can still have plain text in them
+    pub fn get(&self, id: &str) -> String {
+        let text = self.tables.get(&self.language).and_then(|table| table.get(id))
+                       .or_else(|| self.tables.get(DEFAULT_LANGUAGE).and_then(|table| table.get(id)));
+        match text {
+            Some(text) if self.language == PSEUDO_LANGUAGE => pseudo_localize(text),
+            Some(text) => text.clone(),
+            None => id.to_string()
+        }
+    }
+
+    // fills in each {} in order, "Seed: {}" and so on
+    pub fn get_with(&self, id: &str, args: &[&str]) -> String {
+        let mut text = self.get(id);
+        for arg in args.iter() {
+            text = text.replacen("{}", arg, 1);
+        }
+
+        text
+    }
+}
+
+// text that comes from the string table, it gets redone when the language changes
+pub struct Localized(pub String);
+
+pub struct LocalePlugin;
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Strings::from_args())
+           .init_resource::<StringTableHandles>()
+           .add_asset::<StringTable>()
+           .init_asset_loader::<StringsAssetLoader>()
+           .add_system_set(
+               SystemSet::on_enter(AppState::Loading)
+                         .with_system(load_string_tables.system())
+           )
+           .add_system(update_string_tables.system())
+           .add_system(localize_texts.system());
+    }
+}
+
+pub fn load_string_tables(
+    asset_server: Res<AssetServer>,
+    mut handles: ResMut<StringTableHandles>,
+    mut loading: ResMut<asset_loader::AssetsLoading>,
+) {
+    println!("Adding string tables");
+    for (code, _) in LANGUAGES.iter() {
+        // pseudo is made up from english, there's no file for it
+        if *code == PSEUDO_LANGUAGE { continue; }
+
+        let handle: Handle<StringTable> = asset_server.load(format!("lang/{}.ron", code).as_str());
+        loading.asset_handles.push(handle.clone_untyped());
+        handles.handles.push((code.to_string(), handle));
+    }
+}
+
+// copies tables into Strings whenever one loads or gets edited
+pub fn update_string_tables(
+    mut strings: ResMut<Strings>,
+    mut events: EventReader<AssetEvent<StringTable>>,
+    tables: Res<Assets<StringTable>>,
+    handles: Res<StringTableHandles>,
+) {
+    for event in events.iter() {
+        let changed = match event {
+                          AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+                          _ => continue
+                      };
+
+        for (code, handle) in handles.handles.iter() {
+            if handle == changed {
+                if let Some(table) = tables.get(handle) {
+                    strings.tables.insert(code.clone(), table.strings.clone());
+                }
+            }
+        }
+    }
+}
+
+pub fn localize_texts(
+    strings: Res<Strings>,
+    mut texts: Query<(&Localized, &mut Text)>,
+) {
+    for (localized, mut text) in texts.iter_mut() {
+        let value = strings.get(&localized.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_accents_and_pads() {
+        assert_eq!(pseudo_localize("Start"), "[Štàrt··]");
+        assert_eq!(pseudo_localize(""), "[]");
+    }
+
+    #[test]
+    fn pseudo_leaves_placeholders_alone() {
+        let pseudo = pseudo_localize("Seed: {} and {}");
+        assert!(pseudo.starts_with("[Šééd: {} àñd {}"));
+        assert_eq!(pseudo.matches("{}").count(), 2);
+
+        let mut strings = Strings::new(PSEUDO_LANGUAGE);
+        strings.tables.insert(DEFAULT_LANGUAGE.to_string(),
+                              [("menu.seed".to_string(), "Seed: {}".to_string())].iter().cloned().collect());
+        assert!(strings.get_with("menu.seed", &["1234"]).contains("1234"));
+    }
+
+    #[test]
+    fn falls_back_to_english_then_the_id() {
+        let mut strings = Strings::new("es");
+        strings.tables.insert(DEFAULT_LANGUAGE.to_string(),
+                              [("menu.start".to_string(), "Start".to_string())].iter().cloned().collect());
+        strings.tables.insert("es".to_string(), HashMap::new());
+
+        assert_eq!(strings.get("menu.start"), "Start");
+        assert_eq!(strings.get("a plain line"), "a plain line");
+    }
+}
@@ -2,7 +2,9 @@ use bevy::prelude::*;
 use bevy::app::Events;
 use bevy::app::AppExit;
 use std::collections::HashMap;
-use crate::{game_controller, };
+use crate::{actions, locale, rng, GameState, replay};
+
+static MAX_SEED_DIGITS: usize = 12;
 
 pub struct BylineText;
 pub struct MenuButton;
@@ -10,6 +12,7 @@ pub fn setup_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     button_materials: Res<ButtonMaterials>,
+    strings: Res<locale::Strings>,
 ) {
     // ui camera
     commands.spawn_bundle(UiCameraBundle::default());
@@ -59,7 +62,147 @@ pub fn setup_menu(
                 align_items: AlignItems::Center,
                 position_type: PositionType::Absolute,
                 position: Rect {
-                    bottom: Val::Percent(10.0),
+                    bottom: Val::Percent(27.5),
+                    left: Val::Percent(45.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    strings.get("menu.start"),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            }).insert(MenuButton).insert(locale::Localized("menu.start".to_string()));
+        })
+        .insert(MenuButton)
+        .id();
+
+    let mut seed_text_entity = None;
+    let seed_button_entity = commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                // center button
+                margin: Rect::all(Val::Auto),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(35.0),
+                    left: Val::Percent(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            seed_text_entity = Some(parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            }).insert(MenuButton).id());
+        })
+        .insert(MenuButton)
+        .id();
+
+    let mut language_text_entity = None;
+    let language_button_entity = commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                // center button
+                margin: Rect::all(Val::Auto),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(42.5),
+                    left: Val::Percent(40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            language_text_entity = Some(parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            }).insert(MenuButton).id());
+        })
+        .insert(MenuButton)
+        .id();
+
+    let customize_button_entity = commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(65.0)),
+                // center button
+                margin: Rect::all(Val::Auto),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(20.0),
                     left: Val::Percent(45.0),
                     ..Default::default()
                 },
@@ -75,7 +218,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Start",
+                    strings.get("menu.customize"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -88,7 +231,53 @@ pub fn setup_menu(
                     is_transparent: false,
                 },
                 ..Default::default()
-            }).insert(MenuButton);
+            }).insert(MenuButton).insert(locale::Localized("menu.customize".to_string()));
+        })
+        .insert(MenuButton)
+        .id();
+
+    let controls_button_entity = commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(65.0)),
+                // center button
+                margin: Rect::all(Val::Auto),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(12.5),
+                    left: Val::Percent(45.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    strings.get("menu.controls"),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                    Default::default(),
+                ),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            }).insert(MenuButton).insert(locale::Localized("menu.controls".to_string()));
         })
         .insert(MenuButton)
         .id();
@@ -121,7 +310,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Quit",
+                    strings.get("menu.quit"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -134,7 +323,7 @@ pub fn setup_menu(
                     is_transparent: false,
                 },
                 ..Default::default()
-            }).insert(MenuButton);
+            }).insert(MenuButton).insert(locale::Localized("menu.quit".to_string()));
         })
         .insert(MenuButton)
         .id();
@@ -142,6 +331,13 @@ pub fn setup_menu(
     commands.insert_resource(
         MenuData { 
             start_button_entity, 
+            seed_button_entity,
+            seed_text_entity: seed_text_entity.unwrap(),
+            editing_seed: None,
+            language_button_entity,
+            language_text_entity: language_text_entity.unwrap(),
+            customize_button_entity,
+            controls_button_entity,
             quit_button_entity, 
             byline_text_entity,
             selected: start_button_entity
@@ -150,6 +346,14 @@ pub fn setup_menu(
 
 pub struct MenuData {
     start_button_entity: Entity,
+    seed_button_entity: Entity,
+    seed_text_entity: Entity,
+    // what's been typed so far while they're changing the seed
+    editing_seed: Option::<String>,
+    language_button_entity: Entity,
+    language_text_entity: Entity,
+    customize_button_entity: Entity,
+    controls_button_entity: Entity,
     quit_button_entity: Entity,
     byline_text_entity: Entity, 
     selected: Entity,
@@ -157,6 +361,10 @@ pub struct MenuData {
 
 pub fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>) {
     commands.entity(menu_data.start_button_entity).despawn_recursive();
+    commands.entity(menu_data.seed_button_entity).despawn_recursive();
+    commands.entity(menu_data.language_button_entity).despawn_recursive();
+    commands.entity(menu_data.customize_button_entity).despawn_recursive();
+    commands.entity(menu_data.controls_button_entity).despawn_recursive();
     commands.entity(menu_data.quit_button_entity).despawn_recursive();
     commands.entity(menu_data.byline_text_entity).despawn_recursive();
 }
@@ -165,19 +373,20 @@ pub fn menu(
     mut state: ResMut<State<crate::AppState>>,
     mut exit: ResMut<Events<AppExit>>,
     mut menu_data: ResMut<MenuData>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<actions::ActionState>,
     button_materials: Res<ButtonMaterials>,
     mut button_colors: Query<(Entity, &mut Handle<ColorMaterial>), With<Button>>,
     interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<Input<GamepadButton>>,
-    gamepad: Option<Res<game_controller::GameController>>,
-    mut gamepad_buffer: Local<f32>,
     mut bylines: Query<&mut Text, With<BylineText>>,
     mut menu_buttons: Query<&mut Visible, With<MenuButton>>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
+    mut rng: ResMut<rng::GameRng>,
+    mut game_state: ResMut<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut button_texts: Query<&mut Text, Without<BylineText>>,
+    mut strings: ResMut<locale::Strings>,
 ) {
-    *gamepad_buffer += time.delta_seconds();
     let mut selected_button = None;
 
     for mut byline in bylines.iter_mut() {
@@ -194,36 +403,96 @@ pub fn menu(
 //        }
     }
 
-    let mut next_button = HashMap::new();
-    next_button.insert(menu_data.start_button_entity, menu_data.quit_button_entity);
-    next_button.insert(menu_data.quit_button_entity, menu_data.start_button_entity);
+    // typing in a new seed, nothing else works until they're done
+    let was_editing_seed = menu_data.editing_seed.is_some();
+    if let Some(mut typed) = menu_data.editing_seed.take() {
+        for event in received_characters.iter() {
+            if event.char.is_ascii_digit() && typed.len() < MAX_SEED_DIGITS {
+                typed.push(event.char);
+            }
+        }
 
-    let mut prev_button = HashMap::new();
-    prev_button.insert(menu_data.start_button_entity, menu_data.quit_button_entity);
-    prev_button.insert(menu_data.quit_button_entity, menu_data.start_button_entity);
+        if keyboard_input.just_pressed(KeyCode::Back) {
+            typed.pop();
+        }
+
+        if keyboard_input.just_pressed(KeyCode::Return) {
+            if let Ok(seed) = typed.parse::<u64>() {
+                rng.set_seed(seed);
+                game_state.reroll_kids(&mut rng);
+            }
+        } else if !keyboard_input.just_pressed(KeyCode::Escape) {
+            menu_data.editing_seed = Some(typed);
+        }
+    } else {
+        for _ in received_characters.iter() {}
 
-    let mut pressed_buttons = game_controller::get_pressed_buttons(&axes, &buttons, gamepad);
-    if *gamepad_buffer < 0.25 {
-        pressed_buttons = vec!(); 
+        // the pad can't type so it nudges the seed instead
+        if menu_data.selected == menu_data.seed_button_entity {
+            let seed = if actions.just_pressed(actions::GameAction::MoveLeft) {
+                           Some(rng.seed.saturating_sub(1))
+                       } else if actions.just_pressed(actions::GameAction::MoveRight) {
+                           Some(rng.seed.saturating_add(1))
+                       } else {
+                           None
+                       };
+            if let Some(seed) = seed {
+                rng.set_seed(seed);
+                game_state.reroll_kids(&mut rng);
+            }
+        }
+
+        if menu_data.selected == menu_data.language_button_entity {
+            if actions.just_pressed(actions::GameAction::MoveLeft) {
+                strings.step_language(-1);
+            } else if actions.just_pressed(actions::GameAction::MoveRight) {
+                strings.step_language(1);
+            }
+        }
     }
 
-    if !pressed_buttons.is_empty() {
-        *gamepad_buffer = 0.0;
+    if let Ok(mut text) = button_texts.get_mut(menu_data.seed_text_entity) {
+        text.sections[0].value = match &menu_data.editing_seed {
+                                     Some(typed) => strings.get_with("menu.seed_typing", &[typed]),
+                                     None => strings.get_with("menu.seed", &[&rng.seed.to_string()]),
+                                 };
     }
 
+    if let Ok(mut text) = button_texts.get_mut(menu_data.language_text_entity) {
+        text.sections[0].value = strings.get_with("menu.language", &[strings.language_name()]);
+    }
+
+    // includes the frame they hit return or escape, otherwise that same press picks the seed button again
+    if was_editing_seed {
+        return;
+    }
+
+    let mut next_button = HashMap::new();
+    next_button.insert(menu_data.language_button_entity, menu_data.seed_button_entity);
+    next_button.insert(menu_data.seed_button_entity, menu_data.start_button_entity);
+    next_button.insert(menu_data.start_button_entity, menu_data.customize_button_entity);
+    next_button.insert(menu_data.customize_button_entity, menu_data.controls_button_entity);
+    next_button.insert(menu_data.controls_button_entity, menu_data.quit_button_entity);
+    next_button.insert(menu_data.quit_button_entity, menu_data.language_button_entity);
+
+    let mut prev_button = HashMap::new();
+    prev_button.insert(menu_data.language_button_entity, menu_data.quit_button_entity);
+    prev_button.insert(menu_data.seed_button_entity, menu_data.language_button_entity);
+    prev_button.insert(menu_data.start_button_entity, menu_data.seed_button_entity);
+    prev_button.insert(menu_data.customize_button_entity, menu_data.start_button_entity);
+    prev_button.insert(menu_data.controls_button_entity, menu_data.customize_button_entity);
+    prev_button.insert(menu_data.quit_button_entity, menu_data.controls_button_entity);
+
     // keyboard and gamepad
-    if keyboard_input.just_pressed(KeyCode::Return) || keyboard_input.just_pressed(KeyCode::Space)
-    || pressed_buttons.contains(&game_controller::GameButton::Action){
+    if actions.just_pressed(actions::GameAction::Confirm) {
         selected_button = Some(menu_data.selected); 
     }
 
-    if keyboard_input.just_pressed(KeyCode::W) || keyboard_input.just_pressed(KeyCode::Up) 
-    || pressed_buttons.contains(&game_controller::GameButton::Up) {
+    if actions.just_pressed(actions::GameAction::MoveUp) {
         menu_data.selected = *prev_button.get(&menu_data.selected).unwrap();
     }
 
-    if keyboard_input.just_pressed(KeyCode::S) || keyboard_input.just_pressed(KeyCode::Down) 
-    || pressed_buttons.contains(&game_controller::GameButton::Down) {
+    if actions.just_pressed(actions::GameAction::MoveDown) {
         menu_data.selected = *next_button.get(&menu_data.selected).unwrap();
     }
 
@@ -238,8 +507,22 @@ pub fn menu(
 
     if let Some(selected_button) = selected_button {
         if selected_button == menu_data.start_button_entity {
+            // every run on the same seed starts from the same place
+            rng.restart();
             state.set(crate::AppState::InGame).unwrap();
         }
+        if selected_button == menu_data.seed_button_entity {
+            menu_data.editing_seed = Some(String::new());
+        }
+        if selected_button == menu_data.language_button_entity {
+            strings.step_language(1);
+        }
+        if selected_button == menu_data.customize_button_entity {
+            state.set(crate::AppState::Customize).unwrap();
+        }
+        if selected_button == menu_data.controls_button_entity {
+            state.set(crate::AppState::Controls).unwrap();
+        }
         if selected_button == menu_data.quit_button_entity {
             exit.send(AppExit);
         }
@@ -255,9 +538,9 @@ pub fn menu(
 }
 
 pub struct ButtonMaterials {
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
-    pressed: Handle<ColorMaterial>,
+    pub normal: Handle<ColorMaterial>,
+    pub hovered: Handle<ColorMaterial>,
+    pub pressed: Handle<ColorMaterial>,
 }
 
 impl FromWorld for ButtonMaterials {
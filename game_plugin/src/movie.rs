@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use crate::{enemy, cutscene, player, theater_outside, asset_loader, camera, level_collision, GameState, Mode, Kid, AppState,};
+use crate::{enemy, cutscene, player, theater_outside, asset_loader, camera, level_collision, GameState, Mode, AppState, inventory, flags, replay};
 
 /*
 camera_x: -11.483573,
@@ -21,6 +21,7 @@ impl Plugin for MoviePlugin {
                     .with_system(crate::camera::create_camera.system().after("loading_level"))
                     .with_system(set_clear_color.system().after("loading_level"))
                     .with_system(enemy::spawn_enemies.system().after("loading_level"))
+                    .with_system(inventory::spawn_pickups.system().after("loading_level"))
             )
             .add_system_set(
                 SystemSet::on_exit(crate::AppState::Movie)
@@ -36,6 +37,7 @@ impl Plugin for MoviePlugin {
                     .with_system(player::player_input.system())
                    // .with_system(check_for_level_exit.system())
                     .with_system(player::player_movement_update.system())
+                    .with_system(player::update_kid_poses.system())
                     .with_system(listen_for_level_reset.system())
                     .with_system(move_enemy_into_movie.system())
             );
@@ -53,7 +55,7 @@ fn move_enemy_into_movie(
     camera: Query<Entity, With<camera::MainCamera>>,
     collision_meshes: Query<Entity, With<level_collision::DebugLevelCollisionMesh>>, 
 ) {
-    if !game_state.has_seen_half_of_movie { return; }
+    if !game_state.flags.is_set(flags::HAS_SEEN_HALF_OF_MOVIE) { return; }
 
     for (_, mut transform) in enemies.iter_mut() {
         if transform.translation.x == -100.0 && transform.translation.z == -100.0 {
@@ -63,7 +65,7 @@ fn move_enemy_into_movie(
     }
 
 
-    if game_state.has_avoided_movie_guard { 
+    if game_state.flags.is_set(flags::HAS_AVOIDED_MOVIE_GUARD) { 
 
         // I don't know why but this isn't working but it works if I do this here 
 
@@ -93,11 +95,12 @@ fn move_enemy_into_movie(
         current_cutscene.trigger(
             vec!(
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::D, cutscene::Position::Right),
-                cutscene::CutsceneSegment::Textbox("Wow, that was close!".to_string()),
+                cutscene::CutsceneSegment::Textbox("close_call.1".to_string()),
                 cutscene::CutsceneSegment::LevelSwitch(cutscene::Level::Outside),
             ),
             cutscene::Level::Movie
         );
+        current_cutscene.set_id("close_call");
         state.push(AppState::Cutscene).unwrap();
     }
 }
@@ -118,7 +121,7 @@ fn listen_for_level_reset(
 fn reset_level( 
     mut state: ResMut<State<crate::AppState>>,
     mut timer: Local<f32>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     *timer += time.delta_seconds();
 
@@ -140,6 +143,7 @@ fn load_level(
     mut level_info_state: ResMut<asset_loader::LevelInfoState>, 
     level_info_assets: ResMut<Assets<asset_loader::LevelInfo>>,
     mut game_state: ResMut<GameState>,
+    mut rng: ResMut<crate::rng::GameRng>,
 
     mut state: ResMut<State<crate::AppState>>,
 
@@ -148,6 +152,7 @@ fn load_level(
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
         println!("Level loaded");
+        game_state.load_roster(level_asset, &mut rng);
     } else {
         // try again later?
         println!("failed to load movie room");
@@ -182,12 +187,7 @@ fn load_level(
                 });
             }).id();
 
-    game_state.last_positions = [
-                (Kid::A, Some(Vec3::new(0.0, 0.0, 0.0))), 
-                (Kid::B, Some(Vec3::new(0.0, 0.0, -1.0))),
-                (Kid::C, Some(Vec3::new(0.0, 0.0, 0.5))), 
-                (Kid::D, Some(Vec3::new(0.0, 0.0, -0.5))),
-    ].iter().cloned().collect();
+    game_state.place_party(Vec3::ZERO);
     player::spawn_player(&mut commands, &mut materials, &mut meshes, 
                          &person_meshes, &theater_meshes, &game_state);
 }
@@ -205,6 +205,7 @@ fn cleanup_environment(
     enemy: Query<Entity, With<enemy::Enemy>>,
     camera: Query<Entity, With<camera::MainCamera>>,
     collision_meshes: Query<Entity, With<level_collision::DebugLevelCollisionMesh>>, 
+    items: Query<Entity, With<inventory::ItemMarker>>,
 ) {
     for entity in level_mesh.iter() {
         commands.entity(entity).despawn_recursive();
@@ -226,5 +227,9 @@ fn cleanup_environment(
     for entity in collision_meshes.iter() {
         commands.entity(entity).despawn_recursive();
     }
+
+    for entity in items.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
@@ -1,14 +1,13 @@
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
+use bevy_kira_audio::{Audio, AudioChannel, AudioSource};
 use std::collections::HashMap;
-use serde::Deserialize;
-use bevy::reflect::{TypeUuid};
 
 
 pub struct AudioState {
     pub channels: HashMap<AudioChannel, ChannelAudioState>,
     pub sound_channel: AudioChannel,
     pub music_channel: AudioChannel,
+    pub voice_channel: AudioChannel,
     pub music_handle: Handle<AudioSource>,
 }
 
@@ -16,7 +15,6 @@ pub struct AudioState {
 pub struct ChannelAudioState {
     stopped: bool,
     paused: bool,
-    loop_started: bool,
     volume: f32,
 }
 
@@ -25,7 +23,6 @@ impl Default for ChannelAudioState {
         ChannelAudioState {
             volume: 0.6,
             stopped: true,
-            loop_started: false,
             paused: false,
         }
     }
@@ -36,6 +33,7 @@ impl AudioState {
         let mut channels = HashMap::new();
         let sound_channel = AudioChannel::new("first".to_owned());
         let music_channel = AudioChannel::new("music".to_owned());
+        let voice_channel = AudioChannel::new("voice".to_owned());
 
         channels.insert(
             sound_channel.clone(),
@@ -45,12 +43,17 @@ impl AudioState {
             music_channel.clone(),
             ChannelAudioState::default(),
         );
+        channels.insert(
+            voice_channel.clone(),
+            ChannelAudioState::default(),
+        );
 
         AudioState {
             sound_channel,
             music_channel,
+            voice_channel,
             channels,
-            music_handle: asset_server.load("music/music.wav"),
+            music_handle: asset_server.load("music/music.ogg"),
         }
     }
 
@@ -64,7 +67,7 @@ impl AudioState {
         handle: &Handle<AudioSource>, 
         channel: &AudioChannel
     ) {
-        let mut channel_audio_state = channels.get_mut(channel).unwrap();
+        let channel_audio_state = channels.get_mut(channel).unwrap();
         channel_audio_state.paused = false;
         channel_audio_state.stopped = false;
 
@@ -73,9 +76,29 @@ impl AudioState {
     }
 }
 
-pub fn play_music(
-    audio: Res<Audio>,
-    audio_state: Res<AudioState>,
+pub fn setup_audio(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
 ) {
-//    audio.set_volume_in_channel(1.0, &audio_state.music_channel);
+    commands.insert_resource(AudioState::new(&asset_server));
+}
+
+// what someone sounds like when their lines get typed out
+#[derive(Debug, Copy, Clone)]
+pub struct Voice {
+    pub sound: &'static str,
+    pub pitch: f32,
+}
+
+pub fn play_voice(
+    audio: &Audio,
+    audio_state: &AudioState,
+    asset_server: &AssetServer,
+    voice: &Voice,
+) {
+    let volume = audio_state.channels.get(&audio_state.voice_channel).map(|c| c.volume).unwrap_or(1.0);
+
+    audio.set_volume_in_channel(volume, &audio_state.voice_channel);
+    audio.set_playback_rate_in_channel(voice.pitch, &audio_state.voice_channel);
+    audio.play_in_channel(asset_server.load(voice.sound), &audio_state.voice_channel);
 }
@@ -0,0 +1,516 @@
+use bevy::prelude::*;
+use bevy::render::camera::PerspectiveProjection;
+use std::collections::{HashMap, VecDeque};
+use crate::{actions, asset_loader, camera::MainCamera, enemy, inventory, locale, party, pathing, player, GameState, Kid, LevelResetEvent, replay};
+
+static CURSOR_SPEED: f32 = 8.0;
+static ARRIVE_DISTANCE: f32 = 0.3;
+static FOLLOW_DISTANCE: f32 = 3.0;
+static WAIT_TIME: f32 = 3.0;
+static DISTRACT_DISTANCE: f32 = 1.5;
+
+// how close the cursor has to be to a guard or kid to pick them as the target
+static TARGET_DISTANCE: f32 = 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Order {
+    MoveTo(Vec3),
+    Wait(f32),
+    Follow(Kid),
+    Distract(Entity),
+}
+
+impl Order {
+    // a string id, see locale.rs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Order::MoveTo(_) => "order.move",
+            Order::Wait(_) => "order.wait",
+            Order::Follow(_) => "order.follow",
+            Order::Distract(_) => "order.distract",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct KidOrders {
+    pub queue: VecDeque::<Order>,
+    // the points the current order is walking through
+    path: Vec::<Vec3>,
+}
+
+// a kid with an entry here is taking orders and holds still once they run out,
+// clearing the entry puts them back with the group
+#[derive(Default)]
+pub struct Orders {
+    pub kids: HashMap::<Kid, KidOrders>,
+}
+
+impl Orders {
+    pub fn is_busy(&self, kid: Kid) -> bool {
+        self.kids.contains_key(&kid)
+    }
+
+    pub fn give(&mut self, kid: Kid, order: Order, queue: bool) {
+        let kid_orders = self.kids.entry(kid).or_insert_with(KidOrders::default);
+        if !queue {
+            kid_orders.queue.clear();
+            kid_orders.path.clear();
+        }
+        kid_orders.queue.push_back(order);
+    }
+}
+
+#[derive(Default)]
+pub struct CommandMode {
+    pub active: bool,
+    pub selected: Option::<Kid>,
+    pub cursor: Vec3,
+}
+
+pub struct OrderCursor;
+pub struct CommandHudMarker;
+
+pub struct OrdersPlugin;
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Orders>()
+           .init_resource::<CommandMode>()
+           .add_system(clear_orders_on_level_reset.system())
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::InGame)
+                   .with_system(clear_orders.system())
+                   .with_system(create_command_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Credits)
+                   .with_system(cleanup_command_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::MainMenu)
+                   .with_system(cleanup_command_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Lobby)
+                   .with_system(clear_orders.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Movie)
+                   .with_system(clear_orders.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(command_input.system())
+                   .with_system(follow_orders.system())
+                   .with_system(update_order_cursor.system())
+                   .with_system(update_command_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(command_input.system())
+                   .with_system(follow_orders.system())
+                   .with_system(update_order_cursor.system())
+                   .with_system(update_command_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(command_input.system())
+                   .with_system(follow_orders.system())
+                   .with_system(update_order_cursor.system())
+                   .with_system(update_command_hud.system())
+           );
+    }
+}
+
+pub fn clear_orders(
+    mut orders: ResMut<Orders>,
+    mut command_mode: ResMut<CommandMode>,
+) {
+    orders.kids.clear();
+    command_mode.active = false;
+    command_mode.selected = None;
+}
+
+pub fn clear_orders_on_level_reset(
+    mut level_reset_event_reader: EventReader<LevelResetEvent>,
+    orders: ResMut<Orders>,
+    command_mode: ResMut<CommandMode>,
+) {
+    if level_reset_event_reader.iter().count() > 0 {
+        clear_orders(orders, command_mode);
+    }
+}
+
+// kids nobody is playing as can be given orders
+fn orderable_kids(game_state: &GameState) -> Vec::<Kid> {
+    let mut kids = game_state.remaining_kids()
+                             .into_iter()
+                             .filter(|kid| !game_state.is_controlled(*kid))
+                             .collect::<Vec::<_>>();
+    // the hashmap order jumps around, keep it the same every time they cycle
+    kids.sort();
+    kids
+}
+
+// where the mouse is pointing on the floor at the given height
+fn cursor_to_floor(
+    windows: &Res<Windows>,
+    camera_transform: &GlobalTransform,
+    projection: &PerspectiveProjection,
+    height: f32,
+) -> Option::<Vec3> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let screen = Vec2::new(cursor.x / window.width() * 2.0 - 1.0,
+                           cursor.y / window.height() * 2.0 - 1.0);
+
+    let half_height = (projection.fov / 2.0).tan();
+    let direction = camera_transform.rotation
+                  * Vec3::new(screen.x * half_height * projection.aspect_ratio, screen.y * half_height, -1.0);
+    if direction.y.abs() < 0.0001 {
+        return None;
+    }
+
+    let distance = (height - camera_transform.translation.y) / direction.y;
+    if distance < 0.0 {
+        None
+    } else {
+        Some(camera_transform.translation + direction * distance)
+    }
+}
+
+pub fn command_input(
+    actions: Res<actions::ActionState>,
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    cameras: Query<(&GlobalTransform, &PerspectiveProjection), With<MainCamera>>,
+    players: Query<(&player::Player, &Transform)>,
+    enemies: Query<(Entity, &enemy::Enemy, &Transform)>,
+    mut command_mode: ResMut<CommandMode>,
+    mut orders: ResMut<Orders>,
+    game_state: Res<GameState>,
+    time: Res<replay::GameTime>,
+) {
+    let kids = orderable_kids(&game_state);
+    let kid_position = |kid: Kid| {
+        players.iter()
+               .find(|(p, _)| p.kid == kid)
+               .map(|(_, transform)| transform.translation)
+    };
+
+    if actions.just_pressed(actions::GameAction::Command) {
+        if command_mode.active {
+            command_mode.active = false;
+        } else if let Some(kid) = kids.first() {
+            command_mode.active = true;
+            command_mode.selected = Some(*kid);
+            if let Some(position) = kid_position(*kid) {
+                command_mode.cursor = position;
+            }
+        } else {
+            println!("Nobody free to give orders to");
+        }
+    }
+
+    if !command_mode.active {
+        return;
+    }
+
+    // whoever was selected might have been picked up by a co-op player or left the level
+    let selected =
+        match command_mode.selected.filter(|kid| kids.contains(kid)).or_else(|| kids.first().cloned()) {
+            Some(kid) => kid,
+            None => {
+                command_mode.active = false;
+                return;
+            }
+        };
+
+    if actions.just_pressed(actions::GameAction::Switch) {
+        let current = kids.iter().position(|kid| *kid == selected).unwrap_or(0);
+        command_mode.selected = Some(kids[(current + 1) % kids.len()]);
+    } else {
+        command_mode.selected = Some(selected);
+    }
+    let selected = command_mode.selected.unwrap();
+
+    let movement = actions.movement;
+    command_mode.cursor += Vec3::new(movement.y, 0.0, movement.x) * CURSOR_SPEED * time.delta_seconds();
+
+    // clicking only means something while giving orders, it puts the cursor there and gives the order
+    let clicked = mouse_input.just_pressed(MouseButton::Left);
+    if clicked {
+        let height = kid_position(selected).map(|p| p.y).unwrap_or(0.0);
+        for (camera_transform, projection) in cameras.iter() {
+            if let Some(point) = cursor_to_floor(&windows, camera_transform, projection, height) {
+                command_mode.cursor = point;
+            }
+        }
+    }
+
+    // holding sprint adds to the end of the plan instead of replacing it
+    let queue = actions.pressed(actions::GameAction::Sprint);
+    let cursor = command_mode.cursor;
+
+    if actions.just_pressed(actions::GameAction::Action) || clicked {
+        let guard =
+            enemies.iter()
+                   .find(|(_, _, transform)| transform.translation.distance(cursor) < TARGET_DISTANCE)
+                   .map(|(entity, _, _)| entity);
+        let kid =
+            players.iter()
+                   .find(|(p, transform)| p.kid != selected && transform.translation.distance(cursor) < TARGET_DISTANCE)
+                   .map(|(p, _)| p.kid);
+
+        let order =
+            if let Some(guard) = guard {
+                Order::Distract(guard)
+            } else if let Some(kid) = kid {
+                Order::Follow(kid)
+            } else {
+                Order::MoveTo(cursor)
+            };
+
+        println!("Ordering {:?} to {:?}", selected, order);
+        orders.give(selected, order, queue);
+    }
+
+    if actions.just_pressed(actions::GameAction::Gadget) {
+        orders.give(selected, Order::Wait(WAIT_TIME), queue);
+    }
+
+    if actions.just_pressed(actions::GameAction::CycleGadget) {
+        println!("{:?} is back with the group", selected);
+        orders.kids.remove(&selected);
+    }
+}
+
+// walks along the path, true once the end is reached
+fn walk_path(
+    player: &mut player::Player,
+    transform: &Transform,
+    path: &mut Vec::<Vec3>,
+) -> bool {
+    while let Some(next) = path.first() {
+        let direction = Vec3::new(next.x - transform.translation.x, 0.0, next.z - transform.translation.z);
+        if direction.length() < ARRIVE_DISTANCE {
+            path.remove(0);
+        } else {
+            player.movement = Some(direction.normalize());
+            return false;
+        }
+    }
+
+    true
+}
+
+pub fn follow_orders(
+    mut orders: ResMut<Orders>,
+    mut players: Query<(&mut player::Player, &Transform), Without<enemy::Enemy>>,
+    mut enemies: Query<(&mut enemy::Enemy, &Transform), Without<player::Player>>,
+    game_state: Res<GameState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    time: Res<replay::GameTime>,
+) {
+    let level_info = match level_info_assets.get(&level_info_state.handle) {
+                         Some(level_info) => level_info,
+                         None => return
+                     };
+
+    let kid_positions = players.iter_mut()
+                               .map(|(p, transform)| (p.kid, transform.translation))
+                               .collect::<HashMap::<_, _>>();
+
+    for (mut player, transform) in players.iter_mut() {
+        // somebody took over this kid so the plan is off
+        if game_state.is_controlled(player.kid) {
+            orders.kids.remove(&player.kid);
+            continue;
+        }
+
+        let kid_orders = match orders.kids.get_mut(&player.kid) {
+                             Some(kid_orders) => kid_orders,
+                             None => continue
+                         };
+
+        if player.is_hidden { continue; }
+        player.movement_mode = player::MovementMode::Walk;
+
+        let order = match kid_orders.queue.front_mut() {
+                        Some(order) => order,
+                        None => continue
+                    };
+
+        let done =
+            match order {
+                Order::MoveTo(target) => {
+                    if kid_orders.path.is_empty() {
+                        match pathing::find_path(level_info, game_state.current_level, transform.translation, *target,
+                                                 game_state.has_trait(player.kid, party::KidTrait::Small)) {
+                            Some(path) => kid_orders.path = path,
+                            None => {
+                                println!("{:?} can't get to {:?}", player.kid, target);
+                                kid_orders.queue.pop_front();
+                                continue;
+                            }
+                        }
+                    }
+
+                    walk_path(&mut player, transform, &mut kid_orders.path)
+                },
+                Order::Wait(time_left) => {
+                    *time_left -= time.delta_seconds();
+                    *time_left <= 0.0
+                },
+                Order::Follow(kid) => {
+                    // following doesn't end, they keep at it until told otherwise
+                    if let Some(position) = kid_positions.get(kid) {
+                        let direction = Vec3::new(position.x - transform.translation.x, 0.0, position.z - transform.translation.z);
+                        if direction.length() > FOLLOW_DISTANCE {
+                            player.movement = Some(direction.normalize());
+                        }
+                    }
+                    false
+                },
+                Order::Distract(entity) => {
+                    match enemies.get_mut(*entity) {
+                        Ok((mut enemy, enemy_transform)) => {
+                            if enemy_transform.translation.distance(transform.translation) <= DISTRACT_DISTANCE {
+                                kid_orders.path.clear();
+                                if player::can_distract(&player, transform, &enemy, enemy_transform, &game_state) {
+                                    println!("{:?} is distracting", player.kid);
+                                    enemy.is_distracted = true;
+                                    player.is_distracting = Some(*entity);
+                                } else if !enemy.is_distracted {
+                                    println!("{:?} can't distract that one, no ticket", player.kid);
+                                }
+                                true
+                            } else {
+                                if kid_orders.path.is_empty() {
+                                    kid_orders.path = pathing::find_path(level_info, game_state.current_level,
+                                                                         transform.translation, enemy_transform.translation,
+                                                                         game_state.has_trait(player.kid, party::KidTrait::Small))
+                                                                         .unwrap_or_else(|| vec!(enemy_transform.translation));
+                                }
+                                walk_path(&mut player, transform, &mut kid_orders.path);
+                                false
+                            }
+                        },
+                        Err(_) => true
+                    }
+                },
+            };
+
+        if done {
+            kid_orders.queue.pop_front();
+            kid_orders.path.clear();
+        }
+    }
+}
+
+pub fn update_order_cursor(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    command_mode: Res<CommandMode>,
+    mut cursors: Query<(Entity, &mut Transform), With<OrderCursor>>,
+) {
+    if !command_mode.active {
+        for (entity, _) in cursors.iter_mut() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let position = command_mode.cursor + Vec3::new(0.0, 0.1, 0.0);
+    if let Some((_, mut transform)) = cursors.iter_mut().next() {
+        transform.translation = position;
+    } else {
+        commands.spawn_bundle(PbrBundle {
+                    mesh: meshes.add(Mesh::from(shape::Box::new(0.5, 0.05, 0.5))),
+                    material: materials.add(Color::rgb(0.9, 0.9, 0.2).into()),
+                    transform: Transform::from_translation(position),
+                    ..Default::default()
+                })
+                .insert(OrderCursor)
+                .insert(inventory::ItemMarker);
+    }
+}
+
+pub fn cleanup_command_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<CommandHudMarker>>,
+) {
+    for entity in huds.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn create_command_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    existing_huds: Query<Entity, With<CommandHudMarker>>,
+) {
+    if existing_huds.iter().count() > 0 {
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "".to_string(),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::rgb(0.9, 0.9, 0.2),
+                },
+                TextAlignment {
+                    ..Default::default()
+                }
+            ),
+            ..Default::default()
+        })
+        .insert(CommandHudMarker);
+}
+
+pub fn update_command_hud(
+    command_mode: Res<CommandMode>,
+    orders: Res<Orders>,
+    game_state: Res<GameState>,
+    strings: Res<locale::Strings>,
+    mut hud_text: Query<&mut Text, With<CommandHudMarker>>,
+) {
+    let value =
+        match command_mode.selected {
+            Some(kid) if command_mode.active => {
+                let plan =
+                    orders.kids.get(&kid)
+                               .map(|kid_orders| {
+                                   kid_orders.queue
+                                             .iter()
+                                             .map(|order| strings.get(order.name()))
+                                             .collect::<Vec::<_>>()
+                                             .join(" > ")
+                               })
+                               .filter(|plan| !plan.is_empty())
+                               .unwrap_or_else(|| strings.get("hud.orders_nothing"));
+                strings.get_with("hud.orders", &[&game_state.kid_name(kid), &plan])
+            },
+            _ => "".to_string()
+        };
+
+    for mut text in hud_text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use bevy::reflect::{TypeUuid};
+use crate::{asset_loader, cutscene, inventory, locale, player, rng, theater_outside, AppState, GameState, Kid};
+
+// how close the controlled kid has to get before a recruit says hi
+static RECRUIT_DISTANCE: f32 = 2.0;
+
+pub static FAST_SPEED_SCALE: f32 = 1.3;
+// how long a guard keeps talking after a smooth talker walks off
+pub static SMOOTH_TALKER_LINGER: f32 = 4.0;
+// how much farther past a guard's cone a lookout gets warned
+pub static LOOKOUT_CONE_SCALE: f32 = 1.5;
+
+#[derive(Debug, Copy, Clone, Deserialize, PartialEq)]
+pub enum KidTrait {
+    Fast,
+    // can crawl through vents
+    Small,
+    SmoothTalker,
+    Lookout,
+}
+
+// these are string ids, see locale.rs
+impl KidTrait {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KidTrait::Fast => "trait.fast",
+            KidTrait::Small => "trait.small",
+            KidTrait::SmoothTalker => "trait.smooth_talker",
+            KidTrait::Lookout => "trait.lookout",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            KidTrait::Fast => "trait.fast.description",
+            KidTrait::Small => "trait.small.description",
+            KidTrait::SmoothTalker => "trait.smooth_talker.description",
+            KidTrait::Lookout => "trait.lookout.description",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "7d1e3a52-5c0b-4f61-9a0e-2b8f3c6d9e14"]
+pub struct KidInfo {
+    pub kid: Kid,
+    pub name: String,
+    // kids that aren't in the party at the start have to be recruited
+    #[serde(default)]
+    pub in_party: bool,
+    #[serde(default)]
+    pub ability: Option::<KidTrait>,
+}
+
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "2b6f0c8e-91d4-4a37-b5e2-7c4a1f0d3b68"]
+pub struct RecruitSpawnPoint {
+    pub level: cutscene::Level,
+    pub location: Vec2,
+    pub kid: Kid,
+    // plays when they get talked to, they join the party at the end of it unless it hits an End
+    #[serde(default)]
+    pub cutscene: Vec::<cutscene::CutsceneSegment>,
+}
+
+// the original four, used if the level data doesn't have a roster
+pub fn default_roster() -> Vec::<KidInfo> {
+    vec!(
+        KidInfo { kid: Kid::A, name: "A".to_string(), in_party: true, ability: Some(KidTrait::SmoothTalker) },
+        KidInfo { kid: Kid::B, name: "B".to_string(), in_party: true, ability: Some(KidTrait::Fast) },
+        KidInfo { kid: Kid::C, name: "C".to_string(), in_party: true, ability: Some(KidTrait::Small) },
+        KidInfo { kid: Kid::D, name: "D".to_string(), in_party: true, ability: Some(KidTrait::Lookout) },
+    )
+}
+
+pub struct KidHudMarker;
+
+pub struct Recruit {
+    pub kid: Kid,
+    has_been_triggered: bool,
+}
+
+pub struct PartyPlugin;
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+               SystemSet::on_enter(crate::AppState::InGame)
+                   .with_system(spawn_recruits.system())
+                   .with_system(create_kid_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Credits)
+                   .with_system(cleanup_kid_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::MainMenu)
+                   .with_system(cleanup_kid_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Lobby)
+                   .with_system(spawn_recruits.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Movie)
+                   .with_system(spawn_recruits.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(check_for_recruits.system())
+                   .with_system(join_recruits.system())
+                   .with_system(update_kid_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(check_for_recruits.system())
+                   .with_system(join_recruits.system())
+                   .with_system(update_kid_hud.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(check_for_recruits.system())
+                   .with_system(join_recruits.system())
+                   .with_system(update_kid_hud.system())
+           );
+    }
+}
+
+pub fn spawn_recruits(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    theater_meshes: Res<theater_outside::TheaterMeshes>,
+    mut game_state: ResMut<GameState>,
+    mut rng: ResMut<rng::GameRng>,
+    state: Res<State<AppState>>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+) {
+    let level = match cutscene::Level::from_state(state.current()) {
+                    Some(level) => level,
+                    None => return
+                };
+
+    if let Some(level_info) = level_info_assets.get(&level_info_state.handle) {
+        game_state.load_roster(level_info, &mut rng);
+
+        for recruit in level_info.recruits.iter() {
+            if recruit.level != level || game_state.party.contains(&recruit.kid) { continue; }
+
+            let position = Vec3::new(recruit.location.x, 0.0, recruit.location.y);
+            let entity = player::spawn_kid(&mut commands, &mut materials, &theater_meshes, &game_state, recruit.kid, position);
+            commands.entity(entity)
+                    .insert(Recruit { kid: recruit.kid, has_been_triggered: false })
+                    .insert(inventory::ItemMarker);
+        }
+    }
+}
+
+pub fn check_for_recruits(
+    mut recruits: Query<(&mut Recruit, &Transform)>,
+    players: Query<(&player::Player, &Transform)>,
+    mut current_cutscene: ResMut<cutscene::CurrentCutscene>,
+    mut state: ResMut<State<AppState>>,
+    game_state: Res<GameState>,
+    level_info_state: Res<asset_loader::LevelInfoState>,
+    level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
+) {
+    let level_info = match level_info_assets.get(&level_info_state.handle) {
+                         Some(level_info) => level_info,
+                         None => return
+                     };
+
+    for (mut recruit, recruit_transform) in recruits.iter_mut() {
+        let close_enough = players.iter()
+                                  .filter(|(p, _)| game_state.is_controlled(p.kid))
+                                  .any(|(_, t)| t.translation.distance(recruit_transform.translation) < RECRUIT_DISTANCE);
+
+        if recruit.has_been_triggered {
+            // they got turned down, they'll ask again if the kids walk off and come back
+            if !close_enough && !game_state.party.contains(&recruit.kid) {
+                recruit.has_been_triggered = false;
+            }
+            continue;
+        }
+
+        if !close_enough { continue; }
+
+        let mut segments = level_info.recruits
+                                     .iter()
+                                     .find(|r| r.kid == recruit.kid)
+                                     .map(|r| r.cutscene.clone())
+                                     .unwrap_or_default();
+        segments.push(cutscene::CutsceneSegment::Recruit(recruit.kid));
+
+        println!("Recruiting {:?}", recruit.kid);
+        recruit.has_been_triggered = true;
+        current_cutscene.trigger(segments, game_state.current_level);
+        state.push(AppState::Cutscene).unwrap();
+        return;
+    }
+}
+
+// once the cutscene adds them to the party the npc becomes a regular kid
+pub fn join_recruits(
+    mut commands: Commands,
+    recruits: Query<(Entity, &Recruit, &Transform)>,
+    mut game_state: ResMut<GameState>,
+) {
+    for (entity, recruit, transform) in recruits.iter() {
+        if !game_state.party.contains(&recruit.kid) { continue; }
+
+        println!("{:?} joined the party", recruit.kid);
+        game_state.last_positions.insert(recruit.kid, Some(transform.translation));
+        commands.entity(entity)
+                .remove::<Recruit>()
+                .remove::<inventory::ItemMarker>()
+                .insert(player::new_player(recruit.kid));
+    }
+}
+
+pub fn cleanup_kid_hud(
+    mut commands: Commands,
+    huds: Query<Entity, With<KidHudMarker>>,
+) {
+    for entity in huds.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn create_kid_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    existing_huds: Query<Entity, With<KidHudMarker>>,
+) {
+    if existing_huds.iter().count() > 0 {
+        return;
+    }
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "".to_string(),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    ..Default::default()
+                }
+            ),
+            ..Default::default()
+        })
+        .insert(KidHudMarker);
+}
+
+// who you're playing as and what they're good at
+pub fn update_kid_hud(
+    game_state: Res<GameState>,
+    strings: Res<locale::Strings>,
+    mut hud_text: Query<&mut Text, With<KidHudMarker>>,
+) {
+    let kid = game_state.controlling;
+    let value =
+        match game_state.kid_trait(kid) {
+            Some(ability) => strings.get_with("hud.kid", &[&game_state.kid_name(kid), &strings.get(ability.name()),
+                                                             &strings.get(ability.description())]),
+            None => game_state.kid_name(kid),
+        };
+
+    for mut text in hud_text.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
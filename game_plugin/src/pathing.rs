@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use crate::{asset_loader, cutscene, level_collision::{CollisionShape, RectangleCollision}};
+
+// rectangles closer than this count as connected
+static TOUCH_DISTANCE: f32 = 0.1;
+
+// the same floor fit_in_level lets kids walk on
+fn walkable_rects<'a>(
+    level_info: &'a asset_loader::LevelInfo,
+    current_level: cutscene::Level,
+    can_use_vents: bool,
+) -> Vec::<&'a RectangleCollision> {
+    level_info.collision_info.shapes
+              .iter()
+              .filter(|(level, _)| *level == current_level)
+              .filter_map(|(_, shape)| {
+                  match shape {
+                      CollisionShape::Rect((r, _))
+                    | CollisionShape::LevelSwitch((r, _))
+                    | CollisionShape::TicketCheck((r, _))
+                    | CollisionShape::GetTicket((r, _))
+                    | CollisionShape::Stair(r) => Some(r),
+                      CollisionShape::Vent(r) if can_use_vents => Some(r),
+                      _ => None
+                  }
+              })
+              .collect()
+}
+
+fn contains(r: &RectangleCollision, position: Vec3) -> bool {
+    position.x >= r.bottom_x
+    && position.x <= r.top_x
+    && position.z <= r.right_z
+    && position.z >= r.left_z
+}
+
+fn touches(a: &RectangleCollision, b: &RectangleCollision) -> bool {
+    a.bottom_x <= b.top_x + TOUCH_DISTANCE
+    && b.bottom_x <= a.top_x + TOUCH_DISTANCE
+    && a.left_z <= b.right_z + TOUCH_DISTANCE
+    && b.left_z <= a.right_z + TOUCH_DISTANCE
+}
+
+// middle of where two rectangles meet, so kids go through doorways instead of clipping corners
+fn crossing_point(a: &RectangleCollision, b: &RectangleCollision) -> Vec3 {
+    let bottom_x = a.bottom_x.max(b.bottom_x);
+    let top_x = a.top_x.min(b.top_x);
+    let left_z = a.left_z.max(b.left_z);
+    let right_z = a.right_z.min(b.right_z);
+
+    Vec3::new((bottom_x + top_x) / 2.0, 0.0, (left_z + right_z) / 2.0)
+}
+
+pub fn is_walkable(
+    level_info: &asset_loader::LevelInfo,
+    current_level: cutscene::Level,
+    position: Vec3,
+    can_use_vents: bool,
+) -> bool {
+    walkable_rects(level_info, current_level, can_use_vents).iter().any(|r| contains(r, position))
+}
+
+// breadth first over the floor rectangles, returns the points to walk through ending at the target
+pub fn find_path(
+    level_info: &asset_loader::LevelInfo,
+    current_level: cutscene::Level,
+    from: Vec3,
+    to: Vec3,
+    can_use_vents: bool,
+) -> Option::<Vec::<Vec3>> {
+    let rects = walkable_rects(level_info, current_level, can_use_vents);
+    let start = rects.iter().position(|r| contains(r, from))?;
+    let goal = rects.iter().position(|r| contains(r, to))?;
+
+    let mut came_from: Vec::<Option::<usize>> = vec!(None; rects.len());
+    let mut visited = vec!(false; rects.len());
+    let mut queue = VecDeque::new();
+    visited[start] = true;
+    queue.push_back(start);
+
+    while let Some(current) = queue.pop_front() {
+        if current == goal { break; }
+
+        for next in 0..rects.len() {
+            if !visited[next] && touches(rects[current], rects[next]) {
+                visited[next] = true;
+                came_from[next] = Some(current);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    if !visited[goal] {
+        return None;
+    }
+
+    let mut path = vec!(Vec3::new(to.x, 0.0, to.z));
+    let mut current = goal;
+    while let Some(previous) = came_from[current] {
+        path.push(crossing_point(rects[previous], rects[current]));
+        current = previous;
+    }
+    path.reverse();
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // floor pieces are (bottom_x, top_x) along x, all of them span z 0 to 2
+    fn level(rects: &[(&str, f32, f32)]) -> asset_loader::LevelInfo {
+        let shapes = rects.iter()
+                          .map(|(kind, bottom_x, top_x)| {
+                              let r = format!("(left_z: 0.0, right_z: 2.0, top_x: {:?}, bottom_x: {:?}, height: 0.0, base_height: 0.0)",
+                                              top_x, bottom_x);
+                              match *kind {
+                                  "Vent" | "Stair" => format!("(Outside, {}({}))", kind, r),
+                                  _ => format!("(Outside, {}(({}, None)))", kind, r),
+                              }
+                          })
+                          .collect::<Vec::<_>>()
+                          .join(",");
+        let lvl = format!("(collision_info: (shapes: [{}]), cutscenes: (cutscenes: []), enemies: [])", shapes);
+
+        ron::de::from_str(&lvl).unwrap()
+    }
+
+    #[test]
+    fn walks_through_the_middle_of_each_doorway() {
+        let level = level(&[("Rect", 0.0, 2.0), ("Rect", 2.0, 4.0), ("Rect", 4.0, 6.0)]);
+        let path = find_path(&level, cutscene::Level::Outside, Vec3::new(1.0, 0.0, 1.0), Vec3::new(5.0, 0.0, 1.5), false);
+
+        assert_eq!(path, Some(vec!(Vec3::new(2.0, 0.0, 1.0), Vec3::new(4.0, 0.0, 1.0), Vec3::new(5.0, 0.0, 1.5))));
+    }
+
+    #[test]
+    fn goes_straight_there_in_the_same_room() {
+        let level = level(&[("Rect", 0.0, 2.0)]);
+        let path = find_path(&level, cutscene::Level::Outside, Vec3::new(0.5, 0.0, 0.5), Vec3::new(1.5, 3.0, 1.5), false);
+
+        assert_eq!(path, Some(vec!(Vec3::new(1.5, 0.0, 1.5))));
+    }
+
+    #[test]
+    fn no_path_off_the_floor_or_between_rooms_that_dont_touch() {
+        let level = level(&[("Rect", 0.0, 2.0), ("Rect", 5.0, 7.0)]);
+
+        assert_eq!(find_path(&level, cutscene::Level::Outside, Vec3::new(1.0, 0.0, 1.0), Vec3::new(3.5, 0.0, 1.0), false), None);
+        assert_eq!(find_path(&level, cutscene::Level::Outside, Vec3::new(1.0, 0.0, 1.0), Vec3::new(6.0, 0.0, 1.0), false), None);
+        assert_eq!(find_path(&level, cutscene::Level::Lobby, Vec3::new(1.0, 0.0, 1.0), Vec3::new(1.5, 0.0, 1.0), false), None);
+    }
+
+    #[test]
+    fn only_small_kids_go_through_vents() {
+        let level = level(&[("Rect", 0.0, 2.0), ("Vent", 2.0, 4.0), ("Stair", 4.0, 6.0)]);
+        let from = Vec3::new(1.0, 0.0, 1.0);
+        let to = Vec3::new(5.0, 0.0, 1.0);
+
+        assert_eq!(find_path(&level, cutscene::Level::Outside, from, to, false), None);
+        assert_eq!(find_path(&level, cutscene::Level::Outside, from, to, true).map(|p| p.len()), Some(3));
+    }
+}
@@ -2,15 +2,82 @@ use bevy::prelude::*;
 use bevy::app::Events;
 use bevy::app::AppExit;
 use std::collections::HashMap;
-use crate::game_controller;
+use crate::{actions, locale, rng, LevelResetEvent};
+
+pub struct PausePlugin;
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PauseButtonMaterials>()
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::InGame)
+                   .with_system(pause_game.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Lobby)
+                   .with_system(pause_game.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Movie)
+                   .with_system(pause_game.system())
+           )
+           .add_system_set(
+               SystemSet::on_enter(crate::AppState::Pause)
+                   .with_system(setup_menu.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(crate::AppState::Pause)
+                   .with_system(pause_menu.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(crate::AppState::Pause)
+                   .with_system(cleanup_pause_menu.system())
+           );
+    }
+}
+
+pub fn pause_game(
+    mut state: ResMut<State<crate::AppState>>,
+    actions: Res<actions::ActionState>,
+) {
+    if actions.just_pressed(actions::GameAction::Pause) {
+        state.push(crate::AppState::Pause).unwrap();
+    }
+}
 
 pub fn setup_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     button_materials: Res<PauseButtonMaterials>,
+    rng: Res<rng::GameRng>,
+    strings: Res<locale::Strings>,
 ) {
-    // ui camera
-    commands.spawn_bundle(UiCameraBundle::default());
+    // the level already has a ui camera for the follow text
+
+    // so bug reports and replays can say which seed they were on
+    let seed_text_entity = commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Percent(27.0),
+                    left: Val::Percent(45.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                strings.get_with("pause.seed", &[&rng.seed.to_string()]),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .id();
 
     let width = 350.0;
     let resume_button_entity = commands
@@ -37,7 +104,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Resume",
+                    strings.get("pause.resume"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -74,7 +141,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Restart Level",
+                    strings.get("pause.restart"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -111,7 +178,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Quit to Main Menu",
+                    strings.get("pause.main_menu"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -149,7 +216,7 @@ pub fn setup_menu(
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text::with_section(
-                    "Exit Game",
+                    strings.get("pause.exit"),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
@@ -168,6 +235,7 @@ pub fn setup_menu(
             main_menu_button_entity,
             quit_button_entity, 
             restart_button_entity,
+            seed_text_entity,
             selected: resume_button_entity 
         }
     );
@@ -178,6 +246,7 @@ pub struct PauseMenuData {
     main_menu_button_entity: Entity,
     quit_button_entity: Entity,
     restart_button_entity: Entity,
+    seed_text_entity: Entity,
     selected: Entity,
 }
 
@@ -186,23 +255,19 @@ pub fn cleanup_pause_menu(mut commands: Commands, menu_data: Res<PauseMenuData>)
     commands.entity(menu_data.main_menu_button_entity).despawn_recursive();
     commands.entity(menu_data.restart_button_entity).despawn_recursive();
     commands.entity(menu_data.quit_button_entity).despawn_recursive();
+    commands.entity(menu_data.seed_text_entity).despawn_recursive();
 }
 
 pub fn pause_menu(
     mut state: ResMut<State<crate::AppState>>,
     mut exit: ResMut<Events<AppExit>>,
     mut menu_data: ResMut<PauseMenuData>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<actions::ActionState>,
     button_materials: Res<PauseButtonMaterials>,
     mut button_colors: Query<(Entity, &mut Handle<ColorMaterial>), With<Button>>,
     interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<Input<GamepadButton>>,
-    gamepad: Option<Res<game_controller::GameController>>,
-    mut gamepad_buffer: Local<f32>,
-    time: Res<Time>,
+    mut level_reset_event_writer: EventWriter<LevelResetEvent>,
 ) {
-    *gamepad_buffer += time.delta_seconds();
     let mut selected_button = None;
 
     let mut next_button = HashMap::new();
@@ -217,28 +282,21 @@ pub fn pause_menu(
     prev_button.insert(menu_data.main_menu_button_entity, menu_data.restart_button_entity);
     prev_button.insert(menu_data.quit_button_entity, menu_data.main_menu_button_entity);
 
-    let mut pressed_buttons = game_controller::get_pressed_buttons(&axes, &buttons, gamepad);
-    if *gamepad_buffer < 0.25 {
-        pressed_buttons = vec!(); 
-    }
-
-    if !pressed_buttons.is_empty() {
-        *gamepad_buffer = 0.0;
-    }
-
     // keyboard and gamepad
-    if keyboard_input.just_pressed(KeyCode::Return) || keyboard_input.just_pressed(KeyCode::Space)
-    || pressed_buttons.contains(&game_controller::GameButton::Action){
+    if actions.just_pressed(actions::GameAction::Confirm) {
         selected_button = Some(menu_data.selected); 
     }
 
-    if keyboard_input.just_pressed(KeyCode::W) || keyboard_input.just_pressed(KeyCode::Up) 
-    || pressed_buttons.contains(&game_controller::GameButton::Up) {
+    // backing out of the pause menu is the same as resuming
+    if actions.just_pressed(actions::GameAction::Back) || actions.just_pressed(actions::GameAction::Pause) {
+        selected_button = Some(menu_data.resume_button_entity); 
+    }
+
+    if actions.just_pressed(actions::GameAction::MoveUp) {
         menu_data.selected = *prev_button.get(&menu_data.selected).unwrap();
     }
 
-    if keyboard_input.just_pressed(KeyCode::S) || keyboard_input.just_pressed(KeyCode::Down) 
-    || pressed_buttons.contains(&game_controller::GameButton::Down) {
+    if actions.just_pressed(actions::GameAction::MoveDown) {
         menu_data.selected = *next_button.get(&menu_data.selected).unwrap();
     }
 
@@ -256,10 +314,14 @@ pub fn pause_menu(
             state.pop().unwrap();
         }
         if selected_button == menu_data.restart_button_entity {
-            state.set(crate::AppState::RestartLevel).unwrap();
+            // nothing runs RestartLevel, so this resets the level the same way getting caught does
+            // and drops back into it
+            level_reset_event_writer.send(LevelResetEvent);
+            state.pop().unwrap();
         }
         if selected_button == menu_data.main_menu_button_entity {
-            state.set(crate::AppState::Loading).unwrap();
+            // the level is still on the stack under the pause menu, this unwinds it so it cleans up
+            state.replace(crate::AppState::Loading).unwrap();
         }
         if selected_button == menu_data.quit_button_entity {
             exit.send(AppExit);
@@ -1,20 +1,77 @@
 use bevy::prelude::*;
-use rand::seq::SliceRandom;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
 
-use crate::{Direction, game_controller, game_settings, asset_loader, level_collision, 
-            GameState, theater_outside, Kid, Mode, follow_text, enemy} ;
+use crate::{actions, orders, formation, game_settings, asset_loader, level_collision, 
+            GameState, theater_outside, Kid, Mode, follow_text, enemy, inventory, hiding, party,
+            customize, Colors, replay} ;
 
-static DISTRACT_TEXT: &str = "[DISTRACT]";
+static DISTRACT_TEXT: &str = "follow.distract";
 static DISTRACT_DISTANCE: f32 = 2.0;
+static DISGUISED_SPEED: f32 = 0.5;
+pub static DISGUISE_MOVEMENT_THRESHOLD: f32 = 0.01;
+static SNEAK_SPEED: f32 = 0.5;
+static SPRINT_SPEED: f32 = 1.75;
+pub static MAX_STAMINA: f32 = 2.5;
+static STAMINA_RECOVERY: f32 = 0.75;
+static STAMINA_TO_SPRINT: f32 = 0.5;
+static MAX_SPEED_SCALE: f32 = 0.1;
+pub static SNEAK_DETECTION_SCALE: f32 = 0.6;
+pub static SPRINT_DETECTION_SCALE: f32 = 1.25;
+pub static SPRINT_NOISE_DISTANCE: f32 = 5.0;
+static SNEAK_POSE_HEIGHT: f32 = 0.7;
+static SPRINT_POSE_LEAN: f32 = 0.3;
+static FORMATION_STOP_DISTANCE: f32 = 0.4;
+static CONE_DETOUR_ANGLES: [f32; 4] = [FRAC_PI_4, -FRAC_PI_4, FRAC_PI_2, -FRAC_PI_2];
+static CO_OP_FOLLOW_DISTANCE: f32 = 3.0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MovementMode {
+    Walk,
+    Sneak,
+    Sprint,
+}
+
+impl MovementMode {
+    pub fn speed(&self) -> f32 {
+        match self {
+            MovementMode::Walk => 1.0,
+            MovementMode::Sneak => SNEAK_SPEED,
+            MovementMode::Sprint => SPRINT_SPEED,
+        }
+    }
+
+    // how far out the guards can spot a kid moving like this
+    pub fn detection_scale(&self) -> f32 {
+        match self {
+            MovementMode::Walk => 1.0,
+            MovementMode::Sneak => SNEAK_DETECTION_SCALE,
+            MovementMode::Sprint => SPRINT_DETECTION_SCALE,
+        }
+    }
+}
+
 pub struct Player {
     pub kid: Kid,
     pub is_distracting: Option::<Entity>,
-    pub movement: Option::<Direction>,
+    // Up on the stick is +X and Right is +Z, the length is how hard they're pushing
+    pub movement: Option::<Vec3>,
     pub velocity: Vec3,
+    pub is_disguised: bool,
+    pub is_hidden: bool,
+    pub seen_hiding: bool,
+    pub movement_mode: MovementMode,
+    pub stamina: f32,
 }
 
 pub static SCALE: f32 = 0.36;
 
+// the kid's own meshes, so the box and accessories can be told apart from the body
+pub struct KidPart;
+
+// the body and what they're wearing hang off of this so crouching and leaning can move them
+// together, anything put on the kid later (like the box) goes on the kid itself
+pub struct KidPivot;
+
 #[derive(Default)]
 pub struct PersonMeshes {
     pub person: Handle<Mesh>,
@@ -31,6 +88,20 @@ pub fn load_assets(
     loading.asset_handles.push(person_meshes.person.clone_untyped());
 }
 
+pub fn new_player(kid: Kid) -> Player {
+    Player {
+        kid,
+        movement: None,
+        is_distracting: None,
+        velocity: Vec3::default(),
+        is_disguised: false,
+        is_hidden: false,
+        seen_hiding: false,
+        movement_mode: MovementMode::Walk,
+        stamina: MAX_STAMINA,
+    }
+}
+
 pub fn spawn_player(
     commands: &mut Commands, 
     materials: &mut ResMut<Assets<StandardMaterial>>,
@@ -40,110 +111,196 @@ pub fn spawn_player(
     game_state: &ResMut<GameState>,
 
 ) {
-    let kids = vec!(Kid::A, Kid::B, Kid::C, Kid::D);
-
-    for (i, kid) in kids.iter().enumerate() {
-        let position = game_state.last_positions[kid].unwrap();
-        let mut transform = Transform::from_translation(position);
-        transform.apply_non_uniform_scale(Vec3::new(SCALE, SCALE, SCALE)); 
-        transform.rotate(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), std::f32::consts::PI));
-
-        let leg_color = Color::hex(game_state.kid_colors[&kid].legs.clone()).unwrap();
-        let torso_color = Color::hex(game_state.kid_colors[&kid].torso.clone()).unwrap();
-        let skin_color = Color::hex(game_state.kid_colors[&kid].skin.clone()).unwrap();
-        let hair_color = Color::hex(game_state.kid_colors[&kid].hair.clone()).unwrap();
-
-        let player_entity = 
-        commands.spawn_bundle(PbrBundle {
-                    transform,
-                    ..Default::default()
-                })
-                .insert(Player {
-                    kid: *kid,
-                    movement: None,
-                    is_distracting: None,
-                    velocity: Vec3::default()
-                })
-                .with_children(|parent|  {
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: theater_meshes.kid_legs.clone(),
-                        material: materials.add(leg_color.into()),
-                        ..Default::default()
-                    });
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: theater_meshes.kid_torso.clone(),
-                        material: materials.add(torso_color.into()),
-                        ..Default::default()
-                    });
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: theater_meshes.kid_headhand.clone(),
-                        material: materials.add(skin_color.into()),
-                        ..Default::default()
-                    });
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: if *kid == Kid::D {
-                                theater_meshes.kid_hairtwo.clone()
-                              } else {
-                                  // omg this is gross
-                                  let mut rng = rand::thread_rng();
-                                  let mut nums: Vec<i32> = (0..1).collect();
-                                  nums.shuffle(&mut rng);
-                                  if *nums.last().unwrap() == 0{
-                                      theater_meshes.kid_hairone.clone()
-                                  } else {
-                                      theater_meshes.kid_hairtwo.clone()
-                                  }
-                              },
-                        material: materials.add(hair_color.into()),
-                        ..Default::default()
-                    });
-                    parent.spawn_bundle(PbrBundle {
-                        mesh: theater_meshes.kid_face.clone(),
-                        material: theater_meshes.face_material.clone(),
-                        ..Default::default()
-                    });
-                }).id();
+    for kid in game_state.party.iter() {
+        let position = match game_state.last_positions.get(kid) {
+                           Some(Some(position)) => *position,
+                           _ => continue
+                       };
+
+        let player_entity = spawn_kid(commands, materials, theater_meshes, game_state, *kid, position);
+        commands.entity(player_entity).insert(new_player(*kid));
+    }
+}
+
+// just the body, whoever calls this decides if it's a player or someone waiting to be recruited
+pub fn spawn_kid(
+    commands: &mut Commands, 
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    theater_meshes: &theater_outside::TheaterMeshes,
+    game_state: &GameState,
+    kid: Kid,
+    position: Vec3,
+) -> Entity {
+    let mut transform = Transform::from_translation(position);
+    transform.apply_non_uniform_scale(Vec3::new(SCALE, SCALE, SCALE)); 
+    transform.rotate(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), std::f32::consts::PI));
+
+    let colors = game_state.colors(kid);
+
+    commands.spawn_bundle(PbrBundle {
+                transform,
+                ..Default::default()
+            })
+            .with_children(|parent|  {
+                parent.spawn_bundle((Transform::default(), GlobalTransform::default()))
+                      .insert(KidPivot)
+                      .with_children(|pivot| {
+                          spawn_kid_parts(pivot, &colors, theater_meshes, materials);
+                          pivot.spawn_bundle(PbrBundle {
+                              mesh: theater_meshes.kid_face.clone(),
+                              material: theater_meshes.face_material.clone(),
+                              ..Default::default()
+                          }).insert(KidPart);
+                      });
+            }).id()
+}
+
+// everything but the face, cutscenes use their own faces so they can talk
+pub fn spawn_kid_parts(
+    parent: &mut ChildBuilder,
+    colors: &Colors,
+    theater_meshes: &theater_outside::TheaterMeshes,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let leg_color = Color::hex(colors.legs.clone()).unwrap();
+    let torso_color = Color::hex(colors.torso.clone()).unwrap();
+    let skin_color = Color::hex(colors.skin.clone()).unwrap();
+    let hair_color = Color::hex(colors.hair.clone()).unwrap();
+
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.kid_legs.clone(),
+        material: materials.add(leg_color.into()),
+        ..Default::default()
+    }).insert(KidPart);
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.kid_torso.clone(),
+        material: materials.add(torso_color.into()),
+        ..Default::default()
+    }).insert(KidPart);
+    parent.spawn_bundle(PbrBundle {
+        mesh: theater_meshes.kid_headhand.clone(),
+        material: materials.add(skin_color.into()),
+        ..Default::default()
+    }).insert(KidPart);
+    parent.spawn_bundle(PbrBundle {
+        mesh: if colors.is_long_hair {
+                  theater_meshes.kid_hairtwo.clone()
+              } else {
+                  theater_meshes.kid_hairone.clone()
+              },
+        material: materials.add(hair_color.into()),
+        ..Default::default()
+    }).insert(KidPart);
+
+    match colors.accessory {
+        Some(customize::Accessory::Cap) => {
+            // the grown up hat, dropped down to kid height
+            parent.spawn_bundle(PbrBundle {
+                mesh: theater_meshes.hat.clone(),
+                material: materials.add(torso_color.into()),
+                transform: Transform::from_xyz(0.0, customize::CAP_DROP, 0.0),
+                ..Default::default()
+            });
+        },
+        Some(customize::Accessory::Backpack) => {
+            parent.spawn_bundle(PbrBundle {
+                mesh: theater_meshes.kid_backpack.clone(),
+                material: materials.add(leg_color.into()),
+                transform: Transform::from_xyz(-0.5, 1.85, 0.0),
+                ..Default::default()
+            });
+        },
+        None => ()
     }
 }
 
 pub fn player_movement_update(
-    mut player: Query<(&mut Player, &mut Transform)>,
+    mut player: Query<(&mut Player, &mut Transform), Without<enemy::Enemy>>,
+    enemies: Query<(&enemy::Enemy, &Transform), Without<Player>>,
     settings: Res<game_settings::GameSettings>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
     game_state: ResMut<GameState>,
+    orders: Res<orders::Orders>,
+    squad: Res<formation::Squad>,
     level_info_assets: Res<Assets<asset_loader::LevelInfo>>,
     level_info_state: Res<asset_loader::LevelInfoState>, 
 ) {
-    match game_state.mode {
-        Mode::Follow => {
-            let mut controlling_kid_position = Vec3::default();
-            let mut move_away_from = vec!();
-            for (player, transform) in player.iter_mut() {
-                if player.kid ==  game_state.controlling {
-                    controlling_kid_position = transform.translation;
-                } else {
-                    move_away_from.push((player.kid, transform.translation)); 
-                }
+    let mut controlled_kids = vec!();
+    let mut controlling_kid_position = Vec3::default();
+    let mut controlling_kid_mode = MovementMode::Walk;
+    let mut move_away_from = vec!();
+    let mut followers = vec!();
+    for (player, transform) in player.iter_mut() {
+        if player.kid == game_state.controlling {
+            controlling_kid_position = transform.translation;
+            controlling_kid_mode = player.movement_mode;
+        } 
+        if game_state.is_controlled(player.kid) {
+            controlled_kids.push((transform.translation, player.movement_mode));
+        } else {
+            move_away_from.push((player.kid, transform.translation)); 
+            if !orders.is_busy(player.kid) && !player.is_hidden {
+                followers.push(player.kid);
             }
+        }
+    }
 
-            for (mut player, mut transform) in player.iter_mut() {
-                if player.kid ==  game_state.controlling {
-                    move_player_controlled_kid(&mut player, &mut transform, &game_state, &settings, 
-                                               &level_info_assets, &level_info_state, &time);
-                } else {
-                    move_non_player_controlled_kid(&mut player, &mut transform, &controlling_kid_position, 
-                                                   &move_away_from, &game_state, &settings, 
-                                                   &level_info_assets, &level_info_state, &time);
-                }
-            }
-        },
-        Mode::Switch => {
-            for (mut player, mut transform) in player.iter_mut() {
-                if player.kid ==  game_state.controlling {
-                    move_player_controlled_kid(&mut player, &mut transform, &game_state, &settings, 
-                                               &level_info_assets, &level_info_state, &time);
-                }
+    // same spot in line every frame so they don't keep swapping places
+    followers.sort();
+
+    // anything a guard can see right now, followers won't step into it
+    let cones = enemies.iter()
+                       .filter(|(enemy, _)| enemy.distracted_time <= 0.0)
+                       .filter(|(enemy, _)| {
+                           match enemy.enemy_spawn.enemy_type {
+                               enemy::EnemyType::Patrol(_) | enemy::EnemyType::Mom(_) => true,
+                               _ => false
+                           }
+                       })
+                       .map(|(_, transform)| enemy::vision_triangle(transform, &game_state, 1.0))
+                       .collect::<Vec::<_>>();
+
+    for (mut player, mut transform) in player.iter_mut() {
+        if player.is_hidden { continue; }
+
+        // kids with orders steer themselves the same way a person would
+        if game_state.is_controlled(player.kid) || orders.is_busy(player.kid) {
+            move_player_controlled_kid(&mut player, &mut transform, &game_state, &settings, 
+                                       &level_info_assets, &level_info_state, &time);
+            continue;
+        }
+
+        // the rest of the group copies whatever their leader is doing
+        let leader = 
+            match game_state.mode {
+                Mode::Follow => {
+                    let index = followers.iter().position(|kid| *kid == player.kid).unwrap_or(0);
+                    let target = squad.follower_target(controlling_kid_position, index, followers.len());
+                    Some((target, controlling_kid_mode, FORMATION_STOP_DISTANCE))
+                },
+                // nobody is playing as this kid in co-op so they tag along with whoever is closest
+                Mode::Switch if !game_state.co_op.is_empty() => {
+                    let translation = transform.translation;
+                    controlled_kids.iter()
+                                   .min_by(|(a, _), (b, _)| {
+                                       a.distance(translation).partial_cmp(&b.distance(translation))
+                                                              .unwrap_or(std::cmp::Ordering::Equal)
+                                   })
+                                   .map(|(position, mode)| (*position, *mode, CO_OP_FOLLOW_DISTANCE))
+                },
+                Mode::Switch => None
+            };
+
+        if let Some((target, leader_mode, stop_distance)) = leader {
+            if squad.holding {
+                player.velocity = Vec3::ZERO;
+                continue;
             }
+
+            player.movement_mode = leader_mode;
+            move_non_player_controlled_kid(&mut player, &mut transform, &target, stop_distance,
+                                           &move_away_from, &cones, &game_state, &settings, 
+                                           &level_info_assets, &level_info_state, &time);
         }
     }
 }
@@ -151,17 +308,20 @@ pub fn player_movement_update(
 pub fn move_non_player_controlled_kid(
     player: &mut Player, 
     mut transform: &mut Transform, 
-    controlling_kid_position: &Vec3,
+    target: &Vec3,
+    stop_distance: f32,
     move_away_from: &Vec::<(Kid, Vec3)>,
+    cones: &Vec::<(Vec2, Vec2, Vec2)>,
     game_state: &ResMut<GameState>,
     settings: &Res<game_settings::GameSettings>,
     level_info_assets: &Res<Assets<asset_loader::LevelInfo>>,
     level_info_state: &Res<asset_loader::LevelInfoState>, 
-    time: &Res<Time>,
+    time: &Res<replay::GameTime>,
 ) {
-    let direction = *controlling_kid_position - transform.translation;
+    let direction = *target - transform.translation;
+    let direction = Vec3::new(direction.x, 0.0, direction.z);
     let distance = direction.length();
-    if distance > 3.0 {
+    if distance > stop_distance {
         player.velocity += (direction * settings.player_speed) * time.delta_seconds();
     } else {
         player.velocity = Vec3::ZERO;
@@ -176,13 +336,32 @@ pub fn move_non_player_controlled_kid(
         }
     }
 
-    player.velocity = player.velocity.clamp_length_max(settings.player_speed * 0.1);
-    let new_translation = transform.translation + player.velocity;
+    let mut max_speed = settings.player_speed * 0.1 * player.movement_mode.speed();
+    if game_state.has_trait(player.kid, party::KidTrait::Fast) {
+        max_speed *= party::FAST_SPEED_SCALE;
+    }
+    player.velocity = player.velocity.clamp_length_max(max_speed);
+    let mut new_translation = transform.translation + player.velocity;
+
+    // steer along the edge of a guard's view rather than walk into it, and back off the way we came if
+    // there's no way around. if they're already in it they can keep going to get out
+    let in_cone = |position: Vec3| {
+        cones.iter().any(|cone| enemy::point_in_triangle(Vec2::new(position.x, position.z), *cone))
+    };
+    if in_cone(new_translation) && !in_cone(transform.translation) {
+        let step = player.velocity;
+        let detour = CONE_DETOUR_ANGLES.iter()
+                                       .map(|angle| Quat::from_rotation_y(*angle) * step)
+                                       .find(|step| !in_cone(transform.translation + *step));
+        player.velocity = detour.unwrap_or(-step);
+        new_translation = transform.translation + player.velocity;
+    }
 
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
         let temp_new_translation = new_translation;
-        let new_translation = level_collision::fit_in_level(&level_asset, game_state, transform.translation, new_translation);
+        let new_translation = level_collision::fit_in_level(&level_asset, game_state, transform.translation, new_translation,
+                                                              game_state.has_trait(player.kid, party::KidTrait::Small));
         if temp_new_translation.x != new_translation.x {
             player.velocity.x = 0.0;
         }
@@ -217,39 +396,48 @@ pub fn move_player_controlled_kid(
     settings: &Res<game_settings::GameSettings>,
     level_info_assets: &Res<Assets<asset_loader::LevelInfo>>,
     level_info_state: &Res<asset_loader::LevelInfoState>, 
-    time: &Res<Time>,
+    time: &Res<replay::GameTime>,
 ) {
+    let mut max_speed = settings.player_speed * MAX_SPEED_SCALE * player.movement_mode.speed();
+    if player.is_disguised {
+        max_speed *= DISGUISED_SPEED;
+    }
+    if game_state.has_trait(player.kid, party::KidTrait::Fast) {
+        max_speed *= party::FAST_SPEED_SCALE;
+    }
+
     if let Some(movement) = player.movement {
-        let mut acceleration = Vec3::default();
-        match movement {
-            Direction::Up => {
-                acceleration += Vec3::new(1.0, 0.0, 0.0);
-            },
-            Direction::Down => {
-                acceleration += Vec3::new(-1.0, 0.0, 0.0);
-            },
-            Direction::Right => {
-                acceleration += Vec3::new(0.0, 0.0, 1.0);
-            },
-            Direction::Left => {
-                acceleration += Vec3::new(0.0, 0.0, -1.0);
-            },
-            _ => ()
-        } 
+        let magnitude = movement.length().min(1.0);
+        let acceleration = movement.normalize() * player.movement_mode.speed();
 
         player.velocity += (acceleration * settings.player_speed) * time.delta_seconds();
+
+        // a half-pushed stick means a half-speed kid
+        max_speed *= magnitude;
         player.movement = None;
     } else {
         player.velocity *= settings.player_friction.powf(time.delta_seconds());
     }
 
-    player.velocity = player.velocity.clamp_length_max(settings.player_speed);
+    if player.movement_mode == MovementMode::Sprint && player.velocity.length() > 0.0001 {
+        player.stamina -= time.delta_seconds();
+        if player.stamina <= 0.0 {
+            println!("Out of breath");
+            player.stamina = 0.0;
+            player.movement_mode = MovementMode::Walk;
+        }
+    } else {
+        player.stamina = (player.stamina + STAMINA_RECOVERY * time.delta_seconds()).min(MAX_STAMINA);
+    }
+
+    player.velocity = player.velocity.clamp_length_max(max_speed);
     let new_translation = transform.translation + player.velocity;
 
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
         let temp_new_translation = new_translation;
-        let new_translation = level_collision::fit_in_level(&level_asset, &game_state, transform.translation, new_translation);
+        let new_translation = level_collision::fit_in_level(&level_asset, &game_state, transform.translation, new_translation,
+                                                              game_state.has_trait(player.kid, party::KidTrait::Small));
         if temp_new_translation.x != new_translation.x {
             player.velocity.x = 0.0;
         }
@@ -274,13 +462,38 @@ pub fn move_player_controlled_kid(
     }
 }
 
+// crouch down when sneaking and lean forward when sprinting
+pub fn update_kid_poses(
+    players: Query<(&Player, &Children)>,
+    mut pivots: Query<&mut Transform, With<KidPivot>>,
+    time: Res<replay::GameTime>,
+) {
+    for (player, children) in players.iter() {
+        let (height, lean) = 
+            match player.movement_mode {
+                MovementMode::Walk => (1.0, 0.0),
+                MovementMode::Sneak => (SNEAK_POSE_HEIGHT, 0.0),
+                MovementMode::Sprint => (1.0, -SPRINT_POSE_LEAN),
+            };
+        let target_rotation = Quat::from_rotation_z(lean);
+
+        for child in children.iter() {
+            if let Ok(mut transform) = pivots.get_mut(*child) {
+                let speed = (time.delta_seconds() * 10.0).min(1.0);
+                transform.scale.y += (height - transform.scale.y) * speed;
+                transform.rotation = transform.rotation.slerp(target_rotation, speed);
+            }
+        }
+    }
+}
+
 pub fn player_interact_check(
     players: Query<(Entity, &Transform, &Player), Without<enemy::Enemy>>,
     enemies: Query<(&Transform, &enemy::Enemy), Without<Player>>,
     game_state: Res<GameState>,
     mut follow_text_event_writer: EventWriter<follow_text::FollowTextEvent>,
     mut sleep: Local<f32>,
-    time: Res<Time>, 
+    time: Res<replay::GameTime>, 
 ) {
     *sleep -= time.delta_seconds();
     if *sleep > 0.0 {
@@ -297,17 +510,9 @@ pub fn player_interact_check(
         entity = Some(player_entity);
 
         for (enemy_transform, enemy) in enemies.iter() {
-            match enemy.enemy_spawn.enemy_type {
-                enemy::EnemyType::Ticket(actually_checks) => {
-                    if player_transform.translation.distance(enemy_transform.translation) <= DISTRACT_DISTANCE
-                    && !enemy.is_distracted
-                    && (!actually_checks || game_state.has_ticket.contains(&player.kid)) {
-                        text = Some(DISTRACT_TEXT);
-                    }
-                },
-                _ => ()
+            if can_distract(player, player_transform, enemy, enemy_transform, &game_state) {
+                text = Some(DISTRACT_TEXT);
             }
-
         }
     }
 
@@ -325,8 +530,28 @@ pub fn player_interact_check(
     *sleep = 1.0;
 }
 
+pub fn can_distract(
+    player: &Player,
+    player_transform: &Transform,
+    enemy: &enemy::Enemy,
+    enemy_transform: &Transform,
+    game_state: &GameState,
+) -> bool {
+    match enemy.enemy_spawn.enemy_type {
+        enemy::EnemyType::Ticket(actually_checks) => {
+            player_transform.translation.distance(enemy_transform.translation) <= DISTRACT_DISTANCE
+            && !enemy.is_distracted
+            && (!actually_checks
+                || game_state.admitted.contains(&player.kid)
+                || game_state.inventories.get(&player.kid).map(|i| i.contains(inventory::Item::Ticket)).unwrap_or(false))
+        },
+        _ => false
+    }
+}
+
 pub struct DistractEvent {
-    is_starting: bool
+    is_starting: bool,
+    kid: Kid,
 }
 
 pub fn handle_distract_event(
@@ -339,7 +564,7 @@ pub fn handle_distract_event(
     for event in distract_event_reader.iter() {
         println!("Got event");
         for (mut player, player_transform) in players.iter_mut() {
-            if player.kid == game_state.controlling {
+            if player.kid == event.kid {
                 println!("Got player");
                 for (entity, mut enemy, enemy_transform) in enemies.iter_mut() {
                     println!("checking enemy");
@@ -350,32 +575,22 @@ pub fn handle_distract_event(
                             enemy.is_distracted = true;
                             player.is_distracting = Some(entity);
 
-                            // SWITCH LOGIC oh my what have I done this is truly terrible
-
-                            let remaining_kids =
-                                game_state.last_positions
-                                          .iter()
-                                          .filter(|(_, position)| !position.is_none())
-                                          .map(|(kid, _)| kid)
-                                          .collect::<Vec::<_>>();
-                            let current = remaining_kids.iter().position(|k| **k == game_state.controlling).unwrap();
-
-                            let next_kid =
-                                if let Some(k) = remaining_kids.get(current + 1) {
-                                    k
-                                } else {
-                                    remaining_kids[0]
-                                };
-                                      
-                            game_state.controlling = *next_kid;
+                            // whoever did the distracting moves on to the next kid nobody is playing as
+                            game_state.switch_kid(player.kid);
                             player.velocity = Vec3::default();
 
                             follow_text.player_value = "".to_string();
                             player.movement = None;
                         } else {
-                            println!("Setting enemy not distracted");
-                            enemy.is_distracted = false;
                             player.is_distracting = None;
+                            if enemy.is_distracted && game_state.has_trait(player.kid, party::KidTrait::SmoothTalker) {
+                                // they keep chatting for a bit, the ticket timer wraps it up
+                                println!("Enemy still distracted for a bit");
+                                enemy.distracted_time = party::SMOOTH_TALKER_LINGER;
+                            } else {
+                                println!("Setting enemy not distracted");
+                                enemy.is_distracted = false;
+                            }
                         }
                     }
                 }
@@ -385,141 +600,104 @@ pub fn handle_distract_event(
 }
 
 pub fn player_input(
-    keyboard_input: Res<Input<KeyCode>>,
-    time: Res<Time>, 
-    mut player: Query<&mut Player>,
+    actions: Res<actions::ActionState>,
+    pad_actions: Res<actions::PadActions>,
+    command_mode: Res<orders::CommandMode>,
+    mut player: Query<(&mut Player, &Transform), Without<enemy::Enemy>>,
+    enemies: Query<(&enemy::Enemy, &Transform), Without<Player>>,
     camera: Query<&crate::camera::fly_camera::FlyCamera>,
-    mut action_buffer: Local<Option::<u128>>,
-    mut up_buffer: Local<Option::<u128>>,
-    mut down_buffer: Local<Option::<u128>>,
-    mut right_buffer: Local<Option::<u128>>,
-    mut left_buffer: Local<Option::<u128>>,
     mut game_state: ResMut<GameState>,
-    axes: Res<Axis<GamepadAxis>>,
-    buttons: Res<Input<GamepadButton>>,
-    gamepad: Option<Res<game_controller::GameController>>,
     mut follow_text: ResMut<follow_text::FollowText>,
-    mut distract_event_writer: EventWriter<DistractEvent>,
+    event_writers: (EventWriter<DistractEvent>, EventWriter<inventory::UseGadgetEvent>, EventWriter<hiding::HideEvent>),
 ) {
-    let time_buffer = 100;
+    let (mut distract_event_writer, mut use_gadget_event_writer, mut hide_event_writer) = event_writers;
 
     // this is for debugging. If we're flying, don't move the player
     if camera.iter().count() > 0 {
         return;
     }
 
-    let time_since_startup = time.time_since_startup().as_millis();
-    if let Some(time_since_up) = *up_buffer {
-        if time_since_startup - time_since_up > time_buffer {
-            *up_buffer = None;
-        }
+    // switching changes who's controlled partway through, only the kids we started with get input
+    // player one's buttons are busy giving orders while in command mode
+    let mut controllers = vec!();
+    if !command_mode.active {
+        controllers.push((game_state.controlling, &*actions));
     }
-    if let Some(time_since_down) = *down_buffer {
-        if time_since_startup - time_since_down > time_buffer {
-            *down_buffer = None;
-        }
-    }
-    if let Some(time_since_left) = *left_buffer {
-        if time_since_startup - time_since_left > time_buffer {
-            *left_buffer = None;
-        }
-    }
-    if let Some(time_since_right) = *right_buffer {
-        if time_since_startup - time_since_right > time_buffer {
-            *right_buffer = None;
-        }
-    }
-    if let Some(time_since_action) = *action_buffer {
-        if time_since_startup - time_since_action > time_buffer {
-            *action_buffer = None;
+    for (pad, kid) in game_state.co_op.iter() {
+        if let Some(pad_actions) = pad_actions.pads.get(pad) {
+            controllers.push((*kid, pad_actions));
         }
     }
 
-    let pressed_buttons = game_controller::get_pressed_buttons(&axes, &buttons, gamepad);
-    for mut player in player.iter_mut() {
-        if player.kid != game_state.controlling { continue; }
+    for (mut player, transform) in player.iter_mut() {
+        let actions = 
+            match controllers.iter().find(|(kid, _)| *kid == player.kid) {
+                Some((_, actions)) => *actions,
+                None => continue
+            };
 
-        if !action_buffer.is_none() {
-            continue;
-        }
-
-        if (keyboard_input.just_pressed(KeyCode::Space) 
-        || keyboard_input.just_pressed(KeyCode::Return) 
-        || keyboard_input.just_pressed(KeyCode::J) 
-        || keyboard_input.just_pressed(KeyCode::K) 
-        || pressed_buttons.contains(&game_controller::GameButton::Action)
-        || pressed_buttons.contains(&game_controller::GameButton::Switch))
-        && action_buffer.is_none() {
-            *action_buffer = Some(time.time_since_startup().as_millis());
-        }
-
-        if keyboard_input.just_pressed(KeyCode::K) || pressed_buttons.contains(&game_controller::GameButton::Switch) {
-                            // SWITCH LOGIC oh my what have I done this is truly terrible
-                            let remaining_kids =
-                                game_state.last_positions
-                                          .iter()
-                                          .filter(|(_, position)| !position.is_none())
-                                          .map(|(kid, _)| kid)
-                                          .collect::<Vec::<_>>();
-                            let current = remaining_kids.iter().position(|k| **k == game_state.controlling).unwrap();
-
-                            let next_kid =
-                                if let Some(k) = remaining_kids.get(current + 1) {
-                                    k
-                                } else {
-                                    remaining_kids[0]
-                                };
-                                      
-                            game_state.controlling = *next_kid;
+        if actions.just_pressed(actions::GameAction::Switch) {
+            let next_kid = game_state.switch_kid(player.kid);
             player.velocity = Vec3::default();
 
-            follow_text.player_value = "".to_string();
+            if player.kid == game_state.controlling || next_kid == game_state.controlling {
+                follow_text.player_value = "".to_string();
+            }
             player.movement = None;
+            player.movement_mode = MovementMode::Walk;
 
 
             distract_event_writer.send(DistractEvent {
                 is_starting: false,
+                kid: next_kid,
             });
         }
 
-        if keyboard_input.just_pressed(KeyCode::J) || pressed_buttons.contains(&game_controller::GameButton::Action) {
-            if follow_text.player_value == DISTRACT_TEXT {
+        if actions.just_pressed(actions::GameAction::Action) {
+            let distracting = enemies.iter()
+                                     .any(|(enemy, enemy_transform)| {
+                                         can_distract(&player, transform, enemy, enemy_transform, &game_state)
+                                     });
+            if distracting {
                 distract_event_writer.send(DistractEvent {
                     is_starting: true,
+                    kid: player.kid,
                 });
+            } else {
+                hide_event_writer.send(hiding::HideEvent { kid: player.kid });
             }
         }
 
-        let mut move_dir = None;
-        if (keyboard_input.pressed(KeyCode::W) 
-         || keyboard_input.pressed(KeyCode::Up) 
-         || pressed_buttons.contains(&game_controller::GameButton::Up))
-           && up_buffer.is_none() {
-            move_dir = Some(Direction::Up); 
-            *up_buffer = Some(time.time_since_startup().as_millis());
+        if actions.just_pressed(actions::GameAction::Gadget) {
+            use_gadget_event_writer.send(inventory::UseGadgetEvent { kid: player.kid });
         }
-        if (keyboard_input.pressed(KeyCode::S) 
-           || keyboard_input.pressed(KeyCode::Down) 
-           || pressed_buttons.contains(&game_controller::GameButton::Down))
-           && down_buffer.is_none() {
-            move_dir = Some(Direction::Down); 
-            *down_buffer = Some(time.time_since_startup().as_millis());
-        }
-        if (keyboard_input.pressed(KeyCode::A) 
-           || keyboard_input.pressed(KeyCode::Left) 
-           || pressed_buttons.contains(&game_controller::GameButton::Left))
-           && left_buffer.is_none() {
-            move_dir = Some(Direction::Left); 
-            *left_buffer = Some(time.time_since_startup().as_millis());
+
+        if actions.just_pressed(actions::GameAction::CycleGadget) {
+            let kid = player.kid;
+            game_state.inventories.entry(kid).or_insert_with(inventory::Inventory::default).cycle();
         }
-        if (keyboard_input.pressed(KeyCode::D) 
-           || keyboard_input.pressed(KeyCode::Right) 
-           || pressed_buttons.contains(&game_controller::GameButton::Right))
-           && right_buffer.is_none() {
-            move_dir = Some(Direction::Right); 
-            *right_buffer= Some(time.time_since_startup().as_millis());
+
+        // stay put until they come back out
+        if player.is_hidden {
+            player.movement = None;
+            continue;
         }
 
-        player.movement = move_dir;
+        player.movement_mode = 
+            if actions.pressed(actions::GameAction::Sneak) {
+                MovementMode::Sneak
+            } else if actions.pressed(actions::GameAction::Sprint) 
+                   && (player.movement_mode == MovementMode::Sprint || player.stamina > STAMINA_TO_SPRINT) {
+                MovementMode::Sprint
+            } else {
+                MovementMode::Walk
+            };
+
+        let movement = actions.movement;
+        player.movement = if movement != Vec2::ZERO {
+                              Some(Vec3::new(movement.y, 0.0, movement.x))
+                          } else {
+                              None
+                          };
     }
 }
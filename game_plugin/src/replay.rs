@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy::utils::Instant;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::{actions, customize, rng, AppState, Colors, GameState, Kid};
+
+// while recording or playing back every frame is exactly this long, otherwise the
+// same input wouldn't always move everyone the same distance
+pub static FIXED_STEP: f32 = 1.0 / 60.0;
+static DEFAULT_REPLAY_FILE: &str = "replay.ron";
+// bump this when the format changes, see REPLAYS.md
+static REPLAY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub seed: u64,
+    pub step: f32,
+    pub start: StartState,
+    pub frames: Vec::<RecordedFrame>,
+}
+
+// the parts of GameState that aren't the same on every machine when the game boots
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartState {
+    pub kid_colors: Vec::<(Kid, Colors)>,
+    pub saved_kids: customize::SavedKids,
+}
+
+// player one's actions for one frame, empty frames are just ()
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pressed: Vec::<actions::GameAction>,
+    #[serde(default, skip_serializing_if = "is_still")]
+    pub movement: Vec2,
+    // the seed got changed on the main menu
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option::<u64>,
+}
+
+fn is_still(movement: &Vec2) -> bool {
+    *movement == Vec2::ZERO
+}
+
+impl Recording {
+    pub fn load(file: &str) -> Option::<Recording> {
+        let contents = match std::fs::read_to_string(file) {
+                           Ok(contents) => contents,
+                           Err(e) => {
+                               println!("Couldn't open replay {}: {:?}", file, e);
+                               return None;
+                           }
+                       };
+
+        match ron::de::from_str::<Recording>(&contents) {
+            Ok(recording) if recording.version == REPLAY_VERSION => Some(recording),
+            Ok(recording) => {
+                println!("Replay {} is version {}, this build plays version {}", file, recording.version, REPLAY_VERSION);
+                None
+            },
+            Err(e) => {
+                println!("Couldn't read replay {}: {:?}", file, e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, file: &str) {
+        // one frame per line keeps it diffable without being huge
+        let config = ron::ser::PrettyConfig::new().with_depth_limit(2);
+        match ron::ser::to_string_pretty(self, config) {
+            Ok(contents) => {
+                match std::fs::write(file, contents) {
+                    Ok(_) => println!("Saved {} frames to {}", self.frames.len(), file),
+                    Err(e) => println!("Couldn't save replay {}: {:?}", file, e),
+                }
+            },
+            Err(e) => println!("Couldn't save replay {}: {:?}", file, e)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ReplayMode {
+    Off,
+    Recording,
+    Playing,
+}
+
+pub struct Replay {
+    pub mode: ReplayMode,
+    file: String,
+    recording: Recording,
+    next_frame: usize,
+    last_seed: u64,
+}
+
+impl Replay {
+    fn new(mode: ReplayMode, file: String, recording: Recording) -> Self {
+        Replay {
+            mode,
+            file,
+            last_seed: recording.seed,
+            recording,
+            next_frame: 0,
+        }
+    }
+
+    // --record [file] saves everything that happens, --replay [file] plays it back
+    pub fn from_args() -> Self {
+        let args = std::env::args().collect::<Vec::<_>>();
+        let file_after = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .map(|i| {
+                    args.get(i + 1)
+                        .filter(|file| !file.starts_with("--"))
+                        .cloned()
+                        .unwrap_or_else(|| DEFAULT_REPLAY_FILE.to_string())
+                })
+        };
+
+        let empty = Recording {
+                        version: REPLAY_VERSION,
+                        seed: 0,
+                        step: FIXED_STEP,
+                        start: StartState::default(),
+                        frames: vec!(),
+                    };
+
+        if let Some(file) = file_after("--replay") {
+            match Recording::load(&file) {
+                Some(recording) => {
+                    println!("Playing back {} ({} frames)", file, recording.frames.len());
+                    return Replay::new(ReplayMode::Playing, file, recording);
+                },
+                None => println!("Not playing {}, starting normally", file),
+            }
+        }
+
+        match file_after("--record") {
+            Some(file) => {
+                println!("Recording to {}", file);
+                Replay::new(ReplayMode::Recording, file, empty)
+            },
+            None => Replay::new(ReplayMode::Off, DEFAULT_REPLAY_FILE.to_string(), empty)
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.mode != ReplayMode::Off
+    }
+
+    // a replay has to start from the seed it was recorded with
+    pub fn seed(&self) -> Option::<u64> {
+        match self.mode {
+            ReplayMode::Playing => Some(self.recording.seed),
+            _ => None
+        }
+    }
+
+    // called while the game is being built, before anything has used the starting state
+    pub fn start(&mut self, rng: &rng::GameRng, kid_colors: &mut HashMap<Kid, Colors>, saved_kids: &mut customize::SavedKids) {
+        match self.mode {
+            ReplayMode::Playing => {
+                *kid_colors = self.recording.start.kid_colors.iter().cloned().collect();
+                *saved_kids = self.recording.start.saved_kids.clone();
+            },
+            ReplayMode::Recording => {
+                let mut colors = kid_colors.iter().map(|(k, c)| (*k, c.clone())).collect::<Vec::<_>>();
+                colors.sort_by_key(|(k, _)| *k);
+
+                self.recording.seed = rng.seed;
+                self.last_seed = rng.seed;
+                self.recording.start = StartState {
+                                           kid_colors: colors,
+                                           saved_kids: saved_kids.clone(),
+                                       };
+            },
+            ReplayMode::Off => ()
+        }
+    }
+}
+
+// stands in for bevy's Time so a recorded run moves the same way when it plays back
+#[derive(Default)]
+pub struct GameTime {
+    delta_seconds: f32,
+}
+
+impl GameTime {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta_seconds
+    }
+}
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameTime>()
+           .add_system_to_stage(CoreStage::PreUpdate, update_game_time.system())
+           .add_system_to_stage(CoreStage::PreUpdate, play_back_input.system().after("update_action_state"))
+           .add_system_to_stage(CoreStage::PreUpdate, record_input.system().after("update_action_state"))
+           .add_system_to_stage(CoreStage::Last, save_recording.system())
+           .add_system_to_stage(CoreStage::Last, limit_frame_rate.system());
+    }
+}
+
+pub fn update_game_time(
+    time: Res<Time>,
+    replay: Res<Replay>,
+    mut game_time: ResMut<GameTime>,
+) {
+    game_time.delta_seconds = if replay.is_active() { FIXED_STEP } else { time.delta_seconds() };
+}
+
+// how long assets take to load changes from run to run so those frames don't count
+fn is_loading(state: &State<AppState>) -> bool {
+    *state.current() == AppState::Loading
+}
+
+pub fn record_input(
+    mut replay: ResMut<Replay>,
+    action_state: Res<actions::ActionState>,
+    rng: Res<rng::GameRng>,
+    state: Res<State<AppState>>,
+) {
+    if replay.mode != ReplayMode::Recording || is_loading(&state) {
+        return;
+    }
+
+    let seed = if rng.seed != replay.last_seed { Some(rng.seed) } else { None };
+    replay.last_seed = rng.seed;
+
+    replay.recording.frames.push(RecordedFrame {
+        pressed: action_state.pressed_actions(),
+        movement: action_state.movement,
+        seed,
+    });
+}
+
+pub fn play_back_input(
+    mut replay: ResMut<Replay>,
+    mut action_state: ResMut<actions::ActionState>,
+    mut pad_actions: ResMut<actions::PadActions>,
+    mut rng: ResMut<rng::GameRng>,
+    mut game_state: ResMut<GameState>,
+    state: Res<State<AppState>>,
+) {
+    if replay.mode != ReplayMode::Playing || is_loading(&state) {
+        return;
+    }
+
+    match replay.recording.frames.get(replay.next_frame).cloned() {
+        Some(frame) => {
+            if let Some(seed) = frame.seed {
+                rng.set_seed(seed);
+                game_state.reroll_kids(&mut rng);
+            }
+
+            action_state.set_from_replay(&frame.pressed, frame.movement);
+            // only player one is recorded
+            pad_actions.pads.clear();
+            replay.next_frame += 1;
+        },
+        None => {
+            println!("Replay finished after {} frames, handing control back", replay.next_frame);
+            replay.mode = ReplayMode::Off;
+        }
+    }
+}
+
+// saves when the game closes, F9 saves what's there so far in case it's about to crash
+pub fn save_recording(
+    replay: Res<Replay>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    if replay.mode != ReplayMode::Recording {
+        return;
+    }
+
+    if exit_events.iter().count() > 0 || keyboard_input.just_pressed(KeyCode::F9) {
+        replay.recording.save(&replay.file);
+    }
+}
+
+// every frame is FIXED_STEP long while recording so don't let it run faster than that
+pub fn limit_frame_rate(
+    replay: Res<Replay>,
+    mut last_frame: Local<Option::<Instant>>,
+) {
+    if !replay.is_active() {
+        return;
+    }
+
+    if let Some(last) = *last_frame {
+        let step = std::time::Duration::from_secs_f32(FIXED_STEP);
+        let elapsed = last.elapsed();
+        if elapsed < step {
+            std::thread::sleep(step - elapsed);
+        }
+    }
+
+    *last_frame = Some(Instant::now());
+}
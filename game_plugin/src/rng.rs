@@ -0,0 +1,51 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+// every random thing in the game comes out of this so a seed always plays out the same
+pub struct GameRng {
+    pub seed: u64,
+    rng: StdRng,
+    // the kids' looks get their own stream so rolling them doesn't shift anything else on the seed
+    kids: StdRng,
+}
+
+// mixed into the seed for the kids' stream so it doesn't line up with the gameplay one
+static KIDS_STREAM: u64 = 0x6b69_6473;
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        println!("Seed: {}", seed);
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            kids: StdRng::seed_from_u64(seed ^ KIDS_STREAM),
+        }
+    }
+
+    // --seed 1234 or --seed=1234 on the command line, otherwise a fresh one
+    pub fn from_args() -> Self {
+        let seed = crate::arg_value("--seed").and_then(|s| s.parse::<u64>().ok());
+
+        match seed {
+            Some(seed) => GameRng::new(seed),
+            None => GameRng::new(rand::thread_rng().gen::<u64>() % 1_000_000)
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        *self = GameRng::new(seed);
+    }
+
+    // back to the start of the seed, a new run should play out the same as the last one with it.
+    // the kids already have their colors so their stream is left where it is
+    pub fn restart(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn kids(&mut self) -> &mut StdRng {
+        &mut self.kids
+    }
+}
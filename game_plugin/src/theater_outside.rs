@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use crate::{asset_loader, player, camera, level_collision, enemy, AppState, GameState,
-            follow_text, Kid, level_collision::CollisionShape, cutscene, cutscene::CutsceneSegment };
+            follow_text, level_collision::CollisionShape, inventory, cutscene, cutscene::CutsceneSegment, flags, replay};
 
 pub struct LevelReady(pub bool);
 pub struct TheaterOutsidePlugin;
@@ -29,6 +29,7 @@ impl Plugin for TheaterOutsidePlugin {
                     .with_system(follow_text::create_follow_text.system().after("loading_level"))
 
                     .with_system(enemy::spawn_enemies.system().after("loading_level"))
+                    .with_system(inventory::spawn_pickups.system().after("loading_level"))
             )
             .add_system_set(
                 SystemSet::on_exit(crate::AppState::InGame)
@@ -43,6 +44,7 @@ impl Plugin for TheaterOutsidePlugin {
                     .with_system(player::player_input.system())
                     .with_system(check_for_level_exit.system())
                     .with_system(player::player_movement_update.system())
+                    .with_system(player::update_kid_poses.system())
                     .with_system(listen_for_level_reset.system())
                //.with_system(holdable::lift_holdable.system().label("handle_lift_events"))
             );
@@ -73,6 +75,13 @@ pub struct TheaterMeshes {
     pub face: Handle<Mesh>,
     pub face_material: Handle<StandardMaterial>,
     pub talk_material: Handle<StandardMaterial>,
+    // the cutscene portraits' other faces, see cutscene::Emotion
+    pub happy_face_material: Handle<StandardMaterial>,
+    pub happy_talk_material: Handle<StandardMaterial>,
+    pub worried_face_material: Handle<StandardMaterial>,
+    pub worried_talk_material: Handle<StandardMaterial>,
+    pub shocked_face_material: Handle<StandardMaterial>,
+    pub shocked_talk_material: Handle<StandardMaterial>,
 
     pub kid_legs: Handle<Mesh>,
     pub kid_torso: Handle<Mesh>,
@@ -80,6 +89,19 @@ pub struct TheaterMeshes {
     pub kid_hairone: Handle<Mesh>,
     pub kid_hairtwo: Handle<Mesh>,
     pub kid_face: Handle<Mesh>,
+    pub kid_backpack: Handle<Mesh>,
+}
+
+impl TheaterMeshes {
+    // eyes and then the talking mouth for a face
+    pub fn face_materials(&self, emotion: cutscene::Emotion) -> (Handle<StandardMaterial>, Handle<StandardMaterial>) {
+        match emotion {
+            cutscene::Emotion::Neutral => (self.face_material.clone(), self.talk_material.clone()),
+            cutscene::Emotion::Happy => (self.happy_face_material.clone(), self.happy_talk_material.clone()),
+            cutscene::Emotion::Worried => (self.worried_face_material.clone(), self.worried_talk_material.clone()),
+            cutscene::Emotion::Shocked => (self.shocked_face_material.clone(), self.shocked_talk_material.clone()),
+        }
+    }
 }
 
 pub fn check_for_level_exit(
@@ -91,27 +113,34 @@ pub fn check_for_level_exit(
     mut state: ResMut<State<AppState>>,
 ) {
 
-    if game_state.has_seen_half_of_movie {
+    if game_state.flags.is_set(flags::HAS_SEEN_HALF_OF_MOVIE) {
         current_cutscene.trigger(
             vec!(
-                cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::A, cutscene::Position::Left),
-                cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::B, cutscene::Position::Center_Left),
-                cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::C, cutscene::Position::Center_Right),
+                // everyone else piles in while D is already celebrating
+                cutscene::CutsceneSegment::Track("everyone".to_string(), vec!(
+                    cutscene::CutsceneSegment::Delay(0.4),
+                    cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::A, cutscene::Position::Left),
+                    cutscene::CutsceneSegment::Delay(0.3),
+                    cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::B, cutscene::Position::Center_Left),
+                    cutscene::CutsceneSegment::Delay(0.3),
+                    cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::C, cutscene::Position::Center_Right),
+                )),
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::D, cutscene::Position::Right),
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::D),
-                cutscene::CutsceneSegment::Textbox("We did it!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.1".to_string()),
+                cutscene::CutsceneSegment::Sync,
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::C),
-                cutscene::CutsceneSegment::Textbox("That part where Ferris wrote a macro from scratch was amazing!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.2".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("Not as amazing as when Ferris used the 'unsafe' keyword!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.3".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::B),
-                cutscene::CutsceneSegment::Textbox("I can't believe that movie was rated PG!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.4".to_string()),
 
                 cutscene::CutsceneSegment::NoTalking,
-                cutscene::CutsceneSegment::Textbox("HEY!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.5".to_string()),
 
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::A, cutscene::Position::Clear),
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::B, cutscene::Position::Clear),
@@ -120,76 +149,77 @@ pub fn check_for_level_exit(
 
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::Mom, cutscene::Position::Center),
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::Mom),
-                cutscene::CutsceneSegment::Textbox("Just where do you think you're going?".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.6".to_string()),
 
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::A, cutscene::Position::Left),
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("Oh no! We've been caught!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.7".to_string()),
 
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::D, cutscene::Position::Right),
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::D),
-                cutscene::CutsceneSegment::Textbox("Umm... sorry everyone".to_string()),
-                cutscene::CutsceneSegment::Textbox("This is...".to_string()),
-                cutscene::CutsceneSegment::Textbox("This is... my mom".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.8".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.9".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.10".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("WHAT!?".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.11".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::Mom),
-                cutscene::CutsceneSegment::Textbox("That's right! And you were going to leave without saying good bye?".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.12".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::B, cutscene::Position::Center_Left),
                 cutscene::CutsceneSegment::CharacterPosition(cutscene::Character::C, cutscene::Position::Center_Right),
-                cutscene::CutsceneSegment::Textbox("I don't understand..  ".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.13".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::D),
-                cutscene::CutsceneSegment::Textbox("I guess I should have mentioned that".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.14".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::Mom),
-                cutscene::CutsceneSegment::Textbox("You kids cracked us up, pretending to sneak around".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.15".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::C),
-                cutscene::CutsceneSegment::Textbox("You saw us!?".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.16".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::Mom),
-                cutscene::CutsceneSegment::Textbox("Of course we did!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.17".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::C),
-                cutscene::CutsceneSegment::Textbox("Oh no, we're in trouble now!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.18".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::Mom),
-                cutscene::CutsceneSegment::Textbox("Ha ha, don't you know you four can watch movies for free here?".to_string()),
-                cutscene::CutsceneSegment::Textbox("All of the staff knows".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.19".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.20".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("Why didn't you tell us??".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.21".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::D),
-                cutscene::CutsceneSegment::Textbox("Because you all were so excited to sneak in!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.22".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("Wow, so... this whole thing was just an Illusion of Security!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.23".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::B),
-                cutscene::CutsceneSegment::Textbox("That's the theme of the jam!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.24".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::A),
-                cutscene::CutsceneSegment::Textbox("what?".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.25".to_string()),
 
                 cutscene::CutsceneSegment::SetTalking(cutscene::Character::B),
-                cutscene::CutsceneSegment::Textbox("what??".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.26".to_string()),
 
                 cutscene::CutsceneSegment::NoTalking,
 
-                cutscene::CutsceneSegment::Textbox("AND THAT'S THE END OF THE GAME".to_string()),
-                cutscene::CutsceneSegment::Textbox("YOU DID IT, THANK YOU FOR PLAYING".to_string()),
-                cutscene::CutsceneSegment::Textbox("THE GAME CRASHES NOW, BYE!".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.27".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.28".to_string()),
+                cutscene::CutsceneSegment::Textbox("ending.29".to_string()),
 
                 cutscene::CutsceneSegment::Crash,
             ),
             cutscene::Level::Movie
         );
+        current_cutscene.set_id("ending");
         state.push(AppState::Cutscene).unwrap();
         return;
     }
@@ -236,6 +266,7 @@ fn load_assets(
     mut theater_meshes: ResMut<TheaterMeshes>,
     mut level_info_state: ResMut<asset_loader::LevelInfoState>, 
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut loading: ResMut<asset_loader::AssetsLoading>,
 ) {
     println!("Adding theater assets");
@@ -264,6 +295,9 @@ fn load_assets(
 
     theater_meshes.hairtwo = asset_server.load("models/person.glb#Mesh12/Primitive0");
 
+    // no model for this one, a box on their back does the job
+    theater_meshes.kid_backpack = meshes.add(Mesh::from(shape::Box::new(0.4, 1.1, 1.2)));
+
     let texture_handle = asset_server.load("models/theater_outside.png");
     theater_meshes.outside_material = materials.add(StandardMaterial {
         base_color_texture: Some(texture_handle.clone()),
@@ -300,6 +334,42 @@ fn load_assets(
         ..Default::default()
     });
 
+    let texture_handle = asset_server.load("models/EyesHappy.png");
+    theater_meshes.happy_face_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
+    let texture_handle = asset_server.load("models/MouthHappy.png");
+    theater_meshes.happy_talk_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
+    let texture_handle = asset_server.load("models/EyesWorried.png");
+    theater_meshes.worried_face_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
+    let texture_handle = asset_server.load("models/MouthWorried.png");
+    theater_meshes.worried_talk_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
+    let texture_handle = asset_server.load("models/EyesShocked.png");
+    theater_meshes.shocked_face_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
+    let texture_handle = asset_server.load("models/MouthShocked.png");
+    theater_meshes.shocked_talk_material = materials.add(StandardMaterial {
+        base_color_texture: Some(texture_handle.clone()),
+        ..Default::default()
+    });
+
     loading.asset_handles.push(theater_meshes.outside.clone_untyped());
 
     loading.asset_handles.push(theater_meshes.lobby.clone_untyped());
@@ -339,7 +409,7 @@ fn listen_for_level_reset(
 fn reset_level( 
     mut state: ResMut<State<crate::AppState>>,
     mut timer: Local<f32>,
-    time: Res<Time>,
+    time: Res<replay::GameTime>,
 ) {
     *timer += time.delta_seconds();
 
@@ -356,6 +426,7 @@ fn cleanup_environment(
     enemy: Query<Entity, With<enemy::Enemy>>,
     camera: Query<Entity, With<camera::MainCamera>>,
     collision_meshes: Query<Entity, With<level_collision::DebugLevelCollisionMesh>>, 
+    items: Query<Entity, With<inventory::ItemMarker>>,
 ) {
     for entity in level_mesh.iter() {
         commands.entity(entity).despawn_recursive();
@@ -376,6 +447,10 @@ fn cleanup_environment(
     for entity in collision_meshes.iter() {
         commands.entity(entity).despawn_recursive();
     }
+
+    for entity in items.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
 struct TheaterOutside { }
@@ -391,6 +466,7 @@ fn load_level(
     mut level_info_state: ResMut<asset_loader::LevelInfoState>, 
     level_info_assets: ResMut<Assets<asset_loader::LevelInfo>>,
     mut game_state: ResMut<GameState>,
+    mut rng: ResMut<crate::rng::GameRng>,
     mut state: ResMut<State<crate::AppState>>,
 
 ) {
@@ -399,6 +475,7 @@ fn load_level(
     let levels_asset = level_info_assets.get(&level_info_state.handle);
     if let Some(level_asset) = levels_asset  {
         println!("Level loaded");
+        game_state.load_roster(level_asset, &mut rng);
     } else {
         // try again later?
         println!("failed to load level");
@@ -431,12 +508,7 @@ fn load_level(
                 });
             }).id();
 
-    game_state.last_positions = [
-                (Kid::A, Some(Vec3::new(0.0, 0.0, 0.0))), 
-                (Kid::B, Some(Vec3::new(0.0, 0.0, -1.0))),
-                (Kid::C, Some(Vec3::new(0.0, 0.0, 0.5))), 
-                (Kid::D, Some(Vec3::new(0.0, 0.0, -0.5))),
-    ].iter().cloned().collect();
+    game_state.place_party(Vec3::ZERO);
 
     player::spawn_player(&mut commands, &mut materials, &mut meshes, 
                          &person_meshes, &theater_meshes, &game_state);